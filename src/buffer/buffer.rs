@@ -1,7 +1,9 @@
 use std::{
+    collections::HashMap,
     ops::Deref,
     path::Path,
     process::{Command, Stdio},
+    time::{Duration, SystemTime},
 };
 
 use noa_editorconfig::EditorConfig;
@@ -9,9 +11,12 @@ use noa_languages::{get_language_by_name, tree_sitter, Language};
 
 use crate::{
     cursor::{Cursor, CursorId, CursorSet, Position, Range},
+    encoding::{encode, Encoding, FileEncoding},
+    extras::block_selection::BlockSelection,
     mut_raw_buffer::{Change, MutRawBuffer},
     raw_buffer::RawBuffer,
     syntax::{ParserError, Syntax},
+    undo_tree::{UndoBranch, UndoNodeId, UndoStep, UndoTree},
 };
 
 #[derive(Clone, PartialEq, Eq, Debug)]
@@ -20,19 +25,20 @@ pub struct TextEdit {
     pub new_text: String,
 }
 
-struct UndoState {
-    buf: RawBuffer,
-    cursors: CursorSet,
-}
-
 pub struct Buffer {
     lang: &'static Language,
-    syntax: Option<Syntax>,
+    pub(crate) syntax: Option<Syntax>,
     pub(crate) buf: MutRawBuffer,
     pub(crate) cursors: CursorSet,
     pub(crate) config: EditorConfig,
-    undo_stack: Vec<UndoState>,
-    redo_stack: Vec<UndoState>,
+    encoding: FileEncoding,
+    undo_tree: UndoTree,
+    /// The cursors at the last `save_undo` call.
+    undo_cursors: CursorSet,
+    pub(crate) block_selection: Option<BlockSelection>,
+    /// The selections visited by `expand_selections` for each cursor. The
+    /// last one is the current selection.
+    pub(crate) expanded_selections: HashMap<CursorId, Vec<Range>>,
 }
 
 impl Buffer {
@@ -43,8 +49,11 @@ impl Buffer {
             buf: MutRawBuffer::new(),
             cursors: CursorSet::new(),
             config: EditorConfig::default(),
-            undo_stack: Vec::new(),
-            redo_stack: Vec::new(),
+            encoding: FileEncoding::UTF8,
+            undo_tree: UndoTree::new(),
+            undo_cursors: CursorSet::new(),
+            block_selection: None,
+            expanded_selections: HashMap::new(),
         }
     }
 
@@ -59,9 +68,19 @@ impl Buffer {
         }
     }
 
+    /// Reads a text in the detected encoding.
     pub fn from_reader<T: std::io::Read>(reader: T) -> std::io::Result<Buffer> {
+        Buffer::from_reader_with_encoding(reader, None)
+    }
+
+    pub fn from_reader_with_encoding<T: std::io::Read>(
+        reader: T,
+        encoding: Option<&'static Encoding>,
+    ) -> std::io::Result<Buffer> {
+        let (raw_buffer, encoding) = RawBuffer::from_reader_with_encoding(reader, encoding)?;
         Ok(Buffer {
-            buf: MutRawBuffer::from_reader(reader)?,
+            buf: MutRawBuffer::from_raw_buffer(raw_buffer),
+            encoding,
             ..Default::default()
         })
     }
@@ -77,6 +96,14 @@ impl Buffer {
         Ok(())
     }
 
+    pub fn encoding(&self) -> FileEncoding {
+        self.encoding
+    }
+
+    pub fn set_encoding(&mut self, encoding: FileEncoding) {
+        self.encoding = encoding;
+    }
+
     pub fn editorconfig(&self) -> &EditorConfig {
         &self.config
     }
@@ -109,13 +136,56 @@ impl Buffer {
         self.lang
     }
 
+    /// Returns the language at `pos`, e.g. JavaScript in `<script>` in an
+    /// HTML file.
+    pub fn language_at(&self, pos: Position) -> &'static Language {
+        let syntax = match self.syntax.as_ref() {
+            Some(syntax) => syntax,
+            None => return self.lang,
+        };
+
+        let point = pos.into();
+        let mut node = syntax
+            .tree()
+            .root_node()
+            .descendant_for_point_range(point, point);
+        while let Some(current) = node {
+            let parent = current.parent();
+            let injection = self.lang.injections.iter().find(|injection| {
+                injection.kind == current.kind()
+                    && parent.map(|parent| parent.kind()) == Some(injection.parent_kind)
+            });
+
+            if let Some(lang) = injection.and_then(|i| get_language_by_name(i.language)) {
+                return lang;
+            }
+
+            node = parent;
+        }
+
+        self.lang
+    }
+
     pub fn set_language(&mut self, lang: &'static Language) -> Result<(), ParserError> {
-        let syntax = Syntax::new(lang)?;
+        let mut syntax = Syntax::new(lang)?;
+        syntax.parse_fully(self.buf.raw_buffer());
+        self.buf.clear_changes();
         self.lang = lang;
         self.syntax = Some(syntax);
         Ok(())
     }
 
+    /// Updates the syntax tree with the recorded changes. They're consumed as
+    /// in `clear_recorded_changes`.
+    pub fn update_syntax_tree(&mut self) {
+        let changes = self.buf.clear_changes();
+        if let Some(syntax) = self.syntax.as_mut() {
+            if !changes.is_empty() {
+                syntax.parse_incrementally(self.buf.raw_buffer(), &changes);
+            }
+        }
+    }
+
     pub fn cursors(&self) -> &[Cursor] {
         self.cursors.as_slice()
     }
@@ -280,19 +350,19 @@ impl Buffer {
         // Write into a temporary file and then (hopefully atomically) move it
         // to `path`.
         let mut f = tempfile::NamedTempFile::new()?;
-        self.buf.write_to(&mut f)?;
+        self.write_contents(&mut f)?;
         f.persist(path)?;
 
         Ok(())
     }
 
     pub fn save_to_file(&mut self, path: &Path) -> std::io::Result<()> {
-        self.ensure_insert_final_newline();
+        self.format_on_save();
         self.save_to_file_without_formatting(path)
     }
 
     pub fn save_to_file_with_sudo(&mut self, path: &Path) -> std::io::Result<()> {
-        self.ensure_insert_final_newline();
+        self.format_on_save();
 
         let magic = "sudo is available without password";
         let check_sudo_output = Command::new("sudo")
@@ -327,13 +397,26 @@ impl Buffer {
             .spawn()?;
 
         let stdin = use_sudo.stdin.take().unwrap();
-        self.buf.write_to(stdin)?;
+        self.write_contents(stdin)?;
 
         Ok(())
     }
 
+    /// Writes the text in the file's encoding and line ending.
+    fn write_contents(&self, mut writer: impl std::io::Write) -> std::io::Result<()> {
+        if self.encoding == FileEncoding::UTF8 {
+            return self.buf.write_to_with_eol(writer, self.config.end_of_line);
+        }
+
+        let mut text = Vec::new();
+        self.buf
+            .write_to_with_eol(&mut text, self.config.end_of_line)?;
+        let text = String::from_utf8(text).unwrap();
+        writer.write_all(&encode(&text, self.encoding)?)
+    }
+
     /// Inserts a newline if the buffer doesn't end with a newline.
-    fn ensure_insert_final_newline(&mut self) {
+    pub(crate) fn ensure_insert_final_newline(&mut self) {
         let last_y = self.num_lines() - 1;
         let last_x = self.line_len(last_y);
         if self.config.insert_final_newline && last_x > 0 {
@@ -361,11 +444,13 @@ impl Buffer {
 
     /// A special insertion method for pasting different texts for each cursor.
     pub fn insert_multiple(&mut self, texts: &[&str]) {
-        if texts.len() == self.cursors().len() {
+        if texts.len() != self.cursors().len() {
             self.insert(&texts.join("\n"));
+            return;
         }
 
-        let mut texts_iter = texts.iter();
+        // `foreach` visits cursors from the bottom.
+        let mut texts_iter = texts.iter().rev();
         self.cursors.foreach(|c, past_cursors| {
             self.buf
                 .edit_at_cursor(c, past_cursors, texts_iter.next().unwrap());
@@ -443,50 +528,140 @@ impl Buffer {
 
     /// Returns true if the buffer was modified since the last undo save.
     pub fn save_undo(&mut self) -> bool {
-        if let Some(last_undo) = self.undo_stack.last() {
-            if last_undo.buf == *self.buf.raw_buffer() {
-                // No changes.
-                return false;
-            }
+        let changes = self.buf.take_undo_changes();
+        let cursors_before = std::mem::replace(&mut self.undo_cursors, self.cursors.clone());
+        if changes.is_empty() {
+            return false;
         }
 
-        self.redo_stack.clear();
-        self.undo_stack.push(UndoState {
-            buf: self.buf.raw_buffer().clone(),
-            cursors: self.cursors.clone(),
-        });
+        self.undo_tree.push(
+            changes,
+            cursors_before,
+            self.cursors.clone(),
+            SystemTime::now(),
+        );
 
         true
     }
 
-    /// Returns `false` if the undo stack is empty.
+    /// Returns `false` if there's nothing to undo.
     pub fn undo(&mut self) -> bool {
-        if let Some(state) = self.undo_stack.pop() {
-            self.redo_stack.push(state);
-            if let Some(state2) = self.undo_stack.last() {
-                let buf = state2.buf.clone();
-                self.cursors = state2.cursors.clone();
-                self.set_raw_buffer(buf);
-                return true;
-            }
-        }
+        // Save unsaved changes so that they can be undone too.
+        self.save_undo();
 
-        false
+        match self.undo_tree.undo_target() {
+            Some(target) => self.goto_undo_state(target),
+            None => false,
+        }
     }
 
-    /// Returns `false` if the redo stack is empty.
+    /// Returns `false` if there's nothing to redo.
     pub fn redo(&mut self) -> bool {
-        if let Some(state) = self.redo_stack.pop() {
-            self.undo_stack.push(state);
-            if let Some(state2) = self.redo_stack.last() {
-                let buf = state2.buf.clone();
-                self.cursors = state2.cursors.clone();
-                self.set_raw_buffer(buf);
-                return true;
+        self.save_undo();
+
+        match self.undo_tree.redo_target() {
+            Some(target) => self.goto_undo_state(target),
+            None => false,
+        }
+    }
+
+    /// Switches to the next sibling branch in the undo tree. Returns `false`
+    /// if there's no such branch.
+    pub fn next_undo_branch(&mut self) -> bool {
+        self.save_undo();
+
+        match self.undo_tree.sibling_target(true) {
+            Some(target) => self.goto_undo_state(target),
+            None => false,
+        }
+    }
+
+    /// Switches to the previous sibling branch in the undo tree. Returns
+    /// `false` if there's no such branch.
+    pub fn prev_undo_branch(&mut self) -> bool {
+        self.save_undo();
+
+        match self.undo_tree.sibling_target(false) {
+            Some(target) => self.goto_undo_state(target),
+            None => false,
+        }
+    }
+
+    /// Goes back to the buffer state as of `duration` before the current one.
+    pub fn undo_earlier(&mut self, duration: Duration) -> bool {
+        self.save_undo();
+
+        let saved_at = self.undo_tree.saved_at(self.undo_tree.current());
+        let time = saved_at
+            .checked_sub(duration)
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        self.goto_undo_state(self.undo_tree.state_at(time))
+    }
+
+    /// Goes forward to the buffer state as of `duration` after the current one.
+    pub fn undo_later(&mut self, duration: Duration) -> bool {
+        self.save_undo();
+
+        let saved_at = self.undo_tree.saved_at(self.undo_tree.current());
+        self.goto_undo_state(self.undo_tree.state_at(saved_at + duration))
+    }
+
+    pub fn undo_tree(&self) -> &UndoTree {
+        &self.undo_tree
+    }
+
+    /// Replaces the undo history. The current state in `undo_tree` must be
+    /// identical to the current buffer contents.
+    pub fn set_undo_tree(&mut self, undo_tree: UndoTree) {
+        self.buf.take_undo_changes();
+        self.undo_tree = undo_tree;
+        self.undo_cursors = self.cursors.clone();
+    }
+
+    pub fn undo_branches(&self) -> Vec<UndoBranch> {
+        self.undo_tree.branches()
+    }
+
+    /// Restores the buffer state saved in the undo tree. Returns `false` if
+    /// it's already the current state.
+    pub fn goto_undo_state(&mut self, target: UndoNodeId) -> bool {
+        let steps = self.undo_tree.path_to(target);
+        let mut cursors = None;
+        for step in steps {
+            match step {
+                UndoStep::Revert(id) => {
+                    let node = self.undo_tree.node(id);
+                    for change in node.changes.iter().rev() {
+                        let inverse = change.inverse();
+                        self.buf.edit(inverse.range, &inverse.insert_text);
+                    }
+                    cursors = Some(node.cursors_before.clone());
+                }
+                UndoStep::Apply(id) => {
+                    let node = self.undo_tree.node(id);
+                    for change in &node.changes {
+                        self.buf.edit(change.range, &change.insert_text);
+                    }
+                    cursors = Some(node.cursors_after.clone());
+                }
             }
         }
 
-        false
+        self.undo_tree.set_current(target);
+        match cursors {
+            Some(cursors) => {
+                self.restore_undo_cursors(cursors);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn restore_undo_cursors(&mut self, cursors: CursorSet) {
+        // Changes made by undo/redo are not undoable by themselves.
+        self.buf.take_undo_changes();
+        self.cursors = cursors.clone();
+        self.undo_cursors = cursors;
     }
 
     pub fn undo_cursor_movements(&mut self) {
@@ -948,6 +1123,19 @@ mod tests {
         );
     }
 
+    #[test]
+    fn insert_multiple() {
+        let mut b = Buffer::from_text("a\nb\nc");
+        b.set_cursors_for_test(&[Cursor::new(0, 1), Cursor::new(1, 1), Cursor::new(2, 1)]);
+        b.insert_multiple(&["1", "2", "3"]);
+        assert_eq!(b.text(), "a1\nb2\nc3");
+
+        let mut b = Buffer::from_text("a");
+        b.set_cursors_for_test(&[Cursor::new(0, 1)]);
+        b.insert_multiple(&["1", "2"]);
+        assert_eq!(b.text(), "a1\n2");
+    }
+
     #[test]
     fn truncate() {
         // ABCD
@@ -1010,4 +1198,139 @@ mod tests {
         b.redo();
         assert_eq!(b.text(), "ABC");
     }
+
+    #[test]
+    fn test_undo_multiline_changes() {
+        let mut b = Buffer::from_text("ABC\nDEF\nGHI");
+        b.set_cursors_for_test(&[Cursor::new_selection(0, 1, 2, 1)]);
+        b.save_undo();
+        b.insert("XY\nZ");
+        assert_eq!(b.text(), "AXY\nZHI");
+        b.clear_recorded_changes();
+
+        assert!(b.undo());
+        assert_eq!(b.text(), "ABC\nDEF\nGHI");
+        assert_eq!(b.cursors(), &[Cursor::new_selection(0, 1, 2, 1)]);
+
+        // Undo should only touch the modified range.
+        let changes = b.clear_recorded_changes();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].range, Range::new(0, 1, 1, 1));
+        assert_eq!(changes[0].insert_text, "BC\nDEF\nG");
+
+        assert!(b.redo());
+        assert_eq!(b.text(), "AXY\nZHI");
+        assert_eq!(b.cursors(), &[Cursor::new(1, 1)]);
+        assert!(!b.redo());
+    }
+
+    #[test]
+    fn test_undo_unsaved_changes() {
+        let mut b = Buffer::from_text("");
+        b.insert("A");
+        b.save_undo();
+        b.insert("B");
+        // Unsaved changes are undone too.
+        b.undo();
+        assert_eq!(b.text(), "A");
+        b.insert("C");
+        b.save_undo();
+        assert!(!b.redo());
+        assert_eq!(b.text(), "AC");
+        b.undo();
+        b.undo();
+        assert_eq!(b.text(), "");
+    }
+
+    #[test]
+    fn test_undo_branches() {
+        let mut b = Buffer::from_text("");
+        b.insert("A");
+        b.save_undo();
+        b.insert("B");
+        b.save_undo();
+        b.undo();
+        b.insert("\nC");
+        b.save_undo();
+        assert_eq!(b.text(), "A\nC");
+
+        // The undone change is kept as another branch.
+        let branches = b.undo_branches();
+        assert_eq!(branches.len(), 2);
+        assert_eq!(branches[0].line_delta, -1);
+        assert!(!branches[0].is_current);
+        assert_eq!(branches[1].line_delta, 0);
+        assert!(branches[1].is_current);
+
+        assert!(b.prev_undo_branch());
+        assert_eq!(b.text(), "AB");
+        assert!(!b.prev_undo_branch());
+        assert!(b.next_undo_branch());
+        assert_eq!(b.text(), "A\nC");
+
+        assert!(b.goto_undo_state(branches[0].id));
+        assert_eq!(b.text(), "AB");
+        b.undo();
+        b.redo();
+        assert_eq!(b.text(), "AB");
+    }
+
+    #[test]
+    fn test_undo_earlier_and_later() {
+        let mut b = Buffer::from_text("");
+        b.insert("A");
+        b.save_undo();
+        b.insert("B");
+        b.save_undo();
+        assert!(b.undo_earlier(Duration::from_secs(3600)));
+        assert_eq!(b.text(), "");
+        assert!(b.undo_later(Duration::from_secs(3600)));
+        assert_eq!(b.text(), "AB");
+    }
+
+    #[test]
+    fn test_restore_undo_tree() {
+        let mut b = Buffer::from_text("");
+        b.insert("A");
+        b.save_undo();
+        b.insert("B");
+        b.save_undo();
+        let serialized = bincode::serialize(b.undo_tree()).unwrap();
+
+        let mut b = Buffer::from_text("AB");
+        b.set_undo_tree(bincode::deserialize(&serialized).unwrap());
+        b.undo();
+        assert_eq!(b.text(), "A");
+        b.undo();
+        assert_eq!(b.text(), "");
+        b.redo();
+        b.redo();
+        assert_eq!(b.text(), "AB");
+    }
+
+    #[test]
+    fn test_restore_undo_tree_cursor_ids() {
+        let mut b = Buffer::from_text("a\nb");
+        b.set_cursors_for_test(&[Cursor::new(0, 0), Cursor::new(1, 0)]);
+        b.insert("A");
+        b.save_undo();
+        b.insert("B");
+        b.save_undo();
+        let ids = |b: &Buffer| b.cursors().iter().map(|c| c.id()).collect::<Vec<_>>();
+        let serialized = bincode::serialize(b.undo_tree()).unwrap();
+
+        let mut restored = Buffer::from_text("ABa\nABb");
+        restored.set_undo_tree(bincode::deserialize(&serialized).unwrap());
+        restored.undo();
+        assert_eq!(restored.text(), "Aa\nAb");
+        let undone_ids = ids(&restored);
+        restored.redo();
+        assert_eq!(restored.text(), "ABa\nABb");
+
+        // The IDs are the same in different states, but not the ones
+        // allocated before.
+        assert_eq!(undone_ids.len(), 2);
+        assert_eq!(ids(&restored), undone_ids);
+        assert!(!ids(&b).contains(&undone_ids[1]));
+    }
 }
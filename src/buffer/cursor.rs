@@ -1,10 +1,14 @@
 use std::{
+    cell::RefCell,
     cmp::{max, min, Ordering},
+    collections::HashMap,
     fmt::{Debug, Display},
     hash::{Hash, Hasher},
     sync::atomic::{self, AtomicUsize},
 };
 
+use serde::{Deserialize, Deserializer, Serialize};
+
 use crate::{
     paragraph_iter::Paragraph,
     raw_buffer::RawBuffer,
@@ -28,7 +32,7 @@ pub enum Direction {
 /// ^       ^      ^
 /// x=0     x=1    x=2
 /// ```
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Position {
     /// The line number. 0-origin.
     pub y: usize,
@@ -45,10 +49,7 @@ impl Position {
     /// after replacing `range` with `new_text`.
     pub fn position_after_edit(range: Range, new_text: &str) -> Position {
         let pos = range.front();
-        let num_newlines_added = new_text.matches('\n').count();
-        let num_newlines_deleted = range.back().y - range.front().y;
-
-        let y_diff = num_newlines_added.saturating_sub(num_newlines_deleted);
+        let y_diff = new_text.matches('\n').count();
 
         let mut x_diff = 0;
         for c in new_text.chars() {
@@ -349,7 +350,7 @@ impl PartialOrd for Position {
 ///
 /// Note that `start` don't have to be less (in respect to its `Ord` implementation)
 /// than `end`.
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Range {
     /// The start position.
     pub(crate) start: Position,
@@ -455,11 +456,49 @@ impl Display for Range {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
 pub struct CursorId(usize);
 
+thread_local! {
+    /// The IDs allocated for the deserialized IDs in
+    /// `CursorId::with_deserialized_ids`.
+    static DESERIALIZED_IDS: RefCell<Option<HashMap<usize, CursorId>>> =
+        const { RefCell::new(None) };
+}
+
+impl CursorId {
+    /// Calls `f` which deserializes cursors. The same ID in the data is
+    /// deserialized into the same new ID so that the cursors in different
+    /// states are still identified.
+    pub(crate) fn with_deserialized_ids<T>(f: impl FnOnce() -> T) -> T {
+        let prev = DESERIALIZED_IDS.with(|ids| ids.replace(Some(HashMap::new())));
+        let result = f();
+        DESERIALIZED_IDS.with(|ids| ids.replace(prev));
+        result
+    }
+}
+
+impl<'de> Deserialize<'de> for CursorId {
+    /// Allocates a new ID for a deserialized cursor (except the main cursor)
+    /// not to conflict with the ones allocated in this process.
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<CursorId, D::Error> {
+        let id = usize::deserialize(deserializer)?;
+        if id == MAIN_CURSOR_ID.0 {
+            return Ok(MAIN_CURSOR_ID);
+        }
+
+        let alloc = || CursorId(NEXT_CURSOR_ID.fetch_add(1, atomic::Ordering::SeqCst));
+        Ok(
+            DESERIALIZED_IDS.with(|ids| match ids.borrow_mut().as_mut() {
+                Some(ids) => *ids.entry(id).or_insert_with(alloc),
+                None => alloc(),
+            }),
+        )
+    }
+}
+
 /// A text cursor.
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Cursor {
     id: CursorId,
     /// The range selected by the cursor. If the cursor is not a selection,
@@ -763,11 +802,16 @@ struct CursorUndoState {
 }
 
 /// A set of cursors, so-called multiple cursors.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct CursorSet {
     cursors: Vec<Cursor>,
+    #[serde(skip)]
     undo_stack: Vec<CursorUndoState>,
+    #[serde(skip)]
     redo_stack: Vec<CursorUndoState>,
+    /// Cursors added by `add_cursor`, in the order they were added.
+    #[serde(skip)]
+    added_cursors: Vec<CursorId>,
 }
 
 impl CursorSet {
@@ -776,6 +820,7 @@ impl CursorSet {
             cursors: vec![Cursor::new_main_cursor(0, 0)],
             undo_stack: Vec::new(),
             redo_stack: Vec::new(),
+            added_cursors: Vec::new(),
         }
     }
 
@@ -811,10 +856,31 @@ impl CursorSet {
         let id = cursor.id;
         new_cursors.push(cursor);
         self.save_undo_state();
+        self.added_cursors.push(id);
         self.update_cursors(&new_cursors);
         id
     }
 
+    /// Returns the most recently added cursor which still exists.
+    pub fn last_added_cursor(&self) -> Option<&Cursor> {
+        self.added_cursors
+            .last()
+            .and_then(|id| self.get_cursor_by_id(*id))
+    }
+
+    /// Removes the most recently added cursor. Returns false if there's no
+    /// such cursor.
+    pub fn remove_last_added_cursor(&mut self) -> bool {
+        match self.added_cursors.last().copied() {
+            Some(id) => {
+                self.save_undo_state();
+                self.remove_cursor(id);
+                true
+            }
+            None => false,
+        }
+    }
+
     pub fn remove_cursor(&mut self, id: CursorId) {
         let mut new_cursors = self.cursors.to_vec();
         new_cursors.retain(|c| c.id != id);
@@ -874,6 +940,11 @@ impl CursorSet {
 
         self.cursors = new_cursors;
         debug_assert!(!self.cursors.is_empty());
+
+        // Forget merged or removed cursors.
+        let cursors = &self.cursors;
+        self.added_cursors
+            .retain(|id| cursors.iter().any(|c| c.id == *id));
     }
 
     pub fn foreach<F>(&mut self, mut f: F)
@@ -0,0 +1,242 @@
+use std::{cmp::min, io};
+
+use chardetng::EncodingDetector;
+pub use encoding_rs::Encoding;
+use encoding_rs::{DecoderResult, UTF_16BE, UTF_16LE, UTF_8};
+
+/// The character encoding of a file.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FileEncoding {
+    pub encoding: &'static Encoding,
+    /// Whether the file starts with a byte order mark.
+    pub bom: bool,
+}
+
+impl FileEncoding {
+    pub const UTF8: FileEncoding = FileEncoding {
+        encoding: UTF_8,
+        bom: false,
+    };
+
+    pub fn new(encoding: &'static Encoding, bom: bool) -> FileEncoding {
+        FileEncoding { encoding, bom }
+    }
+
+    /// Returns a name like "UTF-8", "UTF-8 BOM", or "Shift_JIS".
+    pub fn name(&self) -> String {
+        if self.bom {
+            format!("{} BOM", self.encoding.name())
+        } else {
+            self.encoding.name().to_owned()
+        }
+    }
+
+    fn bom_bytes(&self) -> &'static [u8] {
+        if !self.bom {
+            return &[];
+        }
+
+        if self.encoding == UTF_8 {
+            b"\xef\xbb\xbf"
+        } else if self.encoding == UTF_16LE {
+            b"\xff\xfe"
+        } else if self.encoding == UTF_16BE {
+            b"\xfe\xff"
+        } else {
+            &[]
+        }
+    }
+}
+
+impl Default for FileEncoding {
+    fn default() -> FileEncoding {
+        FileEncoding::UTF8
+    }
+}
+
+/// Returns `encoding` with the BOM flag set if `bytes` starts with its BOM.
+pub fn with_bom_of(bytes: &[u8], encoding: &'static Encoding) -> FileEncoding {
+    let bom = matches!(Encoding::for_bom(bytes), Some((e, _)) if e == encoding);
+    FileEncoding::new(encoding, bom)
+}
+
+/// Guesses the encoding from the BOM or the contents.
+pub fn detect_encoding(bytes: &[u8]) -> FileEncoding {
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        return FileEncoding::new(encoding, true);
+    }
+
+    if std::str::from_utf8(bytes).is_ok() {
+        return FileEncoding::UTF8;
+    }
+
+    let mut detector = EncodingDetector::new();
+    detector.feed(bytes, true);
+    FileEncoding::new(detector.guess(None, true), false)
+}
+
+/// Returns true if `bytes` doesn't look like a text: it contains NUL or
+/// many control characters, or it's neither UTF-8 nor in a guessed encoding.
+pub fn is_binary(bytes: &[u8]) -> bool {
+    let sample = &bytes[..min(bytes.len(), 8192)];
+    if sample.is_empty() || Encoding::for_bom(sample).is_some() {
+        return false;
+    }
+
+    if sample.contains(&0) {
+        return true;
+    }
+
+    let num_controls = sample
+        .iter()
+        .filter(|b| matches!(b, 0x01..=0x08 | 0x0e..=0x1a | 0x1c..=0x1f | 0x7f))
+        .count();
+    if num_controls * 10 > sample.len() {
+        return true;
+    }
+
+    // Count bytes not valid in UTF-8.
+    let mut num_invalid = 0;
+    let mut rest = sample;
+    while let Err(err) = std::str::from_utf8(rest) {
+        let invalid_len = match err.error_len() {
+            Some(len) => len,
+            // Cut off in the middle of a character.
+            None => break,
+        };
+        num_invalid += invalid_len;
+        rest = &rest[err.valid_up_to() + invalid_len..];
+    }
+
+    if num_invalid * 10 <= sample.len() * 3 {
+        return false;
+    }
+
+    // Mostly invalid as UTF-8. It's a text only if it's valid in the guessed
+    // encoding. The end of the sample may be cut off in the middle of a
+    // character, so it's not the last chunk.
+    let encoding = detect_encoding(sample).encoding;
+    let mut decoder = encoding.new_decoder_without_bom_handling();
+    let mut decoded = String::with_capacity(sample.len() * 3);
+    let (result, _) = decoder.decode_to_string_without_replacement(sample, &mut decoded, false);
+    matches!(result, DecoderResult::Malformed(_, _))
+}
+
+/// Decodes `bytes` into a string. The BOM, if any, is removed.
+pub fn decode(bytes: &[u8], encoding: FileEncoding) -> io::Result<String> {
+    let bytes = match Encoding::for_bom(bytes) {
+        Some((bom_encoding, bom_len)) if bom_encoding == encoding.encoding => &bytes[bom_len..],
+        _ => bytes,
+    };
+
+    let (text, had_errors) = encoding.encoding.decode_without_bom_handling(bytes);
+    if had_errors {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("not a valid {} text", encoding.encoding.name()),
+        ));
+    }
+
+    Ok(text.into_owned())
+}
+
+/// Encodes `text`. Fails if it contains a character not representable in
+/// the encoding.
+pub fn encode(text: &str, encoding: FileEncoding) -> io::Result<Vec<u8>> {
+    let mut bytes = encoding.bom_bytes().to_vec();
+
+    // encoding_rs doesn't support encoding into UTF-16.
+    if encoding.encoding == UTF_16LE {
+        bytes.extend(text.encode_utf16().flat_map(|c| c.to_le_bytes()));
+    } else if encoding.encoding == UTF_16BE {
+        bytes.extend(text.encode_utf16().flat_map(|c| c.to_be_bytes()));
+    } else {
+        let (encoded, _, had_errors) = encoding.encoding.encode(text);
+        if had_errors {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "the text is not representable in {}",
+                    encoding.encoding.name()
+                ),
+            ));
+        }
+
+        bytes.extend_from_slice(&encoded);
+    }
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use encoding_rs::{SHIFT_JIS, WINDOWS_1252};
+
+    use super::*;
+
+    #[test]
+    fn test_detect_encoding() {
+        assert_eq!(detect_encoding(b"abc"), FileEncoding::UTF8);
+        assert_eq!(detect_encoding("あいう".as_bytes()), FileEncoding::UTF8);
+        assert_eq!(
+            detect_encoding(b"\xef\xbb\xbfabc"),
+            FileEncoding::new(UTF_8, true)
+        );
+        assert_eq!(
+            detect_encoding(b"\xff\xfea\x00"),
+            FileEncoding::new(UTF_16LE, true)
+        );
+
+        let (sjis, _, _) = SHIFT_JIS.encode("日本語のテキストです。ひらがなとカタカナ。");
+        assert_eq!(detect_encoding(&sjis), FileEncoding::new(SHIFT_JIS, false));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for (encoding, text) in [
+            (FileEncoding::UTF8, "abc\n"),
+            (FileEncoding::new(UTF_8, true), "abc\n"),
+            (FileEncoding::new(UTF_16LE, true), "あいう\n"),
+            (FileEncoding::new(UTF_16BE, true), "あいう\n"),
+            (FileEncoding::new(SHIFT_JIS, false), "あいう\n"),
+            (FileEncoding::new(WINDOWS_1252, false), "café\n"),
+        ] {
+            let bytes = encode(text, encoding).unwrap();
+            assert_eq!(decode(&bytes, encoding).unwrap(), text);
+        }
+
+        assert_eq!(
+            encode("abc", FileEncoding::new(UTF_8, true)).unwrap(),
+            b"\xef\xbb\xbfabc"
+        );
+        assert!(encode("あ", FileEncoding::new(WINDOWS_1252, false)).is_err());
+        assert!(decode(b"\xff\xff", FileEncoding::UTF8).is_err());
+    }
+
+    #[test]
+    fn test_is_binary() {
+        assert!(!is_binary(b""));
+        assert!(!is_binary(b"fn main() {\n\tprintln!();\n}\n"));
+        assert!(!is_binary("あいう".as_bytes()));
+        assert!(!is_binary(b"\xff\xfea\x00b\x00"));
+        assert!(is_binary(b"\x7fELF\x02\x01\x01\x00"));
+        assert!(is_binary(b"\x01\x02\x03abc"));
+
+        let (sjis, _, _) = SHIFT_JIS.encode("日本語のテキストです。ひらがなとカタカナ。");
+        assert!(!is_binary(&sjis));
+        // Cut off in the middle of a character.
+        assert!(!is_binary(&sjis[..sjis.len() - 1]));
+    }
+
+    #[test]
+    fn test_with_bom_of() {
+        assert_eq!(
+            with_bom_of(b"\xef\xbb\xbfabc", UTF_8),
+            FileEncoding::new(UTF_8, true)
+        );
+        assert_eq!(
+            with_bom_of(b"\xef\xbb\xbfabc", SHIFT_JIS),
+            FileEncoding::new(SHIFT_JIS, false)
+        );
+    }
+}
@@ -0,0 +1,304 @@
+use noa_languages::AutoPair;
+
+use crate::{
+    buffer::Buffer,
+    cursor::{Cursor, Position},
+    syntax::TsNodeExt,
+};
+
+// Imitate VSCode's default behavior.
+// https://code.visualstudio.com/api/language-extensions/language-configuration-guide
+const AUTO_CLOSE_BEFORE: &str = ";:.,=}])>` \n\t";
+
+enum PairAction {
+    Insert,
+    InsertPair(char),
+    /// Move over the existing closing character instead of inserting one.
+    TypeOver,
+}
+
+impl Buffer {
+    /// Inserts `ch` with its closing character, or moves over the closing
+    /// character at the cursor.
+    pub(crate) fn insert_char_with_auto_pair(&mut self, ch: char) {
+        self.update_syntax_tree();
+
+        // `foreach` visits cursors from the bottom.
+        let actions: Vec<PairAction> = self
+            .cursors()
+            .iter()
+            .rev()
+            .map(|c| self.pair_action(c, ch))
+            .collect();
+
+        let mut actions = actions.into_iter();
+        self.cursors
+            .foreach(|c, past_cursors| match actions.next().unwrap() {
+                PairAction::Insert => {
+                    self.buf.edit_at_cursor(c, past_cursors, &ch.to_string());
+                }
+                PairAction::InsertPair(close) => {
+                    self.buf
+                        .edit_at_cursor(c, past_cursors, &format!("{}{}", ch, close));
+                    c.move_left(&self.buf);
+                }
+                PairAction::TypeOver => {
+                    c.move_right(&self.buf);
+                }
+            });
+    }
+
+    /// Deletes the character before each cursor. If the cursor is in an empty
+    /// pair like `(|)`, deletes the closing character too.
+    pub fn smart_backspace(&mut self) {
+        // `foreach` visits cursors from the bottom.
+        let in_empty_pairs: Vec<bool> = self
+            .cursors()
+            .iter()
+            .rev()
+            .map(|c| !c.is_selection() && self.is_in_empty_pair(c.moving_position()))
+            .collect();
+
+        let mut in_empty_pairs = in_empty_pairs.into_iter();
+        self.cursors.foreach(|c, past_cursors| {
+            let in_empty_pair = in_empty_pairs.next().unwrap();
+            if c.selection().is_empty() {
+                c.expand_left(&self.buf);
+                if in_empty_pair {
+                    c.expand_right(&self.buf);
+                }
+            }
+            self.buf.edit_at_cursor(c, past_cursors, "");
+        });
+    }
+
+    fn pair_action(&self, c: &Cursor, ch: char) -> PairAction {
+        if c.is_selection() {
+            return PairAction::Insert;
+        }
+
+        let pos = c.moving_position();
+        let pairs = self.language_at(pos).auto_pairs;
+        let next_char = self.buf.char_iter(pos).next();
+        let prev_char = self.buf.char_iter(pos).prev();
+
+        if next_char == Some(ch)
+            && pairs.iter().any(|pair| pair.close == ch)
+            && self.is_closing_char_at(pos)
+        {
+            return PairAction::TypeOver;
+        }
+
+        let pair = match pairs.iter().find(|pair| pair.open == ch) {
+            Some(pair) => pair,
+            None => return PairAction::Insert,
+        };
+
+        if !AUTO_CLOSE_BEFORE.contains(next_char.unwrap_or('\n')) {
+            return PairAction::Insert;
+        }
+
+        // Quotes after a word like `don't`.
+        if pair.open == pair.close && prev_char.is_some_and(|c| c.is_alphanumeric()) {
+            return PairAction::Insert;
+        }
+
+        if self.is_in_node_kinds(pos, pair) {
+            return PairAction::Insert;
+        }
+
+        PairAction::InsertPair(pair.close)
+    }
+
+    /// Returns true if the character at `pos` closes a syntax node opened
+    /// before `pos`, e.g. `)` in `f(a|)`. Always true if the syntax tree is
+    /// not available.
+    fn is_closing_char_at(&self, pos: Position) -> bool {
+        let syntax = match self.syntax() {
+            Some(syntax) => syntax,
+            None => return true,
+        };
+
+        let end = Position::new(pos.y, pos.x + 1);
+        let mut node = syntax
+            .tree()
+            .root_node()
+            .descendant_for_point_range(pos.into(), end.into());
+        while let Some(current) = node {
+            let range = current.buffer_range();
+            if range.front() < pos && range.back() == end {
+                return true;
+            }
+
+            node = current.parent();
+        }
+
+        false
+    }
+
+    /// Returns true if `pos` is inside a node in `pair.not_in` like strings.
+    fn is_in_node_kinds(&self, pos: Position, pair: &AutoPair) -> bool {
+        let syntax = match self.syntax() {
+            Some(syntax) => syntax,
+            None => return false,
+        };
+
+        // Look for the nodes containing the character before `pos` too: ones
+        // ending at `pos` like line comments.
+        let prev_pos = Position::new(pos.y, pos.x.saturating_sub(1));
+        let mut node = syntax
+            .tree()
+            .root_node()
+            .descendant_for_point_range(prev_pos.into(), pos.into());
+        while let Some(current) = node {
+            let start = current.buffer_range().front();
+            let end = current.buffer_range().back();
+            // A line comment continues until the end of line.
+            let is_in_node =
+                start < pos && (pos < end || (pos == end && self.starts_with_line_comment(start)));
+            if is_in_node && pair.not_in.contains(&current.kind()) {
+                return true;
+            }
+
+            node = current.parent();
+        }
+
+        false
+    }
+
+    fn starts_with_line_comment(&self, pos: Position) -> bool {
+        match self.language_at(pos).line_comment {
+            Some(keyword) => self
+                .buf
+                .char_iter(pos)
+                .take(keyword.chars().count())
+                .eq(keyword.chars()),
+            None => false,
+        }
+    }
+
+    fn is_in_empty_pair(&self, pos: Position) -> bool {
+        let next_char = self.buf.char_iter(pos).next();
+        let prev_char = self.buf.char_iter(pos).prev();
+        self.language_at(pos)
+            .auto_pairs
+            .iter()
+            .any(|pair| Some(pair.open) == prev_char && Some(pair.close) == next_char)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use noa_languages::get_language_by_name;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn buffer(text: &str, lang: &str, cursors: &[Cursor]) -> Buffer {
+        let mut b = Buffer::from_text(text);
+        if lang != "plain" {
+            b.set_language(get_language_by_name(lang).unwrap()).unwrap();
+        }
+        b.set_cursors_for_test(cursors);
+        b
+    }
+
+    #[test]
+    fn auto_close() {
+        let mut b = buffer("", "plain", &[Cursor::new(0, 0)]);
+        b.smart_insert_char('(');
+        assert_eq!(b.text(), "()");
+        assert_eq!(b.cursors(), &[Cursor::new(0, 1)]);
+        b.smart_insert_char('"');
+        assert_eq!(b.text(), "(\"\")");
+        assert_eq!(b.cursors(), &[Cursor::new(0, 2)]);
+
+        // Not before a word.
+        let mut b = buffer("abc", "plain", &[Cursor::new(0, 0)]);
+        b.smart_insert_char('[');
+        assert_eq!(b.text(), "[abc");
+
+        // Not after a word.
+        let mut b = buffer("don", "plain", &[Cursor::new(0, 3)]);
+        b.smart_insert_char('\'');
+        assert_eq!(b.text(), "don'");
+
+        // Multiple cursors.
+        let mut b = buffer("a \nb ", "plain", &[Cursor::new(0, 2), Cursor::new(1, 2)]);
+        b.smart_insert_char('{');
+        assert_eq!(b.text(), "a {}\nb {}");
+        assert_eq!(b.cursors(), &[Cursor::new(0, 3), Cursor::new(1, 3)]);
+    }
+
+    #[test]
+    fn no_auto_close_in_strings_and_comments() {
+        let mut b = buffer("let s = \"a \";", "rust", &[Cursor::new(0, 10)]);
+        b.smart_insert_char('(');
+        assert_eq!(b.text(), "let s = \"a( \";");
+
+        // After a string.
+        let mut b = buffer("f(\"a\")", "rust", &[Cursor::new(0, 5)]);
+        b.smart_insert_char('[');
+        assert_eq!(b.text(), "f(\"a\"[])");
+
+        let mut b = buffer("// abc ", "rust", &[Cursor::new(0, 7)]);
+        b.smart_insert_char('"');
+        assert_eq!(b.text(), "// abc \"");
+
+        // `'` is not a pair in Rust.
+        let mut b = buffer("fn f<>() {}", "rust", &[Cursor::new(0, 5)]);
+        b.smart_insert_char('\'');
+        assert_eq!(b.text(), "fn f<'>() {}");
+
+        let mut b = buffer("x = ", "python", &[Cursor::new(0, 4)]);
+        b.smart_insert_char('\'');
+        assert_eq!(b.text(), "x = ''");
+    }
+
+    #[test]
+    fn type_over_closing_chars() {
+        let mut b = buffer("f(a)", "rust", &[Cursor::new(0, 3)]);
+        b.smart_insert_char(')');
+        assert_eq!(b.text(), "f(a)");
+        assert_eq!(b.cursors(), &[Cursor::new(0, 4)]);
+
+        let mut b = buffer("", "rust", &[Cursor::new(0, 0)]);
+        b.smart_insert_char('"');
+        b.smart_insert_char('a');
+        b.smart_insert_char('"');
+        assert_eq!(b.text(), "\"a\"");
+        assert_eq!(b.cursors(), &[Cursor::new(0, 3)]);
+
+        // The quote opens a string.
+        let mut b = buffer("f(\"a\")", "rust", &[Cursor::new(0, 2)]);
+        b.smart_insert_char('"');
+        assert_eq!(b.text(), "f(\"\"a\")");
+
+        // Not a closing character.
+        let mut b = buffer(")", "plain", &[Cursor::new(0, 0)]);
+        b.smart_insert_char('(');
+        assert_eq!(b.text(), "())");
+        b.smart_insert_char(')');
+        assert_eq!(b.text(), "())");
+        assert_eq!(b.cursors(), &[Cursor::new(0, 2)]);
+    }
+
+    #[test]
+    fn backspace_empty_pairs() {
+        let mut b = buffer(
+            "()\n(a)\n\"\"",
+            "plain",
+            &[Cursor::new(0, 1), Cursor::new(1, 1), Cursor::new(2, 1)],
+        );
+        b.smart_backspace();
+        assert_eq!(b.text(), "\na)\n");
+        assert_eq!(
+            b.cursors(),
+            &[Cursor::new(0, 0), Cursor::new(1, 0), Cursor::new(2, 0)]
+        );
+
+        let mut b = buffer("(abc)", "plain", &[Cursor::new_selection(0, 1, 0, 4)]);
+        b.smart_backspace();
+        assert_eq!(b.text(), "()");
+    }
+}
@@ -0,0 +1,346 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{
+    buffer::{Buffer, TextEdit},
+    cursor::{Cursor, Position, Range},
+    display_width::DisplayWidth,
+    reflow_iter::width_to_next_tab_stop,
+};
+
+/// A position on the screen: `column` is in display width, not in characters.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DisplayPosition {
+    pub y: usize,
+    pub column: usize,
+}
+
+/// A rectangular selection.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct BlockSelection {
+    pub anchor: DisplayPosition,
+    /// The moving corner.
+    pub head: DisplayPosition,
+}
+
+/// Calls `f` with the x, the display column, and the display width of each
+/// grapheme in `line`. Stops if `f` returns false.
+fn for_each_column<F>(line: &str, tab_width: usize, mut f: F)
+where
+    F: FnMut(usize, usize, usize) -> bool,
+{
+    let mut x = 0;
+    let mut column = 0;
+    for grapheme in line.graphemes(true) {
+        // Compute the width in the same way as `ReflowIter`.
+        let width = match grapheme {
+            "\t" => width_to_next_tab_stop(column, tab_width),
+            _ => grapheme.display_width().max(1),
+        };
+
+        if !f(x, column, width) {
+            return;
+        }
+
+        x += grapheme.chars().count();
+        column += width;
+    }
+}
+
+impl Buffer {
+    /// Returns the display column of `pos`, taking tabs and wide characters
+    /// into account.
+    pub fn display_column(&self, pos: Position) -> usize {
+        let mut result = 0;
+        for_each_column(
+            &self.line_text(pos.y),
+            self.config.tab_width,
+            |x, column, width| {
+                if x >= pos.x {
+                    return false;
+                }
+
+                result = column + width;
+                true
+            },
+        );
+        result
+    }
+
+    /// Returns the display width of the line `y`.
+    pub fn line_display_width(&self, y: usize) -> usize {
+        self.display_column(Position::new(y, self.line_len(y)))
+    }
+
+    /// Returns the position at `column` in the line `y`. If `column` is in
+    /// the middle of a wide character, returns the position after it if
+    /// `round_up` is true, or before it otherwise.
+    fn position_at_display_column(&self, y: usize, column: usize, round_up: bool) -> Position {
+        let mut result = self.line_len(y);
+        for_each_column(
+            &self.line_text(y),
+            self.config.tab_width,
+            |x, start, width| {
+                if start >= column {
+                    result = x;
+                    return false;
+                }
+
+                if start + width > column {
+                    result = if round_up { x + 1 } else { x };
+                    return false;
+                }
+
+                true
+            },
+        );
+        Position::new(y, result)
+    }
+
+    /// Returns the selection of each line in the block.
+    fn block_ranges(&self, block: BlockSelection) -> Vec<Range> {
+        let BlockSelection { anchor, head } = block;
+        let left = anchor.column.min(head.column);
+        let right = anchor.column.max(head.column);
+        let top = anchor.y.min(head.y);
+        let bottom = anchor.y.max(head.y).min(self.num_lines() - 1);
+
+        (top..=bottom)
+            .map(|y| {
+                let start = self.position_at_display_column(y, left, false);
+                let end = self.position_at_display_column(y, right, left != right);
+                if head.column < anchor.column {
+                    Range::from_positions(end, start)
+                } else {
+                    Range::from_positions(start, end)
+                }
+            })
+            .collect()
+    }
+
+    /// Returns true if the cursors form the last block selection.
+    pub fn is_block_selection(&self) -> bool {
+        match self.block_selection {
+            Some(block) => self
+                .cursors()
+                .iter()
+                .map(|c| c.selection())
+                .eq(self.block_ranges(block)),
+            None => false,
+        }
+    }
+
+    /// Replaces cursors with one cursor per line in `block`. The main cursor
+    /// is on the line of `block.head`.
+    pub fn select_block(&mut self, block: BlockSelection) {
+        let mut new_cursors = Vec::new();
+        for range in self.block_ranges(block) {
+            if range.start.y == block.head.y {
+                let mut main = self.main_cursor().clone();
+                main.select_range(range);
+                new_cursors.push(main);
+            } else {
+                new_cursors.push(Cursor::from_range(range));
+            }
+        }
+
+        if !new_cursors.iter().any(|c| c.is_main_cursor()) {
+            // `head` is below the last line.
+            let mut main = self.main_cursor().clone();
+            main.select_range(new_cursors.pop().unwrap().selection());
+            new_cursors.push(main);
+        }
+
+        self.cursors.save_undo_state();
+        self.update_cursors(&new_cursors);
+        self.block_selection = Some(block);
+    }
+
+    /// Moves the head of the block selection. Starts a new block selection at
+    /// the main cursor if it's not in the block selection.
+    pub fn extend_block_selection(&mut self, y_diff: isize, column_diff: isize) {
+        let mut block = match self.block_selection {
+            Some(block) if self.is_block_selection() => block,
+            _ => {
+                let pos = self.main_cursor().moving_position();
+                let start = DisplayPosition {
+                    y: pos.y,
+                    column: self.display_column(pos),
+                };
+                BlockSelection {
+                    anchor: start,
+                    head: start,
+                }
+            }
+        };
+
+        let max_y = self.num_lines() - 1;
+        block.head.y = block.head.y.saturating_add_signed(y_diff).min(max_y);
+        block.head.column = block.head.column.saturating_add_signed(column_diff);
+        self.select_block(block);
+    }
+
+    /// Inserts `lines` as a block at the main cursor: the i-th line is
+    /// inserted into the i-th line below the cursor at the same display
+    /// column. Short lines are padded with spaces.
+    pub fn paste_block(&mut self, lines: &[&str]) {
+        // Delete the selections first: the positions below must be computed
+        // in the text after the deletion.
+        self.delete_if_not_empty();
+
+        let pos = self.main_cursor().front();
+        let column = self.display_column(pos);
+        let mut edits = Vec::new();
+        let mut appended = String::new();
+        for (i, line) in lines.iter().enumerate() {
+            let y = pos.y + i;
+            if y >= self.num_lines() {
+                // Add new lines at the end of the buffer.
+                appended.push('\n');
+                appended.push_str(&" ".repeat(column));
+                appended.push_str(line);
+                continue;
+            }
+
+            let width = self.line_display_width(y);
+            let (x, padding) = if width < column {
+                (self.line_len(y), column - width)
+            } else {
+                (self.position_at_display_column(y, column, false).x, 0)
+            };
+
+            edits.push(TextEdit {
+                range: Range::new(y, x, y, x),
+                new_text: format!("{}{}", " ".repeat(padding), line),
+            });
+        }
+
+        if !appended.is_empty() {
+            let last_y = self.num_lines() - 1;
+            let last_x = self.line_len(last_y);
+            edits.push(TextEdit {
+                range: Range::new(last_y, last_x, last_y, last_x),
+                new_text: appended,
+            });
+        }
+
+        self.apply_text_edits(edits);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn selections(b: &Buffer) -> Vec<Range> {
+        b.cursors().iter().map(|c| c.selection()).collect()
+    }
+
+    fn block(anchor: (usize, usize), head: (usize, usize)) -> BlockSelection {
+        BlockSelection {
+            anchor: DisplayPosition {
+                y: anchor.0,
+                column: anchor.1,
+            },
+            head: DisplayPosition {
+                y: head.0,
+                column: head.1,
+            },
+        }
+    }
+
+    #[test]
+    fn test_display_column() {
+        let b = Buffer::from_text("a\tb\nあいu");
+        assert_eq!(b.display_column(Position::new(0, 0)), 0);
+        assert_eq!(b.display_column(Position::new(0, 1)), 1);
+        assert_eq!(b.display_column(Position::new(0, 2)), b.config.tab_width);
+        assert_eq!(b.display_column(Position::new(1, 2)), 4);
+        assert_eq!(b.display_column(Position::new(1, 3)), 5);
+    }
+
+    #[test]
+    fn test_select_block() {
+        let mut b = Buffer::from_text("abcdef\nab\nabcdef");
+        b.select_block(block((0, 1), (2, 4)));
+        assert_eq!(
+            selections(&b),
+            vec![
+                Range::new(0, 1, 0, 4),
+                Range::new(1, 1, 1, 2),
+                Range::new(2, 1, 2, 4)
+            ]
+        );
+        assert_eq!(b.main_cursor().selection(), Range::new(2, 1, 2, 4));
+        assert!(b.is_block_selection());
+
+        // Right to left.
+        b.select_block(block((0, 4), (1, 1)));
+        assert_eq!(
+            selections(&b),
+            vec![Range::new(0, 4, 0, 1), Range::new(1, 2, 1, 1)]
+        );
+
+        b.clear_secondary_cursors();
+        assert!(!b.is_block_selection());
+    }
+
+    #[test]
+    fn test_select_block_with_wide_chars() {
+        // "あ" and "い" are 2 columns wide.
+        let mut b = Buffer::from_text("abcd\nあい\n\tx");
+        b.config.tab_width = 4;
+        b.select_block(block((0, 1), (2, 5)));
+        assert_eq!(
+            selections(&b),
+            vec![
+                Range::new(0, 1, 0, 4),
+                Range::new(1, 0, 1, 2),
+                Range::new(2, 0, 2, 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extend_block_selection() {
+        let mut b = Buffer::from_text("abc\nabc\nabc");
+        b.select_main_cursor_range(Range::new(0, 1, 0, 1));
+        b.extend_block_selection(1, 0);
+        b.extend_block_selection(1, 0);
+        b.extend_block_selection(0, 1);
+        assert_eq!(
+            selections(&b),
+            vec![
+                Range::new(0, 1, 0, 2),
+                Range::new(1, 1, 1, 2),
+                Range::new(2, 1, 2, 2)
+            ]
+        );
+        b.extend_block_selection(-1, 0);
+        assert_eq!(
+            selections(&b),
+            vec![Range::new(0, 1, 0, 2), Range::new(1, 1, 1, 2)]
+        );
+    }
+
+    #[test]
+    fn test_paste_block() {
+        let mut b = Buffer::from_text("abc\na\nabc");
+        b.select_main_cursor_range(Range::new(0, 2, 0, 2));
+        b.paste_block(&["X", "Y", "Z", "W"]);
+        assert_eq!(b.text(), "abXc\na Y\nabZc\n  W");
+    }
+
+    #[test]
+    fn test_paste_block_over_multi_line_selection() {
+        let mut b = Buffer::from_text("aaaa\nb\ncc\n");
+        b.select_main_cursor_range(Range::new(0, 3, 1, 1));
+        b.paste_block(&["X", "Y"]);
+        assert_eq!(b.text(), "aaaX\ncc Y\n");
+
+        let mut b = Buffer::from_text("aaaa\nbbbbbb\nc\n");
+        b.select_main_cursor_range(Range::new(0, 2, 1, 1));
+        b.paste_block(&["X", "Y"]);
+        assert_eq!(b.text(), "aaXbbbbb\nc Y\n");
+    }
+}
@@ -1,20 +1,64 @@
 use std::{cmp::min, collections::HashMap};
 
+use noa_languages::Language;
+
 use crate::{
     buffer::Buffer,
     cursor::{Position, Range},
+    syntax::TsNodeExt,
 };
 
+/// How to comment out a line.
+#[derive(Clone, Copy)]
+enum CommentStyle {
+    Line(&'static str),
+    Block(&'static str, &'static str),
+}
+
+impl CommentStyle {
+    fn for_language(lang: &Language) -> Option<CommentStyle> {
+        match (lang.line_comment, lang.block_comment) {
+            (Some(keyword), _) => Some(CommentStyle::Line(keyword)),
+            (None, Some((open, close))) => Some(CommentStyle::Block(open, close)),
+            (None, None) => None,
+        }
+    }
+
+    fn is_commented_out(&self, text: &str) -> bool {
+        match self {
+            CommentStyle::Line(keyword) => text.starts_with(keyword),
+            CommentStyle::Block(open, close) => is_block_comment(text, open, close),
+        }
+    }
+}
+
+fn is_block_comment(text: &str, open: &str, close: &str) -> bool {
+    text.len() >= open.len() + close.len() && text.starts_with(open) && text.ends_with(close)
+}
+
+/// Returns the number of characters of the opening delimiter and the closing
+/// delimiter in `text` to be removed, including a whitespace next to them.
+fn block_comment_delimiters_len(text: &str, open: &str, close: &str) -> (usize, usize) {
+    let inner = &text[open.len()..text.len() - close.len()];
+    let mut open_len = open.chars().count();
+    let mut close_len = close.chars().count();
+    if let Some(rest) = inner.strip_prefix(' ') {
+        open_len += 1;
+        if rest.ends_with(' ') {
+            close_len += 1;
+        }
+    } else if inner.ends_with(' ') {
+        close_len += 1;
+    }
+
+    (open_len, close_len)
+}
+
 impl Buffer {
+    /// Comments out the lines with cursors, or uncomments them if they are
+    /// already commented out. Block comments are used if the language
+    /// doesn't have line comments.
     pub fn toggle_line_comment_out(&mut self) {
-        let keyword_without_whitespace = match self.language().line_comment.as_ref() {
-            Some(keyword) => *keyword,
-            None => return,
-        };
-        let keyword_with_whitespace = format!("{} ", keyword_without_whitespace);
-        let keyword_without_whitespace_len = keyword_without_whitespace.chars().count();
-        let keyword_with_whitespace_len = keyword_with_whitespace.chars().count();
-
         let mut target_lines = Vec::new();
         for c in self.cursors() {
             let ys = c.selection().overlapped_lines();
@@ -27,37 +71,81 @@ impl Buffer {
             }
         }
 
-        let increment_comment = target_lines.iter().any(|y| {
-            !self
-                .buf
-                .line_text(*y)
-                .trim_start()
-                .starts_with(keyword_without_whitespace)
-        });
+        // Determine the comment style for each line from the language at the
+        // beginning of the line.
+        let target_lines: Vec<(usize, CommentStyle)> = target_lines
+            .into_iter()
+            .filter_map(|y| {
+                let pos = Position::new(y, self.buf.line_indent_len(y));
+                CommentStyle::for_language(self.language_at(pos)).map(|style| (y, style))
+            })
+            .collect();
+
+        let increment_comment = target_lines
+            .iter()
+            .any(|(y, style)| !style.is_commented_out(self.buf.line_text(*y).trim()));
 
         // Add/remove comment outs.
         let mut x_diffs = HashMap::new();
-        for y in target_lines {
+        for (y, style) in target_lines {
             let current_indent_len = self.buf.line_indent_len(y);
             let pos_after_indent = Position::new(y, current_indent_len);
             let eol = Position::new(y, self.buf.line_len(y));
             let stripped_line_text = self.substr(Range::from_positions(pos_after_indent, eol));
 
-            if increment_comment {
-                self.buf.edit(
-                    Range::from_positions(pos_after_indent, pos_after_indent),
-                    &keyword_with_whitespace,
-                );
-            } else if stripped_line_text.starts_with(&keyword_with_whitespace) {
-                let end = Position::new(y, current_indent_len + keyword_with_whitespace_len);
-                self.buf
-                    .edit(Range::from_positions(pos_after_indent, end), "");
-                x_diffs.insert(y, keyword_with_whitespace_len);
-            } else if stripped_line_text.starts_with(keyword_without_whitespace) {
-                let end = Position::new(y, current_indent_len + keyword_without_whitespace_len);
-                self.buf
-                    .edit(Range::from_positions(pos_after_indent, end), "");
-                x_diffs.insert(y, keyword_without_whitespace_len);
+            match style {
+                CommentStyle::Line(keyword_without_whitespace) => {
+                    let keyword_with_whitespace = format!("{} ", keyword_without_whitespace);
+                    let keyword_without_whitespace_len = keyword_without_whitespace.chars().count();
+                    let keyword_with_whitespace_len = keyword_with_whitespace.chars().count();
+
+                    if increment_comment {
+                        self.buf.edit(
+                            Range::from_positions(pos_after_indent, pos_after_indent),
+                            &keyword_with_whitespace,
+                        );
+                    } else if stripped_line_text.starts_with(&keyword_with_whitespace) {
+                        let end =
+                            Position::new(y, current_indent_len + keyword_with_whitespace_len);
+                        self.buf
+                            .edit(Range::from_positions(pos_after_indent, end), "");
+                        x_diffs.insert(y, keyword_with_whitespace_len);
+                    } else if stripped_line_text.starts_with(keyword_without_whitespace) {
+                        let end =
+                            Position::new(y, current_indent_len + keyword_without_whitespace_len);
+                        self.buf
+                            .edit(Range::from_positions(pos_after_indent, end), "");
+                        x_diffs.insert(y, keyword_without_whitespace_len);
+                    }
+                }
+                CommentStyle::Block(open, close) => {
+                    let text = stripped_line_text.trim_end();
+                    if increment_comment {
+                        if text.is_empty() {
+                            self.buf.edit(
+                                Range::from_positions(pos_after_indent, pos_after_indent),
+                                &format!("{} {}", open, close),
+                            );
+                        } else {
+                            self.buf
+                                .edit(Range::from_positions(eol, eol), &format!(" {}", close));
+                            self.buf.edit(
+                                Range::from_positions(pos_after_indent, pos_after_indent),
+                                &format!("{} ", open),
+                            );
+                        }
+                    } else if is_block_comment(text, open, close) {
+                        let (open_len, close_len) = block_comment_delimiters_len(text, open, close);
+                        let text_end = current_indent_len + text.chars().count();
+                        self.buf
+                            .edit(Range::new(y, text_end - close_len, y, text_end), "");
+                        self.buf.edit(
+                            Range::new(y, current_indent_len, y, current_indent_len + open_len),
+                            "",
+                        );
+                        x_diffs.insert(y, open_len);
+                    }
+                }
             }
         }
 
@@ -80,11 +168,87 @@ impl Buffer {
             }
         });
     }
+
+    /// Wraps the selections with block comments, or unwraps them if they are
+    /// already block comments. Without a selection, removes the block comment
+    /// at the cursor or inserts an empty one.
+    pub fn toggle_block_comment_out(&mut self) {
+        // The range to be replaced, the new text, and the new cursor position
+        // for each cursor. The new text is selected if the position is None.
+        let edits: Vec<Option<(Range, String, Option<Position>)>> = self
+            .cursors()
+            .iter()
+            .map(|c| {
+                let selection = c.selection();
+                let (open, close) = self.language_at(selection.front()).block_comment?;
+                let range = if selection.is_empty() {
+                    self.block_comment_at(selection.front(), open, close)
+                        .unwrap_or(selection)
+                } else {
+                    selection
+                };
+
+                let text = self.substr(range);
+                let edit = if is_block_comment(&text, open, close) {
+                    let (open_len, close_len) = block_comment_delimiters_len(&text, open, close);
+                    let inner_len = text.chars().count() - open_len - close_len;
+                    let inner: String = text.chars().skip(open_len).take(inner_len).collect();
+                    (range, inner, None)
+                } else if selection.is_empty() {
+                    let pos = range.front();
+                    let cursor_pos = Position::new(pos.y, pos.x + open.chars().count() + 1);
+                    (range, format!("{}  {}", open, close), Some(cursor_pos))
+                } else {
+                    (range, format!("{} {} {}", open, text, close), None)
+                };
+
+                Some(edit)
+            })
+            .collect();
+
+        // `foreach` visits cursors from the bottom.
+        let mut edits = edits.into_iter().rev();
+        self.cursors.foreach(|c, past_cursors| {
+            let (range, new_text, cursor_pos) = match edits.next().unwrap() {
+                Some(edit) => edit,
+                None => return,
+            };
+
+            c.select_range(range);
+            self.buf.edit_at_cursor(c, past_cursors, &new_text);
+            match cursor_pos {
+                Some(pos) => c.move_to_pos(pos),
+                None => c.select_range(Range::from_positions(range.front(), c.moving_position())),
+            }
+        });
+    }
+
+    /// Returns the range of the block comment at `pos` in the syntax tree.
+    fn block_comment_at(&self, pos: Position, open: &str, close: &str) -> Option<Range> {
+        let point = pos.into();
+        let mut node = self
+            .syntax()?
+            .tree()
+            .root_node()
+            .descendant_for_point_range(point, point);
+        while let Some(current) = node {
+            if current.kind().contains("comment") {
+                let range = current.buffer_range();
+                if is_block_comment(&self.substr(range), open, close) {
+                    return Some(range);
+                }
+            }
+
+            node = current.parent();
+        }
+
+        None
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::cursor::Cursor;
+    use crate::{cursor::Cursor, syntax::SyntaxParser};
     use noa_languages::get_language_by_name;
     use pretty_assertions::assert_eq;
 
@@ -155,4 +319,118 @@ mod tests {
         buffer.toggle_line_comment_out();
         assert_eq!(buffer.text(), "  abc\n  def");
     }
+
+    fn html_buffer(text: &str) -> Buffer {
+        let lang = get_language_by_name("html").unwrap();
+        let mut buffer = Buffer::from_text(text);
+        buffer.set_language(lang).unwrap();
+        let mut parser = SyntaxParser::new(lang).unwrap();
+        parser.parse_fully(buffer.raw_buffer());
+        buffer.set_syntax_tree(parser.tree().clone());
+        buffer
+    }
+
+    #[test]
+    fn test_comment_out_with_block_comments() {
+        let mut buffer = html_buffer("<p>\n  abc\n\n</p>");
+        buffer.set_cursors_for_test(&[Cursor::new_selection(0, 0, 3, 0)]);
+        buffer.toggle_line_comment_out();
+        assert_eq!(
+            buffer.text(),
+            "<!-- <p> -->\n  <!-- abc -->\n<!-- -->\n</p>"
+        );
+
+        let mut buffer = html_buffer("<!-- <p> -->\n  <!--abc-->\n<!-- -->");
+        buffer.set_cursors_for_test(&[Cursor::new_selection(0, 0, 2, 8)]);
+        buffer.toggle_line_comment_out();
+        assert_eq!(buffer.text(), "<p>\n  abc\n");
+    }
+
+    #[test]
+    fn test_comment_out_injected_language() {
+        let mut buffer = html_buffer("<script>\n  f();\n</script>");
+        buffer.set_cursors_for_test(&[Cursor::new(1, 2)]);
+        buffer.toggle_line_comment_out();
+        assert_eq!(buffer.text(), "<script>\n  // f();\n</script>");
+
+        let mut buffer = html_buffer("<script>\n  f();\n</script>");
+        buffer.set_cursors_for_test(&[Cursor::new(0, 0)]);
+        buffer.toggle_line_comment_out();
+        assert_eq!(buffer.text(), "<!-- <script> -->\n  f();\n</script>");
+    }
+
+    #[test]
+    fn test_toggle_block_comment_out() {
+        let lang = get_language_by_name("rust").unwrap();
+
+        let mut buffer = Buffer::from_text("f(a, b);");
+        buffer.set_language(lang).unwrap();
+        buffer.set_cursors_for_test(&[
+            Cursor::new_selection(0, 2, 0, 3),
+            Cursor::new_selection(0, 5, 0, 6),
+        ]);
+        buffer.toggle_block_comment_out();
+        assert_eq!(buffer.text(), "f(/* a */, /* b */);");
+        assert_eq!(
+            buffer.cursors(),
+            &[
+                Cursor::new_selection(0, 2, 0, 9),
+                Cursor::new_selection(0, 11, 0, 18),
+            ]
+        );
+
+        buffer.toggle_block_comment_out();
+        assert_eq!(buffer.text(), "f(a, b);");
+        assert_eq!(
+            buffer.cursors(),
+            &[
+                Cursor::new_selection(0, 2, 0, 3),
+                Cursor::new_selection(0, 5, 0, 6),
+            ]
+        );
+
+        // Multiple lines.
+        let mut buffer = Buffer::from_text("a\nb");
+        buffer.set_language(lang).unwrap();
+        buffer.set_cursors_for_test(&[Cursor::new_selection(0, 0, 1, 1)]);
+        buffer.toggle_block_comment_out();
+        assert_eq!(buffer.text(), "/* a\nb */");
+        assert_eq!(buffer.cursors(), &[Cursor::new_selection(0, 0, 1, 4)]);
+
+        // A selection after a multi-line one in the same line.
+        let mut buffer = Buffer::from_text("a\nb c");
+        buffer.set_language(lang).unwrap();
+        buffer.set_cursors_for_test(&[
+            Cursor::new_selection(0, 0, 1, 1),
+            Cursor::new_selection(1, 2, 1, 3),
+        ]);
+        buffer.toggle_block_comment_out();
+        assert_eq!(buffer.text(), "/* a\nb */ /* c */");
+        assert_eq!(
+            buffer.cursors(),
+            &[
+                Cursor::new_selection(0, 0, 1, 4),
+                Cursor::new_selection(1, 5, 1, 12),
+            ]
+        );
+        buffer.toggle_block_comment_out();
+        assert_eq!(buffer.text(), "a\nb c");
+
+        // No selection.
+        let mut buffer = Buffer::from_text("ab");
+        buffer.set_language(lang).unwrap();
+        buffer.set_cursors_for_test(&[Cursor::new(0, 1)]);
+        buffer.toggle_block_comment_out();
+        assert_eq!(buffer.text(), "a/*  */b");
+        assert_eq!(buffer.cursors(), &[Cursor::new(0, 4)]);
+    }
+
+    #[test]
+    fn test_remove_block_comment_at_cursor() {
+        let mut buffer = html_buffer("<p><!-- abc --></p>");
+        buffer.set_cursors_for_test(&[Cursor::new(0, 9)]);
+        buffer.toggle_block_comment_out();
+        assert_eq!(buffer.text(), "<p>abc</p>");
+        assert_eq!(buffer.cursors(), &[Cursor::new_selection(0, 3, 0, 6)]);
+    }
 }
@@ -4,13 +4,68 @@ use crate::{buffer::Buffer, cursor::Range, syntax::TsNodeExt};
 
 impl Buffer {
     pub fn expand_selections(&mut self) {
+        self.update_syntax_tree();
+        let mut history = std::mem::take(&mut self.expanded_selections);
         self.update_cursors_with(|c, buf| {
             if let Some(syntax) = buf.syntax() {
+                let selection = c.selection();
                 let root = syntax.tree().root_node();
-                let new_selection = walk_ts_node(root, &mut root.walk(), c.selection());
+                let new_selection = walk_ts_node(root, &mut root.walk(), selection);
+                if new_selection == selection {
+                    return;
+                }
+
+                // Start over if the selection has been changed by others.
+                let visited = history.entry(c.id()).or_default();
+                if visited.last() != Some(&selection) {
+                    visited.clear();
+                    visited.push(selection);
+                }
+
+                visited.push(new_selection);
                 c.select_range(new_selection);
             }
         });
+
+        self.expanded_selections = history;
+        self.forget_removed_cursors_in_expanded_selections();
+    }
+
+    /// Undoes `expand_selections`. If the selection is not expanded by it,
+    /// selects the first named child node in the selection instead.
+    pub fn shrink_selections(&mut self) {
+        self.update_syntax_tree();
+        let mut history = std::mem::take(&mut self.expanded_selections);
+        self.update_cursors_with(|c, buf| {
+            let selection = c.selection();
+            let visited = history.entry(c.id()).or_default();
+            if visited.len() >= 2 && visited.last() == Some(&selection) {
+                visited.pop();
+                c.select_range(*visited.last().unwrap());
+                return;
+            }
+
+            visited.clear();
+            if let Some(syntax) = buf.syntax() {
+                let root = syntax.tree().root_node();
+                let node = root.named_descendant_for_point_range(
+                    selection.front().into(),
+                    selection.back().into(),
+                );
+                if let Some(child) = node.and_then(|node| first_named_child_in(node, selection)) {
+                    c.select_range(child);
+                }
+            }
+        });
+
+        self.expanded_selections = history;
+        self.forget_removed_cursors_in_expanded_selections();
+    }
+
+    fn forget_removed_cursors_in_expanded_selections(&mut self) {
+        let cursors = self.cursors.as_slice();
+        self.expanded_selections
+            .retain(|id, _| cursors.iter().any(|c| c.id() == *id));
     }
 }
 
@@ -30,6 +85,28 @@ fn walk_ts_node<'tree>(
     parent.buffer_range()
 }
 
+/// Returns the range of the first named child node in `selection`.
+fn first_named_child_in(node: tree_sitter::Node<'_>, selection: Range) -> Option<Range> {
+    if selection.is_empty() {
+        return None;
+    }
+
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        let range = child.buffer_range();
+        if range == selection {
+            // The node covers the whole selection. Look into its children.
+            return first_named_child_in(child, selection);
+        }
+
+        if selection.contains_range(range) {
+            return Some(range);
+        }
+    }
+
+    None
+}
+
 #[cfg(test)]
 mod tests {
     use std::borrow::Cow;
@@ -125,4 +202,47 @@ mod tests {
             "pub fn main() {\n    if true {\n        dbg!(vec![123 + 0, 456]);\n    }\n}\n"
         );
     }
+
+    #[test]
+    fn shrink_selections() {
+        let mut b = Buffer::from_text(concat!(
+            "pub fn main() {\n",
+            "    dbg!(vec![123 + 0, 456]);\n",
+            "}\n",
+        ));
+        b.set_language(get_language_by_name("rust").unwrap())
+            .unwrap();
+
+        // Undo expand_selections.
+        b.set_cursors_for_test(&[Cursor::new(1, 17)]);
+        b.expand_selections();
+        b.expand_selections();
+        b.expand_selections();
+        assert_eq!(selected_str(&b), "(vec![123 + 0, 456])");
+        b.shrink_selections();
+        assert_eq!(selected_str(&b), "[123 + 0, 456]");
+        b.shrink_selections();
+        assert_eq!(selected_str(&b), "123");
+        b.shrink_selections();
+        assert_eq!(b.cursors(), &[Cursor::new(1, 17)]);
+
+        // Select the first named child.
+        b.set_cursors_for_test(&[Cursor::new_selection(0, 0, 2, 1)]);
+        b.shrink_selections();
+        assert_eq!(selected_str(&b), "pub");
+        b.shrink_selections();
+        assert_eq!(selected_str(&b), "pub");
+
+        b.set_cursors_for_test(&[Cursor::new_selection(1, 13, 1, 27)]);
+        b.shrink_selections();
+        assert_eq!(selected_str(&b), "123");
+
+        // The selection has been changed after expanding.
+        b.set_cursors_for_test(&[Cursor::new(1, 17)]);
+        b.expand_selections();
+        b.expand_selections();
+        b.set_cursors_for_test(&[Cursor::new_selection(1, 4, 1, 28)]);
+        b.shrink_selections();
+        assert_eq!(selected_str(&b), "dbg");
+    }
 }
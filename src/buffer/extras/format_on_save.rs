@@ -0,0 +1,223 @@
+use noa_editorconfig::{EditorConfig, IndentStyle};
+
+use crate::{
+    buffer::{Buffer, TextEdit},
+    cursor::Range,
+    raw_buffer::RawBuffer,
+};
+
+/// Returns the indentation `indent` converted into `config.indent_style`.
+fn convert_indent(indent: &str, config: &EditorConfig) -> String {
+    let tab_width = config.tab_width.max(1);
+    let mut width = 0;
+    for ch in indent.chars() {
+        if ch == '\t' {
+            width += tab_width - width % tab_width;
+        } else {
+            width += 1;
+        }
+    }
+
+    match config.indent_style {
+        IndentStyle::Tab => format!(
+            "{}{}",
+            "\t".repeat(width / tab_width),
+            " ".repeat(width % tab_width)
+        ),
+        IndentStyle::Space => " ".repeat(width),
+    }
+}
+
+/// Computes edits to clean up whitespace: trailing whitespace (if
+/// `trim_trailing_whitespace` is set), blank lines at the end of file, and
+/// indentation not in the style set in .editorconfig.
+fn compute_whitespace_edits(buf: &RawBuffer, config: &EditorConfig) -> Vec<TextEdit> {
+    let mut edits = Vec::new();
+    let last_y = buf.num_lines() - 1;
+    let last_non_blank_y = (0..=last_y)
+        .rev()
+        .find(|y| !buf.line_text(*y).trim().is_empty());
+
+    // Collapse blank lines at the end of file into a newline.
+    let first_blank_y = last_non_blank_y.map(|y| y + 1).unwrap_or(0);
+    let ends_with_blank_lines =
+        last_y > first_blank_y || (last_y == first_blank_y && buf.line_len(last_y) > 0);
+    let last_kept_y = if ends_with_blank_lines {
+        edits.push(TextEdit {
+            range: Range::new(first_blank_y, 0, last_y, buf.line_len(last_y)),
+            new_text: "".to_owned(),
+        });
+        first_blank_y.saturating_sub(1)
+    } else {
+        last_y
+    };
+
+    if last_non_blank_y.is_none() {
+        return edits;
+    }
+
+    for y in 0..=last_kept_y {
+        let line = buf.line_text(y);
+        let trimmed_len = line.trim_end().chars().count();
+        let line_len = line.chars().count();
+        if config.trim_trailing_whitespace && trimmed_len < line_len {
+            edits.push(TextEdit {
+                range: Range::new(y, trimmed_len, y, line_len),
+                new_text: "".to_owned(),
+            });
+        }
+
+        // Blank lines have no indentation to be converted. Don't convert it
+        // by the default or guessed style either: it may be wrong.
+        if trimmed_len == 0 || !config.indent_style_is_explicit {
+            continue;
+        }
+
+        let indent_len = buf.line_indent_len(y);
+        let indent: String = line.chars().take(indent_len).collect();
+        let new_indent = convert_indent(&indent, config);
+        if new_indent != indent {
+            edits.push(TextEdit {
+                range: Range::new(y, 0, y, indent_len),
+                new_text: new_indent,
+            });
+        }
+    }
+
+    edits
+}
+
+impl Buffer {
+    /// Cleans up the buffer before saving it into a file. The changes are
+    /// applied as text edits so that they can be undone and cursors follow
+    /// them.
+    pub fn format_on_save(&mut self) {
+        // Make the cleanup a separate undo step.
+        self.save_undo();
+
+        let edits = compute_whitespace_edits(&self.buf, &self.config);
+        if !edits.is_empty() {
+            self.apply_text_edits(edits);
+        }
+
+        self.ensure_insert_final_newline();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::cursor::Cursor;
+
+    fn config(indent_style: IndentStyle, trim_trailing_whitespace: bool) -> EditorConfig {
+        EditorConfig {
+            indent_style,
+            indent_style_is_explicit: true,
+            indent_size: 4,
+            tab_width: 4,
+            trim_trailing_whitespace,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_convert_indent() {
+        let spaces = config(IndentStyle::Space, false);
+        let tabs = config(IndentStyle::Tab, false);
+        assert_eq!(convert_indent("\t", &spaces), "    ");
+        assert_eq!(convert_indent("  \t", &spaces), "    ");
+        assert_eq!(convert_indent("\t  ", &spaces), "      ");
+        assert_eq!(convert_indent("      ", &tabs), "\t  ");
+        assert_eq!(convert_indent("        ", &tabs), "\t\t");
+        assert_eq!(convert_indent("", &tabs), "");
+    }
+
+    #[test]
+    fn trim_trailing_whitespace() {
+        let mut b = Buffer::from_text("abc  \n\t\n  xyz\t\n");
+        b.set_editorconfig(config(IndentStyle::Space, true));
+        b.set_cursors_for_test(&[Cursor::new(0, 5), Cursor::new(2, 4)]);
+        b.format_on_save();
+        assert_eq!(b.text(), "abc\n\n  xyz\n");
+        assert_eq!(b.cursors(), &[Cursor::new(0, 3), Cursor::new(2, 4)]);
+
+        // Disabled.
+        let mut b = Buffer::from_text("abc  \n");
+        b.set_editorconfig(config(IndentStyle::Space, false));
+        b.format_on_save();
+        assert_eq!(b.text(), "abc  \n");
+    }
+
+    #[test]
+    fn collapse_trailing_blank_lines() {
+        let mut b = Buffer::from_text("abc\n\n  \n\n");
+        b.set_editorconfig(config(IndentStyle::Space, false));
+        b.set_cursors_for_test(&[Cursor::new(3, 0)]);
+        b.format_on_save();
+        assert_eq!(b.text(), "abc\n");
+        assert_eq!(b.cursors(), &[Cursor::new(1, 0)]);
+
+        let mut b = Buffer::from_text("abc\n  ");
+        b.set_editorconfig(config(IndentStyle::Space, false));
+        b.format_on_save();
+        assert_eq!(b.text(), "abc\n");
+
+        let mut b = Buffer::from_text("abc");
+        b.set_editorconfig(config(IndentStyle::Space, false));
+        b.format_on_save();
+        assert_eq!(b.text(), "abc");
+
+        let mut b = Buffer::from_text("\n\n");
+        b.set_editorconfig(config(IndentStyle::Space, false));
+        b.format_on_save();
+        assert_eq!(b.text(), "");
+    }
+
+    #[test]
+    fn convert_indentation() {
+        let mut b = Buffer::from_text("fn f() {\n\tif x {\n\t    y();\n\t}\n}\n");
+        b.set_editorconfig(config(IndentStyle::Space, false));
+        b.set_cursors_for_test(&[Cursor::new(2, 5)]);
+        b.format_on_save();
+        assert_eq!(b.text(), "fn f() {\n    if x {\n        y();\n    }\n}\n");
+        assert_eq!(b.cursors(), &[Cursor::new(2, 8)]);
+
+        let mut b = Buffer::from_text("a\n    b\n      c\n");
+        b.set_editorconfig(config(IndentStyle::Tab, false));
+        b.format_on_save();
+        assert_eq!(b.text(), "a\n\tb\n\t  c\n");
+    }
+
+    #[test]
+    fn keep_indentation_without_explicit_style() {
+        let text = "all:\n\tcc -o a a.c\n";
+        let mut b = Buffer::from_text(text);
+        b.set_editorconfig(EditorConfig::default());
+        b.format_on_save();
+        assert_eq!(b.text(), text);
+
+        // Guessed from the contents.
+        let mut b = Buffer::from_text(text);
+        b.set_editorconfig(EditorConfig {
+            indent_style_is_explicit: false,
+            ..config(IndentStyle::Space, true)
+        });
+        b.format_on_save();
+        assert_eq!(b.text(), text);
+    }
+
+    #[test]
+    fn undo_format_on_save() {
+        let mut b = Buffer::from_text("");
+        b.set_editorconfig(config(IndentStyle::Space, true));
+        b.insert("abc  ");
+        b.format_on_save();
+        b.save_undo();
+        assert_eq!(b.text(), "abc");
+
+        b.undo();
+        assert_eq!(b.text(), "abc  ");
+    }
+}
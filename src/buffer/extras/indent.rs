@@ -1,8 +1,17 @@
-use std::{cmp::min, collections::HashMap};
+use std::{
+    cmp::min,
+    collections::{HashMap, HashSet},
+};
 
 use noa_editorconfig::{EditorConfig, IndentStyle};
+use noa_languages::tree_sitter::{self, Node};
 
-use crate::{buffer::Buffer, cursor::Range, raw_buffer::RawBuffer};
+use crate::{
+    buffer::Buffer,
+    cursor::{Position, Range},
+    raw_buffer::RawBuffer,
+    syntax::{Query, TsNodeExt},
+};
 
 fn compute_desired_indent_len(buf: &RawBuffer, config: &EditorConfig, y: usize) -> usize {
     let current_line = buf.substr(Range::new(y, 0, y, buf.line_len(y)));
@@ -27,7 +36,174 @@ fn compute_desired_indent_len(buf: &RawBuffer, config: &EditorConfig, y: usize)
     0
 }
 
+/// Returns the position of the last non-whitespace character before `pos`.
+fn last_non_whitespace_before(buf: &RawBuffer, pos: Position) -> Option<Position> {
+    let mut iter = buf.char_iter(pos);
+    while let Some(ch) = iter.prev() {
+        if !ch.is_whitespace() {
+            return Some(iter.next_position());
+        }
+    }
+
+    None
+}
+
+/// Returns the position of the first non-whitespace character at or after
+/// `pos` in the same line.
+fn first_non_whitespace_after(buf: &RawBuffer, pos: Position) -> Option<Position> {
+    buf.line_text(pos.y)
+        .chars()
+        .skip(pos.x)
+        .position(|ch| !ch.is_whitespace())
+        .map(|i| Position::new(pos.y, pos.x + i))
+}
+
+/// Returns true if the last descendant of `node` is empty, e.g. the body of
+/// `def f():` in Python which is not written yet.
+fn ends_with_empty_node(node: Node<'_>) -> bool {
+    let mut node = node;
+    while node.child_count() > 0 {
+        let last = node.child(node.child_count() - 1).unwrap();
+        if last.start_byte() == last.end_byte() {
+            return true;
+        }
+
+        node = last;
+    }
+
+    false
+}
+
+/// Computes the indentation of the line beginning at `line_break` from the
+/// captures in `indents.scm`. `line_start` is the first non-whitespace
+/// character in the line, if any.
+///
+/// - `@indent` indents the lines inside the node after its first line.
+/// - `@dedent` and `@outdent` dedent the line following the node.
+/// - `@branch` dedents the line beginning with the node, e.g. `}` and `else`.
+///
+/// Returns the indentation and whether the line begins with a `@branch` node.
+fn compute_indent_len_by_query(
+    buf: &RawBuffer,
+    tree: &tree_sitter::Tree,
+    query: &Query,
+    config: &EditorConfig,
+    line_break: Position,
+    line_start: Option<Position>,
+) -> (usize, bool) {
+    // The nodes containing the last character before the line determine the
+    // indentation.
+    let anchor = match last_non_whitespace_before(buf, line_break) {
+        Some(anchor) => anchor,
+        None => return (0, false),
+    };
+
+    let anchor_end = Position::new(anchor.y, anchor.x + 1);
+    let query_end = line_start
+        .map(|pos| Position::new(pos.y, pos.x + 1))
+        .unwrap_or(line_break)
+        .max(anchor_end);
+
+    let mut indents = HashSet::new();
+    let mut dedents = HashSet::new();
+    let mut starts_with_branch = false;
+    query.query_nodes(
+        tree,
+        buf,
+        Some(Range::from_positions(anchor, query_end)),
+        |node, capture| match capture {
+            // Newer nvim-treesitter queries are named `@indent.*`.
+            "indent" | "indent.begin" => {
+                indents.insert(node.id());
+            }
+            "dedent" | "outdent" | "indent.dedent" => {
+                dedents.insert(node.id());
+            }
+            "branch" | "indent.branch" if Some(node.buffer_range().front()) == line_start => {
+                starts_with_branch = true;
+            }
+            _ => {}
+        },
+    );
+
+    let root = tree.root_node();
+    // A closing token at the beginning of the anchor line, like `}`, closes
+    // the nodes opened in the same line as its pair.
+    let first_x = buf.line_indent_len(anchor.y);
+    let closing = root
+        .descendant_for_point_range(
+            Position::new(anchor.y, first_x).into(),
+            Position::new(anchor.y, first_x + 1).into(),
+        )
+        .filter(|node| node.child_count() == 0);
+    let is_closed_by_first_token = |node: &Node<'_>| {
+        closing.is_some_and(|closing| {
+            closing.end_position() == node.end_position()
+                && closing.parent().map(|parent| parent.start_position().row)
+                    == Some(node.start_position().row)
+        })
+    };
+
+    let mut indent_node_y = None;
+    let mut dedent = false;
+    let mut node = root.descendant_for_point_range(anchor.into(), anchor_end.into());
+    while let Some(current) = node {
+        let range = current.buffer_range();
+        if indents.contains(&current.id()) {
+            let is_inside = if range.back() > line_break {
+                true
+            } else if range.front().y == anchor.y {
+                let last_char = buf.char_iter(range.back()).prev();
+                ends_with_empty_node(current) || last_char == Some(':')
+            } else {
+                !is_closed_by_first_token(&current)
+            };
+
+            if is_inside {
+                indent_node_y = Some(range.front().y);
+                break;
+            }
+        }
+
+        if dedents.contains(&current.id()) && range.back() <= line_break {
+            dedent = true;
+        }
+
+        node = current.parent();
+    }
+
+    let mut indent_len = match indent_node_y {
+        Some(y) => buf.line_indent_len(y) + config.indent_size,
+        None => 0,
+    };
+
+    if starts_with_branch || dedent {
+        indent_len = indent_len.saturating_sub(config.indent_size);
+    }
+
+    (indent_len, starts_with_branch)
+}
+
 impl Buffer {
+    /// Computes the indentation of the line beginning at `line_break` from
+    /// `indents.scm`, or returns `None` if it's not available.
+    fn compute_indent_len_by_syntax(
+        &self,
+        line_break: Position,
+        line_start: Option<Position>,
+    ) -> Option<(usize, bool)> {
+        let syntax = self.syntax.as_ref()?;
+        let query = syntax.indents_query()?;
+        Some(compute_indent_len_by_query(
+            &self.buf,
+            syntax.tree(),
+            query,
+            &self.config,
+            line_break,
+            line_start,
+        ))
+    }
+
     pub fn indent(&mut self) {
         if let Some(cursor) = self.cursors.single_selection_cursor() {
             let ys = cursor.selection().overlapped_lines();
@@ -138,10 +314,20 @@ impl Buffer {
     }
 
     pub fn smart_insert_char(&mut self, c: char) {
-        self.insert_char(c);
+        self.insert_char_with_auto_pair(c);
 
         // Smart dedent.
-        if c == '}' {
+        self.update_syntax_tree();
+        if self
+            .syntax
+            .as_ref()
+            .and_then(|s| s.indents_query())
+            .is_some()
+        {
+            if !c.is_alphanumeric() && !c.is_whitespace() {
+                self.reindent_lines_beginning_with_branch();
+            }
+        } else if c == '}' {
             self.cursors.foreach(|c, past_cursors| {
                 if c.is_selection() {
                     return;
@@ -174,43 +360,71 @@ impl Buffer {
                 self.buf.edit_at_cursor(c, past_cursors, "}");
             });
         }
+    }
+
+    /// Re-indents the lines with cursors if they begin with a `@branch` node
+    /// like `}` and `else:`, which has just been typed.
+    fn reindent_lines_beginning_with_branch(&mut self) {
+        // Cursors are updated from the bottom.
+        let mut visited_ys = HashSet::new();
+        let mut desired_lens = Vec::new();
+        for c in self.cursors().iter().rev() {
+            let pos = c.moving_position();
+            let indent_len = self.buf.line_indent_len(pos.y);
+            let desired_len = if !c.is_selection() && pos.x > indent_len && visited_ys.insert(pos.y)
+            {
+                match self.compute_indent_len_by_syntax(
+                    Position::new(pos.y, 0),
+                    Some(Position::new(pos.y, indent_len)),
+                ) {
+                    Some((len, true)) if len != indent_len => Some(len),
+                    _ => None,
+                }
+            } else {
+                None
+            };
 
-        // Auto close.
-        let closing_char = match c {
-            '"' => '\"',
-            '\'' => '\'',
-            '`' => '`',
-            '{' => '}',
-            '(' => ')',
-            '[' => ']',
-            _ => return,
-        };
-
-        // Imitate VSCode's default behavior.
-        // https://code.visualstudio.com/api/language-extensions/language-configuration-guide
-        const AUTO_CLOSE_BEFORE: &str = ";:.,=}])>` \n\t";
+            desired_lens.push(desired_len);
+        }
 
+        let mut desired_lens = desired_lens.into_iter();
         self.cursors.foreach(|c, past_cursors| {
-            if c.is_selection() {
-                return;
-            }
-
-            let after_char = self
-                .buf
-                .char_iter(c.moving_position())
-                .next()
-                .unwrap_or('\n');
+            let desired_len = match desired_lens.next().unwrap() {
+                Some(len) => len,
+                None => return,
+            };
 
-            if AUTO_CLOSE_BEFORE.contains(after_char) {
-                self.buf
-                    .edit_at_cursor(c, past_cursors, &closing_char.to_string());
-                c.move_left(&self.buf);
-            }
+            let pos = c.moving_position();
+            let indent_len = self.buf.line_indent_len(pos.y);
+            c.select(pos.y, 0, pos.y, indent_len);
+            self.buf.edit_at_cursor(
+                c,
+                past_cursors,
+                &match self.config.indent_style {
+                    IndentStyle::Tab => "\t".repeat(desired_len),
+                    IndentStyle::Space => " ".repeat(desired_len),
+                },
+            );
+            c.move_to(pos.y, pos.x - indent_len + desired_len);
         });
     }
 
     pub fn insert_newline_and_indent(&mut self) {
+        // Compute indentation from the syntax tree before it gets outdated by
+        // the edits. Cursors are updated from the bottom.
+        self.update_syntax_tree();
+        let mut query_indent_lens = Vec::new();
+        for c in self.cursors().iter().rev() {
+            let line_start = first_non_whitespace_after(&self.buf, c.back());
+            query_indent_lens.push(
+                self.compute_indent_len_by_syntax(c.front(), line_start)
+                    .map(|(len, _)| len),
+            );
+        }
+
+        let mut query_indent_lens = query_indent_lens.into_iter();
         self.cursors.foreach(|c, past_cursors| {
+            let query_indent_len = query_indent_lens.next().unwrap();
             if !c.is_selection() {
                 let pos = c.front();
                 let line_text = self.buf.line_text(pos.y);
@@ -223,9 +437,9 @@ impl Buffer {
                     self.buf.edit_at_cursor(c, past_cursors, "\n");
 
                     // Add indentation.
-                    let indent_size =
+                    let indent_size = query_indent_len.unwrap_or_else(|| {
                         compute_desired_indent_len(&self.buf, &self.config, c.front().y)
-                            + self.config.indent_size;
+                    }) + self.config.indent_size;
                     self.buf.edit_at_cursor(
                         c,
                         past_cursors,
@@ -239,8 +453,9 @@ impl Buffer {
                     self.buf.edit_at_cursor(c, past_cursors, "\n");
 
                     // Add indentation.
-                    let indent_size =
-                        compute_desired_indent_len(&self.buf, &self.config, c.front().y);
+                    let indent_size = query_indent_len.unwrap_or_else(|| {
+                        compute_desired_indent_len(&self.buf, &self.config, c.front().y)
+                    });
                     self.buf.edit_at_cursor(
                         c,
                         past_cursors,
@@ -259,7 +474,9 @@ impl Buffer {
             self.buf.edit_at_cursor(c, past_cursors, "\n");
 
             // Add indentation.
-            let indent_size = compute_desired_indent_len(&self.buf, &self.config, c.front().y);
+            let indent_size = query_indent_len.unwrap_or_else(|| {
+                compute_desired_indent_len(&self.buf, &self.config, c.front().y)
+            });
             self.buf.edit_at_cursor(
                 c,
                 past_cursors,
@@ -423,4 +640,100 @@ mod tests {
         assert_eq!(b.text(), "    if foo {\n    }");
         assert_eq!(b.cursors(), &[Cursor::new(1, 5)]);
     }
+
+    fn buffer_with_indents_query(text: &str, lang: &str, query: &str) -> Buffer {
+        let mut b = Buffer::from_text(text);
+        b.set_language(get_language_by_name(lang).unwrap()).unwrap();
+        b.syntax.as_mut().unwrap().set_indents_query_for_test(query);
+        b
+    }
+
+    const PYTHON_INDENTS: &str = r#"
+        [(function_definition) (if_statement) (for_statement)] @indent
+        [(return_statement) (pass_statement)] @dedent
+        [(elif_clause) (else_clause) ")"] @branch
+    "#;
+
+    const RUST_INDENTS: &str = r#"
+        [(function_item) (block) (arguments)] @indent
+        ["}" ")"] @branch
+    "#;
+
+    #[test]
+    fn indent_by_query() {
+        let mut b = buffer_with_indents_query("def f():", "python", PYTHON_INDENTS);
+        b.set_cursors_for_test(&[Cursor::new(0, 8)]);
+        b.insert_newline_and_indent();
+        assert_eq!(b.text(), "def f():\n    ");
+        assert_eq!(b.cursors(), &[Cursor::new(1, 4)]);
+
+        // The syntax tree follows the edits.
+        b.insert("for x in y:");
+        b.insert_newline_and_indent();
+        assert_eq!(b.text(), "def f():\n    for x in y:\n        ");
+
+        let mut b = buffer_with_indents_query("def f():\n    x = g(a)", "python", PYTHON_INDENTS);
+        b.set_cursors_for_test(&[Cursor::new(1, 12)]);
+        b.insert_newline_and_indent();
+        assert_eq!(b.text(), "def f():\n    x = g(a)\n    ");
+
+        let mut b = buffer_with_indents_query(
+            "fn f() {\n    if x {\n        a();\n    }\n}",
+            "rust",
+            RUST_INDENTS,
+        );
+        b.set_cursors_for_test(&[Cursor::new(3, 5)]);
+        b.insert_newline_and_indent();
+        assert_eq!(
+            b.text(),
+            "fn f() {\n    if x {\n        a();\n    }\n    \n}"
+        );
+
+        let mut b = buffer_with_indents_query("fn f() {\n    g(a)\n}", "rust", RUST_INDENTS);
+        b.set_cursors_for_test(&[Cursor::new(1, 6)]);
+        b.insert_newline_and_indent();
+        assert_eq!(b.text(), "fn f() {\n    g(\n        a)\n}");
+    }
+
+    #[test]
+    fn dedent_by_query() {
+        let mut b = buffer_with_indents_query("def f():\n    return 1", "python", PYTHON_INDENTS);
+        b.set_cursors_for_test(&[Cursor::new(1, 12)]);
+        b.insert_newline_and_indent();
+        assert_eq!(b.text(), "def f():\n    return 1\n");
+
+        let mut b = buffer_with_indents_query("fn f() {\n    a();\n}", "rust", RUST_INDENTS);
+        b.set_cursors_for_test(&[Cursor::new(2, 1)]);
+        b.insert_newline_and_indent();
+        assert_eq!(b.text(), "fn f() {\n    a();\n}\n");
+
+        let mut b = buffer_with_indents_query("fn f() {}", "rust", RUST_INDENTS);
+        b.set_cursors_for_test(&[Cursor::new(0, 8)]);
+        b.insert_newline_and_indent();
+        assert_eq!(b.text(), "fn f() {\n    \n}");
+        assert_eq!(b.cursors(), &[Cursor::new(1, 4)]);
+    }
+
+    #[test]
+    fn reindent_branch_by_query() {
+        let mut b = buffer_with_indents_query("fn f() {\n    a();\n    ", "rust", RUST_INDENTS);
+        b.set_cursors_for_test(&[Cursor::new(2, 4)]);
+        b.smart_insert_char('}');
+        assert_eq!(b.text(), "fn f() {\n    a();\n}");
+        assert_eq!(b.cursors(), &[Cursor::new(2, 1)]);
+    }
+
+    #[test]
+    fn indent_yaml_by_query() {
+        let mut b = buffer_with_indents_query("a:", "yaml", "(block_mapping_pair) @indent");
+        b.set_editorconfig(EditorConfig {
+            indent_size: 2,
+            ..Default::default()
+        });
+        b.set_cursors_for_test(&[Cursor::new(0, 2)]);
+        b.insert_newline_and_indent();
+        b.insert("b: 1");
+        b.insert_newline_and_indent();
+        assert_eq!(b.text(), "a:\n  b: 1\n  ");
+    }
 }
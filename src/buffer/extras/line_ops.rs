@@ -0,0 +1,359 @@
+use std::{cmp::Ordering, iter::Peekable, str::Chars};
+
+use crate::{
+    buffer::Buffer,
+    cursor::{Cursor, Range},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortOrder {
+    Lexical,
+    /// Compares numbers in lines by their values: `a2` < `a10`.
+    Natural,
+    CaseInsensitive,
+}
+
+/// Consumes digits and returns them without leading zeros.
+fn take_number(chars: &mut Peekable<Chars<'_>>) -> String {
+    let mut digits = String::new();
+    while let Some(ch) = chars.next_if(char::is_ascii_digit) {
+        digits.push(ch);
+    }
+    digits.trim_start_matches('0').to_owned()
+}
+
+/// Compares strings splitting them into digits and non-digits.
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a_chars = a.chars().peekable();
+    let mut b_chars = b.chars().peekable();
+    loop {
+        match (a_chars.peek(), b_chars.peek()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let x = take_number(&mut a_chars);
+                let y = take_number(&mut b_chars);
+                match x.len().cmp(&y.len()).then_with(|| x.cmp(&y)) {
+                    Ordering::Equal => {}
+                    ordering => return ordering,
+                }
+            }
+            (Some(x), Some(y)) => match x.cmp(y) {
+                Ordering::Equal => {
+                    a_chars.next();
+                    b_chars.next();
+                }
+                ordering => return ordering,
+            },
+        }
+    }
+}
+
+/// Joins `next` to `prev` like `J` in Vim: leading whitespace and the comment
+/// keyword in `next` are removed and a space is inserted if needed.
+fn join_two_lines(prev: &str, next: &str, line_comment: Option<&str>) -> String {
+    let prev = prev.trim_end();
+    let mut next = next.trim_start();
+    if let Some(keyword) = line_comment {
+        if prev.trim_start().starts_with(keyword) {
+            if let Some(rest) = next.strip_prefix(keyword) {
+                next = rest.trim_start();
+            }
+        }
+    }
+
+    let needs_space = !prev.is_empty()
+        && !next.is_empty()
+        && !prev.ends_with(['(', '['])
+        && !next.starts_with([')', ']', '}', ',', '.', ';']);
+    if needs_space {
+        format!("{} {}", prev, next)
+    } else {
+        format!("{}{}", prev, next)
+    }
+}
+
+impl Buffer {
+    pub fn sort_lines(&mut self, order: SortOrder) {
+        self.transform_lines(false, |lines| {
+            let mut lines = lines.to_vec();
+            match order {
+                SortOrder::Lexical => lines.sort(),
+                SortOrder::Natural => lines.sort_by(|a, b| natural_cmp(a, b)),
+                SortOrder::CaseInsensitive => {
+                    lines.sort_by(|a, b| a.to_lowercase().cmp(&b.to_lowercase()).then(a.cmp(b)))
+                }
+            }
+            lines
+        });
+    }
+
+    pub fn reverse_lines(&mut self) {
+        self.transform_lines(false, |lines| lines.iter().rev().cloned().collect());
+    }
+
+    pub fn shuffle_lines(&mut self) {
+        self.transform_lines(false, |lines| {
+            let mut lines = lines.to_vec();
+            fastrand::shuffle(&mut lines);
+            lines
+        });
+    }
+
+    /// Removes duplicated lines. The first occurrence is kept.
+    pub fn unique_lines(&mut self) {
+        self.transform_lines(false, |lines| {
+            let mut unique: Vec<String> = Vec::new();
+            for line in lines {
+                if !unique.contains(line) {
+                    unique.push(line.clone());
+                }
+            }
+            unique
+        });
+    }
+
+    /// Joins the selected lines, or the current line and the next one.
+    pub fn join_lines(&mut self) {
+        let line_comment = self.language().line_comment;
+        self.transform_lines(true, |lines| {
+            let mut joined = lines[0].clone();
+            for line in &lines[1..] {
+                joined = join_two_lines(&joined, line, line_comment);
+            }
+            vec![joined]
+        });
+    }
+
+    /// Returns the lines to be transformed. Each group is a sorted list of
+    /// lines: lines in a selection, or lines with cursors.
+    fn line_groups(&self, with_next_line: bool) -> Vec<Vec<usize>> {
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        for c in self.cursors() {
+            let front = c.front();
+            let back = c.back();
+            let mut last_y = if back.y > front.y && back.x == 0 {
+                back.y - 1
+            } else {
+                back.y
+            };
+
+            if with_next_line && last_y == front.y {
+                last_y = (front.y + 1).min(self.buf.num_lines() - 1);
+            }
+
+            match groups.last_mut() {
+                Some(group) if *group.last().unwrap() >= front.y => {
+                    group.extend((group.last().unwrap() + 1)..=last_y);
+                }
+                _ => groups.push((front.y..=last_y).collect()),
+            }
+        }
+
+        // Multiple cursors without selections: transform the lines with
+        // cursors together.
+        if !with_next_line && groups.len() > 1 && groups.iter().all(|g| g.len() == 1) {
+            groups = vec![groups.into_iter().flatten().collect()];
+        }
+
+        groups
+    }
+
+    /// Replaces lines in each group with `f(lines)` and selects them.
+    fn transform_lines<F>(&mut self, with_next_line: bool, mut f: F)
+    where
+        F: FnMut(&[String]) -> Vec<String>,
+    {
+        let main_y = self.main_cursor().front().y;
+        let mut edits = Vec::new();
+        let mut new_selections = Vec::new();
+        let mut main_index = None;
+        let mut y_diff: isize = 0;
+        for ys in self.line_groups(with_next_line) {
+            let lines: Vec<String> = ys.iter().map(|y| self.buf.line_text(*y)).collect();
+            let new_lines = f(&lines);
+            let is_block = ys.windows(2).all(|w| w[0] + 1 == w[1]);
+            if is_block {
+                let first_y = ys[0];
+                let last_y = *ys.last().unwrap();
+                let new_first_y = (first_y as isize + y_diff) as usize;
+                if ys.contains(&main_y) {
+                    main_index = Some(new_selections.len());
+                }
+
+                let last_len = new_lines.last().map(|l| l.chars().count()).unwrap_or(0);
+                new_selections.push(Range::new(
+                    new_first_y,
+                    0,
+                    new_first_y + new_lines.len().saturating_sub(1),
+                    last_len,
+                ));
+                edits.push((
+                    Range::new(first_y, 0, last_y, self.buf.line_len(last_y)),
+                    new_lines.join("\n"),
+                ));
+                y_diff += new_lines.len() as isize - ys.len() as isize;
+            } else {
+                // Only the last lines are removed so the lines above don't move.
+                for (i, y) in ys.iter().enumerate() {
+                    match new_lines.get(i) {
+                        Some(new_line) => {
+                            if *y == main_y {
+                                main_index = Some(new_selections.len());
+                            }
+
+                            new_selections.push(Range::new(*y, 0, *y, new_line.chars().count()));
+                            edits.push((
+                                Range::new(*y, 0, *y, self.buf.line_len(*y)),
+                                new_line.clone(),
+                            ));
+                        }
+                        None if *y + 1 < self.buf.num_lines() => {
+                            edits.push((Range::new(*y, 0, *y + 1, 0), String::new()));
+                        }
+                        None => {
+                            let prev_y = y - 1;
+                            let range = Range::new(
+                                prev_y,
+                                self.buf.line_len(prev_y),
+                                *y,
+                                self.buf.line_len(*y),
+                            );
+                            edits.push((range, String::new()));
+                        }
+                    }
+                }
+            }
+        }
+
+        // Apply edits from the bottom so that ranges of other edits don't move.
+        for (range, new_text) in edits.iter().rev() {
+            self.buf.edit(*range, new_text);
+        }
+
+        let main_index = main_index.unwrap_or(new_selections.len() - 1);
+        let new_cursors: Vec<Cursor> = new_selections
+            .into_iter()
+            .enumerate()
+            .map(|(i, range)| {
+                if i == main_index {
+                    let mut main = self.main_cursor().clone();
+                    main.select_range(range);
+                    main
+                } else {
+                    Cursor::from_range(range)
+                }
+            })
+            .collect();
+
+        self.update_cursors(&new_cursors);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_natural_cmp() {
+        assert_eq!(natural_cmp("a2", "a10"), Ordering::Less);
+        assert_eq!(natural_cmp("a10", "a2"), Ordering::Greater);
+        assert_eq!(natural_cmp("a02", "a2"), Ordering::Less);
+        assert_eq!(natural_cmp("a", "a1"), Ordering::Less);
+        assert_eq!(natural_cmp("b1", "a2"), Ordering::Greater);
+    }
+
+    #[test]
+    fn sort_selected_lines() {
+        let mut b = Buffer::from_text("x\nc\na10\nB\na2\ny");
+        b.set_cursors_for_test(&[Cursor::new_selection(1, 0, 5, 0)]);
+        b.sort_lines(SortOrder::Lexical);
+        assert_eq!(b.text(), "x\nB\na10\na2\nc\ny");
+        assert_eq!(b.cursors(), &[Cursor::new_selection(1, 0, 4, 1)]);
+
+        b.sort_lines(SortOrder::Natural);
+        assert_eq!(b.text(), "x\nB\na2\na10\nc\ny");
+
+        b.sort_lines(SortOrder::CaseInsensitive);
+        assert_eq!(b.text(), "x\na10\na2\nB\nc\ny");
+    }
+
+    #[test]
+    fn sort_lines_with_cursors() {
+        let mut b = Buffer::from_text("c\nx\na\nb");
+        b.set_cursors_for_test(&[Cursor::new(0, 0), Cursor::new(2, 0), Cursor::new(3, 0)]);
+        b.sort_lines(SortOrder::Lexical);
+        assert_eq!(b.text(), "a\nx\nb\nc");
+        assert_eq!(
+            b.cursors(),
+            &[
+                Cursor::new_selection(0, 0, 0, 1),
+                Cursor::new_selection(2, 0, 2, 1),
+                Cursor::new_selection(3, 0, 3, 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn reverse_and_shuffle_lines() {
+        let mut b = Buffer::from_text("a\nb\nc\n");
+        b.set_cursors_for_test(&[Cursor::new_selection(0, 0, 3, 0)]);
+        b.reverse_lines();
+        assert_eq!(b.text(), "c\nb\na\n");
+
+        b.shuffle_lines();
+        let mut lines: Vec<_> = b.text().lines().map(str::to_owned).collect();
+        lines.sort();
+        assert_eq!(lines, vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn unique_lines() {
+        let mut b = Buffer::from_text("a\nb\na\nc\nb\nz");
+        b.set_cursors_for_test(&[Cursor::new_selection(0, 0, 4, 1)]);
+        b.unique_lines();
+        assert_eq!(b.text(), "a\nb\nc\nz");
+        assert_eq!(b.cursors(), &[Cursor::new_selection(0, 0, 2, 1)]);
+
+        // Lines with cursors.
+        let mut b = Buffer::from_text("a\nx\na\ny\nb");
+        b.set_cursors_for_test(&[Cursor::new(0, 0), Cursor::new(2, 0), Cursor::new(4, 0)]);
+        b.unique_lines();
+        assert_eq!(b.text(), "a\nx\nb\ny");
+    }
+
+    #[test]
+    fn join_lines() {
+        let mut b = Buffer::from_text("foo(\n    a,\n    b\n)\nbar");
+        b.set_cursors_for_test(&[Cursor::new_selection(0, 0, 3, 1)]);
+        b.join_lines();
+        assert_eq!(b.text(), "foo(a, b)\nbar");
+        assert_eq!(b.cursors(), &[Cursor::new_selection(0, 0, 0, 9)]);
+
+        // The current line and the next one.
+        let mut b = Buffer::from_text("abc\n  def\nxyz");
+        b.set_cursors_for_test(&[Cursor::new(0, 1)]);
+        b.join_lines();
+        assert_eq!(b.text(), "abc def\nxyz");
+
+        // The last line.
+        let mut b = Buffer::from_text("abc");
+        b.set_cursors_for_test(&[Cursor::new(0, 1)]);
+        b.join_lines();
+        assert_eq!(b.text(), "abc");
+    }
+
+    #[test]
+    fn test_join_two_lines() {
+        assert_eq!(
+            join_two_lines("// abc", "  // def", Some("//")),
+            "// abc def"
+        );
+        assert_eq!(join_two_lines("abc", "// def", Some("//")), "abc // def");
+        assert_eq!(join_two_lines("abc  ", "", None), "abc");
+        assert_eq!(join_two_lines("f(a", ");", None), "f(a);");
+    }
+}
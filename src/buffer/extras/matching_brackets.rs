@@ -2,14 +2,18 @@ use crate::{
     buffer::Buffer,
     char_iter::CharIter,
     cursor::{Position, Range},
+    syntax::TsNodeExt,
 };
 
 impl Buffer {
-    pub fn matching_bracket(&mut self, pos: Position) -> Option<Range> {
+    /// Returns the matching bracket of the one at `pos`. Brackets in strings
+    /// and comments only match the ones in the same string or comment.
+    pub fn matching_bracket(&self, pos: Position) -> Option<Range> {
         let (mut char_iter, opening, start_ch, end_ch) = self.find_bracket_nearby(pos)?;
         debug_assert_ne!(start_ch, end_ch);
 
         let start_pos = char_iter.last_position();
+        let scope = self.string_or_comment_at(start_pos);
         let mut nested = 0;
         while let Some(ch) = if opening {
             char_iter.next()
@@ -20,6 +24,12 @@ impl Buffer {
                 continue;
             }
 
+            if (ch == start_ch || ch == end_ch)
+                && self.string_or_comment_at(char_iter.last_position()) != scope
+            {
+                continue;
+            }
+
             if ch == start_ch {
                 nested += 1;
             }
@@ -38,7 +48,7 @@ impl Buffer {
         None
     }
 
-    pub fn find_bracket_nearby(&mut self, pos: Position) -> Option<(CharIter, bool, char, char)> {
+    pub fn find_bracket_nearby(&self, pos: Position) -> Option<(CharIter, bool, char, char)> {
         let get_corresponding_char = |c: char| match c {
             '(' => Some((true, ')')),
             '{' => Some((true, '}')),
@@ -69,19 +79,41 @@ impl Buffer {
 
         None
     }
+
+    /// Returns the range of the outermost string or comment containing the
+    /// character at `pos` in the syntax tree.
+    pub(crate) fn string_or_comment_at(&self, pos: Position) -> Option<Range> {
+        let end = Position::new(pos.y, pos.x + 1);
+        let mut node = self
+            .syntax()?
+            .tree()
+            .root_node()
+            .descendant_for_point_range(pos.into(), end.into());
+        let mut found = None;
+        while let Some(current) = node {
+            if current.kind().contains("string") || current.kind().contains("comment") {
+                found = Some(current.buffer_range());
+            }
+
+            node = current.parent();
+        }
+
+        found
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use noa_languages::get_language_by_name;
     use pretty_assertions::assert_eq;
 
     #[test]
     fn matching_bracket() {
-        let mut b = Buffer::from_text("");
+        let b = Buffer::from_text("");
         assert_eq!(b.matching_bracket(Position::new(0, 0)), None);
 
-        let mut b = Buffer::from_text("{}");
+        let b = Buffer::from_text("{}");
         assert_eq!(
             b.matching_bracket(Position::new(0, 0)),
             Some(Range::new(0, 1, 0, 2))
@@ -91,7 +123,7 @@ mod tests {
             Some(Range::new(0, 0, 0, 1))
         );
 
-        let mut b = Buffer::from_text("{{{}}}");
+        let b = Buffer::from_text("{{{}}}");
         assert_eq!(
             b.matching_bracket(Position::new(0, 0)),
             Some(Range::new(0, 5, 0, 6))
@@ -101,7 +133,7 @@ mod tests {
             Some(Range::new(0, 4, 0, 5))
         );
 
-        let mut b = Buffer::from_text("{abc}");
+        let b = Buffer::from_text("{abc}");
         assert_eq!(
             b.matching_bracket(Position::new(0, 0)),
             Some(Range::new(0, 4, 0, 5))
@@ -119,4 +151,25 @@ mod tests {
             Some(Range::new(0, 0, 0, 1))
         );
     }
+
+    #[test]
+    fn matching_bracket_in_strings() {
+        let mut b = Buffer::from_text("f(\")\", \"()\") // (");
+        b.set_language(get_language_by_name("rust").unwrap())
+            .unwrap();
+        assert_eq!(
+            b.matching_bracket(Position::new(0, 1)),
+            Some(Range::new(0, 11, 0, 12))
+        );
+        assert_eq!(
+            b.matching_bracket(Position::new(0, 11)),
+            Some(Range::new(0, 1, 0, 2))
+        );
+        assert_eq!(
+            b.matching_bracket(Position::new(0, 8)),
+            Some(Range::new(0, 9, 0, 10))
+        );
+        assert_eq!(b.matching_bracket(Position::new(0, 3)), None);
+        assert_eq!(b.matching_bracket(Position::new(0, 16)), None);
+    }
 }
@@ -1,9 +1,18 @@
+pub mod auto_pair;
+pub mod block_selection;
 pub mod comment_out;
 pub mod duplicate_lines;
 pub mod edit_words;
 pub mod expand_selections;
+pub mod format_on_save;
 pub mod indent;
+pub mod line_ops;
 pub mod matching_brackets;
 pub mod move_lines;
+pub mod next_occurrence;
+pub mod reflow;
+pub mod replace;
 pub mod select_lines;
+pub mod surround;
+pub mod text_objects;
 pub mod truncate;
@@ -0,0 +1,201 @@
+use crate::{
+    buffer::Buffer,
+    cursor::{Position, Range},
+    find::FindOptions,
+};
+
+impl Buffer {
+    /// Returns the text of the main selection and how to search it. A word
+    /// is searched as a whole word.
+    fn occurrence_query(&self) -> Option<(String, FindOptions)> {
+        let selection = self.main_cursor().selection();
+        if selection.is_empty() {
+            return None;
+        }
+
+        let options = if self.current_word(selection.front()) == Some(selection) {
+            FindOptions::whole_word()
+        } else {
+            FindOptions::default()
+        };
+
+        Some((self.substr(selection), options))
+    }
+
+    /// Returns the first occurrence after `pos` which is not selected by any
+    /// cursor. Wraps around at the end of the buffer.
+    fn next_unselected_occurrence(
+        &self,
+        query: &str,
+        pos: Position,
+        options: FindOptions,
+    ) -> Option<Range> {
+        let is_unselected = |range: &Range| {
+            !self
+                .cursors()
+                .iter()
+                .any(|c| c.selection().overlaps_with(*range))
+        };
+
+        self.find_iter(query, pos, options)
+            .find(is_unselected)
+            .or_else(|| {
+                self.find_iter(query, Position::new(0, 0), options)
+                    .take_while(|range| range.front() < pos)
+                    .find(is_unselected)
+            })
+    }
+
+    /// Adds a cursor on the next occurrence of the main selection, searching
+    /// from the last added cursor. If the main cursor is not a selection,
+    /// selects the word at it instead.
+    ///
+    /// Returns false if there's nothing to add.
+    pub fn add_next_occurrence(&mut self) -> bool {
+        let main = self.main_cursor();
+        if !main.is_selection() {
+            return match self.current_word(main.moving_position()) {
+                Some(range) => {
+                    self.select_main_cursor_range(range);
+                    true
+                }
+                None => false,
+            };
+        }
+
+        let (query, options) = match self.occurrence_query() {
+            Some(query) => query,
+            None => return false,
+        };
+
+        let last = self.cursors.last_added_cursor().unwrap_or(main).selection();
+        match self.next_unselected_occurrence(&query, last.back(), options) {
+            Some(range) => {
+                self.add_cursor(range);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Moves the last added cursor (or the main cursor if there's none) to
+    /// the next occurrence of the main selection.
+    ///
+    /// Returns false if there's nothing to skip.
+    pub fn skip_occurrence(&mut self) -> bool {
+        let (query, options) = match self.occurrence_query() {
+            Some(query) => query,
+            None => return false,
+        };
+
+        match self.cursors.last_added_cursor().map(|c| c.selection()) {
+            Some(last) => {
+                self.cursors.remove_last_added_cursor();
+                if let Some(range) = self.next_unselected_occurrence(&query, last.back(), options) {
+                    self.add_cursor(range);
+                }
+            }
+            None => {
+                let main = self.main_cursor().selection();
+                match self.next_unselected_occurrence(&query, main.back(), options) {
+                    Some(range) => self.select_main_cursor_range(range),
+                    None => return false,
+                }
+            }
+        }
+
+        true
+    }
+
+    /// Removes the cursor added most recently. Returns false if there's no
+    /// such cursor.
+    pub fn remove_last_added_cursor(&mut self) -> bool {
+        self.cursors.remove_last_added_cursor()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cursor::Cursor;
+
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn selections(b: &Buffer) -> Vec<Range> {
+        b.cursors().iter().map(|c| c.selection()).collect()
+    }
+
+    #[test]
+    fn test_add_next_occurrence() {
+        let mut b = Buffer::from_text("foo bar foo foobar foo");
+        b.set_cursors_for_test(&[Cursor::new(0, 1)]);
+        assert!(b.add_next_occurrence());
+        assert_eq!(selections(&b), vec![Range::new(0, 0, 0, 3)]);
+        assert!(b.add_next_occurrence());
+        assert_eq!(
+            selections(&b),
+            vec![Range::new(0, 0, 0, 3), Range::new(0, 8, 0, 11)]
+        );
+        // "foobar" is skipped since "foo" is searched as a whole word.
+        assert!(b.add_next_occurrence());
+        assert_eq!(
+            selections(&b),
+            vec![
+                Range::new(0, 0, 0, 3),
+                Range::new(0, 8, 0, 11),
+                Range::new(0, 19, 0, 22)
+            ]
+        );
+        assert!(!b.add_next_occurrence());
+
+        // Wrap around.
+        let mut b = Buffer::from_text("ab ab ab");
+        b.set_cursors_for_test(&[Cursor::new_selection(0, 3, 0, 5)]);
+        assert!(b.add_next_occurrence());
+        assert!(b.add_next_occurrence());
+        assert_eq!(
+            selections(&b),
+            vec![
+                Range::new(0, 0, 0, 2),
+                Range::new(0, 3, 0, 5),
+                Range::new(0, 6, 0, 8)
+            ]
+        );
+        assert_eq!(b.main_cursor().selection(), Range::new(0, 3, 0, 5));
+    }
+
+    #[test]
+    fn test_skip_occurrence() {
+        let mut b = Buffer::from_text("x.y x.y x.y");
+        b.set_cursors_for_test(&[Cursor::new_selection(0, 0, 0, 3)]);
+        assert!(b.add_next_occurrence());
+        assert!(b.skip_occurrence());
+        assert_eq!(
+            selections(&b),
+            vec![Range::new(0, 0, 0, 3), Range::new(0, 8, 0, 11)]
+        );
+
+        // Without added cursors, the main cursor moves.
+        let mut b = Buffer::from_text("x.y x.y x.y");
+        b.set_cursors_for_test(&[Cursor::new_selection(0, 0, 0, 3)]);
+        assert!(b.skip_occurrence());
+        assert_eq!(selections(&b), vec![Range::new(0, 4, 0, 7)]);
+    }
+
+    #[test]
+    fn test_remove_last_added_cursor() {
+        let mut b = Buffer::from_text("a a a");
+        b.set_cursors_for_test(&[Cursor::new_selection(0, 0, 0, 1)]);
+        assert!(!b.remove_last_added_cursor());
+        assert!(b.add_next_occurrence());
+        assert!(b.add_next_occurrence());
+        assert!(b.remove_last_added_cursor());
+        assert_eq!(
+            selections(&b),
+            vec![Range::new(0, 0, 0, 1), Range::new(0, 2, 0, 3)]
+        );
+        assert!(b.remove_last_added_cursor());
+        assert_eq!(selections(&b), vec![Range::new(0, 0, 0, 1)]);
+        assert!(!b.remove_last_added_cursor());
+    }
+}
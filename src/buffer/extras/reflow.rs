@@ -0,0 +1,369 @@
+use crate::{
+    buffer::{Buffer, TextEdit},
+    cursor::Range,
+    display_width::DisplayWidth,
+    raw_buffer::RawBuffer,
+};
+
+const DEFAULT_MAX_LINE_LENGTH: usize = 80;
+
+/// Splits a line into the prefix (indentation and the comment keyword) and
+/// the contents.
+fn split_prefix<'a>(line: &'a str, line_comment: Option<&str>) -> (&'a str, &'a str) {
+    let mut prefix_len = line.len() - line.trim_start().len();
+    if let Some(keyword) = line_comment {
+        if line[prefix_len..].starts_with(keyword) {
+            prefix_len += keyword.len();
+            // Doc comments like `///` and `//!`.
+            prefix_len += line[prefix_len..]
+                .chars()
+                .take_while(|ch| keyword.contains(*ch) || *ch == '!')
+                .map(char::len_utf8)
+                .sum::<usize>();
+            prefix_len += line[prefix_len..].len() - line[prefix_len..].trim_start().len();
+        }
+    }
+
+    line.split_at(prefix_len)
+}
+
+/// Returns the length of the list marker (`- `, `* `, `+ `, or `1. `) at the
+/// beginning of `text`.
+fn bullet_len(text: &str) -> Option<usize> {
+    let digits = text.chars().take_while(|ch| ch.is_ascii_digit()).count();
+    let marker_len = if digits > 0 && matches!(text[digits..].chars().next(), Some('.' | ')')) {
+        digits + 1
+    } else if matches!(text.chars().next(), Some('-' | '*' | '+')) {
+        1
+    } else {
+        return None;
+    };
+
+    let spaces = text[marker_len..]
+        .chars()
+        .take_while(|ch| *ch == ' ')
+        .count();
+    if spaces == 0 {
+        return None;
+    }
+
+    Some(marker_len + spaces)
+}
+
+fn is_wide(ch: char) -> bool {
+    ch.display_width() > 1
+}
+
+/// A unit of wrapping: a word or a wide character, which can be placed at
+/// the beginning of a line.
+struct Token<'a> {
+    text: &'a str,
+    space_before: bool,
+}
+
+/// Splits `text` into tokens. Lines can be broken between wide characters
+/// (e.g. CJK) even if there're no spaces between them.
+fn tokenize(text: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    for word in text.split_whitespace() {
+        let mut space_before = true;
+        let mut start = 0;
+        for (i, ch) in word.char_indices() {
+            let end = i + ch.len_utf8();
+            let next_is_wide = word[end..].chars().next().map(is_wide);
+            if is_wide(ch) || next_is_wide == Some(true) || next_is_wide.is_none() {
+                tokens.push(Token {
+                    text: &word[start..end],
+                    space_before,
+                });
+                space_before = false;
+                start = end;
+            }
+        }
+    }
+
+    tokens
+}
+
+/// Wraps `tokens` into lines not wider than `max_width`, except for too long
+/// words.
+fn wrap(
+    tokens: &[Token<'_>],
+    first_prefix: &str,
+    rest_prefix: &str,
+    max_width: usize,
+) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = first_prefix.to_owned();
+    let mut width = line.display_width();
+    let mut is_empty = true;
+    for token in tokens {
+        let space = if token.space_before && !is_empty {
+            1
+        } else {
+            0
+        };
+        let token_width = token.text.display_width();
+        if !is_empty && width + space + token_width > max_width {
+            lines.push(line);
+            line = rest_prefix.to_owned();
+            width = line.display_width();
+            is_empty = true;
+        }
+
+        if token.space_before && !is_empty {
+            line.push(' ');
+            width += 1;
+        }
+
+        line.push_str(token.text);
+        width += token_width;
+        is_empty = false;
+    }
+
+    lines.push(line);
+    lines
+}
+
+/// Reflows lines `ys`. Blank lines and list items separate paragraphs.
+fn reflow_lines(
+    buf: &RawBuffer,
+    ys: std::ops::Range<usize>,
+    line_comment: Option<&str>,
+    max_width: usize,
+) -> String {
+    let lines: Vec<String> = ys.map(|y| buf.line_text(y)).collect();
+    let prefix = split_prefix(&lines[0], line_comment).0.to_owned();
+
+    let mut new_lines = Vec::new();
+    // The first line prefix, the continuation line prefix, and the text.
+    let mut paragraph: Option<(String, String, String)> = None;
+    let flush = |paragraph: &mut Option<(String, String, String)>, new_lines: &mut Vec<String>| {
+        if let Some((first_prefix, rest_prefix, text)) = paragraph.take() {
+            // Join lines without spaces between wide characters.
+            let tokens = tokenize(&text);
+            new_lines.extend(wrap(&tokens, &first_prefix, &rest_prefix, max_width));
+        }
+    };
+
+    for line in &lines {
+        let (_, content) = split_prefix(line, line_comment);
+        let content = content.trim_end();
+        if content.is_empty() {
+            flush(&mut paragraph, &mut new_lines);
+            new_lines.push(prefix.trim_end().to_owned());
+            continue;
+        }
+
+        if let Some(len) = bullet_len(content) {
+            flush(&mut paragraph, &mut new_lines);
+            let first_prefix = format!("{}{}", prefix, &content[..len]);
+            let rest_prefix = format!("{}{}", prefix, " ".repeat(len));
+            paragraph = Some((first_prefix, rest_prefix, content[len..].to_owned()));
+            continue;
+        }
+
+        match &mut paragraph {
+            Some((_, _, text)) => {
+                let joins_wide_chars = text.chars().last().map(is_wide) == Some(true)
+                    && content.chars().next().map(is_wide) == Some(true);
+                if !joins_wide_chars {
+                    text.push(' ');
+                }
+                text.push_str(content);
+            }
+            None => {
+                paragraph = Some((prefix.clone(), prefix.clone(), content.to_owned()));
+            }
+        }
+    }
+
+    flush(&mut paragraph, &mut new_lines);
+    new_lines.join("\n")
+}
+
+/// Returns the lines of the paragraph at `y`: adjacent non-blank lines with
+/// the same prefix.
+fn paragraph_around(
+    buf: &RawBuffer,
+    y: usize,
+    line_comment: Option<&str>,
+) -> Option<std::ops::Range<usize>> {
+    let line = buf.line_text(y);
+    let (prefix, content) = split_prefix(&line, line_comment);
+    if content.trim().is_empty() {
+        return None;
+    }
+
+    let prefix = prefix.trim_end().to_owned();
+    let in_paragraph = |y: usize| {
+        let line = buf.line_text(y);
+        let (other_prefix, content) = split_prefix(&line, line_comment);
+        other_prefix.trim_end() == prefix && !content.trim().is_empty()
+    };
+
+    let mut start = y;
+    while start > 0 && in_paragraph(start - 1) {
+        start -= 1;
+    }
+
+    let mut end = y + 1;
+    while end < buf.num_lines() && in_paragraph(end) {
+        end += 1;
+    }
+
+    Some(start..end)
+}
+
+impl Buffer {
+    /// Re-wraps the selected lines or the paragraph at the cursor to
+    /// `max_line_length` in the editorconfig.
+    pub fn reflow_paragraph(&mut self) {
+        let line_comment = self.language().line_comment;
+        let max_width = self
+            .config
+            .max_line_length
+            .unwrap_or(DEFAULT_MAX_LINE_LENGTH);
+
+        let mut edits: Vec<TextEdit> = Vec::new();
+        for c in self.cursors() {
+            let selection = c.selection();
+            let ys = if selection.is_empty() {
+                match paragraph_around(&self.buf, selection.front().y, line_comment) {
+                    Some(ys) => ys,
+                    None => continue,
+                }
+            } else {
+                let back = selection.back();
+                let end_y = if back.x == 0 && back.y > selection.front().y {
+                    back.y
+                } else {
+                    back.y + 1
+                };
+                selection.front().y..end_y
+            };
+
+            let last_y = ys.end - 1;
+            let range = Range::new(ys.start, 0, last_y, self.buf.line_len(last_y));
+            if edits.iter().any(|edit| edit.range.overlaps_with(range)) {
+                continue;
+            }
+
+            edits.push(TextEdit {
+                range,
+                new_text: reflow_lines(&self.buf, ys, line_comment, max_width),
+            });
+        }
+
+        self.apply_text_edits(edits);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use noa_editorconfig::EditorConfig;
+    use noa_languages::get_language_by_name;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::cursor::Cursor;
+
+    fn reflow(text: &str, lang: &str, max_line_length: usize, cursor: Cursor) -> String {
+        let mut b = Buffer::from_text(text);
+        if lang != "plain" {
+            b.set_language(get_language_by_name(lang).unwrap()).unwrap();
+        }
+        b.set_editorconfig(EditorConfig {
+            max_line_length: Some(max_line_length),
+            ..Default::default()
+        });
+        b.set_cursors_for_test(&[cursor]);
+        b.reflow_paragraph();
+        b.text()
+    }
+
+    #[test]
+    fn test_split_prefix() {
+        assert_eq!(split_prefix("  abc", None), ("  ", "abc"));
+        assert_eq!(split_prefix("  // abc", Some("//")), ("  // ", "abc"));
+        assert_eq!(split_prefix("/// abc", Some("//")), ("/// ", "abc"));
+        assert_eq!(split_prefix("//! abc", Some("//")), ("//! ", "abc"));
+        assert_eq!(split_prefix("# abc", Some("//")), ("", "# abc"));
+    }
+
+    #[test]
+    fn test_bullet_len() {
+        assert_eq!(bullet_len("- abc"), Some(2));
+        assert_eq!(bullet_len("*  abc"), Some(3));
+        assert_eq!(bullet_len("12. abc"), Some(4));
+        assert_eq!(bullet_len("-abc"), None);
+        assert_eq!(bullet_len("abc"), None);
+    }
+
+    #[test]
+    fn reflow_plain_paragraph() {
+        assert_eq!(
+            reflow(
+                "aaa bbb ccc ddd\neee\n\nfff ggg\n",
+                "plain",
+                8,
+                Cursor::new(1, 0)
+            ),
+            "aaa bbb\nccc ddd\neee\n\nfff ggg\n"
+        );
+
+        // Too long words are not broken.
+        assert_eq!(
+            reflow("abcdefghij k", "plain", 4, Cursor::new(0, 0)),
+            "abcdefghij\nk"
+        );
+    }
+
+    #[test]
+    fn reflow_comments() {
+        assert_eq!(
+            reflow(
+                "fn f() {\n    /// aaa bbb\n    /// ccc\n    let x = 1;\n}",
+                "rust",
+                20,
+                Cursor::new(1, 8)
+            ),
+            "fn f() {\n    /// aaa bbb ccc\n    let x = 1;\n}"
+        );
+
+        assert_eq!(
+            reflow(
+                "// aaa bbb ccc ddd\n//\n// eee",
+                "rust",
+                11,
+                Cursor::new_selection(0, 0, 2, 3)
+            ),
+            "// aaa bbb\n// ccc ddd\n//\n// eee"
+        );
+    }
+
+    #[test]
+    fn reflow_bullets() {
+        assert_eq!(
+            reflow(
+                "- aaa bbb ccc\n- ddd\n  eee\n",
+                "plain",
+                9,
+                Cursor::new_selection(0, 0, 3, 0)
+            ),
+            "- aaa bbb\n  ccc\n- ddd eee\n"
+        );
+    }
+
+    #[test]
+    fn reflow_wide_chars() {
+        assert_eq!(
+            reflow("あいうえお\nかき", "plain", 6, Cursor::new(0, 0)),
+            "あいう\nえおか\nき"
+        );
+        assert_eq!(
+            reflow("abc あいう", "plain", 8, Cursor::new(0, 0)),
+            "abc あい\nう"
+        );
+    }
+}
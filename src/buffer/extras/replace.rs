@@ -0,0 +1,174 @@
+use regex_automata::{meta::Regex, util::syntax, Anchored, Input};
+
+use crate::{
+    buffer::{Buffer, TextEdit},
+    cursor::{Position, Range},
+    find::{FindError, FindOptions},
+};
+
+impl Buffer {
+    /// Returns edits which replace occurrences of `query` in `range` with
+    /// `replacement`.
+    ///
+    /// If `is_regex` is true, `query` is a regular expression and `$1` or
+    /// `${name}` in `replacement` are replaced with the captured text (use `$$`
+    /// for a literal `$`).
+    pub fn replace_edits(
+        &self,
+        query: &str,
+        replacement: &str,
+        is_regex: bool,
+        options: FindOptions,
+        range: Range,
+    ) -> Result<Vec<TextEdit>, FindError> {
+        let in_range = |m: &Range| m.back() <= range.back();
+        if !is_regex {
+            return Ok(self
+                .find_iter(query, range.front(), options)
+                .take_while(in_range)
+                .map(|m| TextEdit {
+                    range: m,
+                    new_text: replacement.to_owned(),
+                })
+                .collect());
+        }
+
+        let matches: Vec<Range> = self
+            .find_regex_iter(query, range.front(), options)?
+            .take_while(in_range)
+            .collect();
+
+        // The DFAs used for finding matches don't support capture groups. Run
+        // the regex again on each match to resolve them.
+        let regex = Regex::builder()
+            .syntax(syntax::Config::new().case_insensitive(!options.is_case_sensitive(query, true)))
+            .build(query)
+            .map_err(|err| FindError::InvalidRegex(Box::new(err)))?;
+        let mut caps = regex.create_captures();
+        let mut edits = Vec::with_capacity(matches.len());
+        for m in matches {
+            // Include the whole lines so that assertions like `\b` and `^`
+            // see the surrounding text.
+            let line_start = Position::new(m.front().y, 0);
+            let line_end = Position::new(m.back().y, self.line_len(m.back().y));
+            let haystack = self.substr(Range::from_positions(line_start, line_end));
+            let start = self
+                .substr(Range::from_positions(line_start, m.front()))
+                .len();
+            let end = start + self.substr(m).len();
+
+            let input = Input::new(&haystack)
+                .span(start..end)
+                .anchored(Anchored::Yes);
+            regex.search_captures(&input, &mut caps);
+            let new_text = if caps.is_match() {
+                caps.interpolate_string(&haystack, replacement)
+            } else {
+                replacement.to_owned()
+            };
+
+            edits.push(TextEdit { range: m, new_text });
+        }
+
+        Ok(edits)
+    }
+
+    /// Applies `edits` as a single undo step.
+    pub fn apply_replacements(&mut self, edits: Vec<TextEdit>) {
+        self.save_undo();
+        self.apply_text_edits(edits);
+        self.save_undo();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn replace(text: &str, query: &str, replacement: &str, is_regex: bool) -> String {
+        let mut b = Buffer::from_text(text);
+        let range = Range::new(0, 0, b.num_lines(), 0);
+        let edits = b
+            .replace_edits(query, replacement, is_regex, FindOptions::default(), range)
+            .unwrap();
+        b.apply_replacements(edits);
+        b.text()
+    }
+
+    #[test]
+    fn test_replace_text() {
+        assert_eq!(replace("", "a", "b", false), "");
+        assert_eq!(replace("abcabc", "b", "xyz", false), "axyzcaxyzc");
+        assert_eq!(replace("a.c abc", ".", "$1", false), "a$1c abc");
+        assert_eq!(replace("abc\nabc", "c\na", "-", false), "ab-bc");
+    }
+
+    #[test]
+    fn test_replace_regex() {
+        assert_eq!(replace("abc abc", "b", "x", true), "axc axc");
+        assert_eq!(
+            replace("let x = 1;\nlet yz = 2;", r"(\w+) = (\d+)", "$2 = $1", true),
+            "let 1 = x;\nlet 2 = yz;"
+        );
+        assert_eq!(
+            replace(
+                "foo(bar)",
+                r"(?P<f>\w+)\((?P<arg>\w+)\)",
+                "${arg}.${f}()",
+                true
+            ),
+            "bar.foo()"
+        );
+        assert_eq!(replace("a1 b2", r"\d", "$$", true), "a$ b$");
+        assert_eq!(replace("abc abc", r"^a", "x", true), "xbc abc");
+        assert_eq!(replace("ab ab", r"\bb", "x", true), "ab ab");
+    }
+
+    #[test]
+    fn test_replace_in_range() {
+        let b = Buffer::from_text("aaa\naaa\naaa");
+        let edits = b
+            .replace_edits(
+                "a",
+                "b",
+                false,
+                FindOptions::default(),
+                Range::new(1, 1, 2, 1),
+            )
+            .unwrap();
+        assert_eq!(
+            edits.iter().map(|e| e.range).collect::<Vec<_>>(),
+            vec![
+                Range::new(1, 1, 1, 2),
+                Range::new(1, 2, 1, 3),
+                Range::new(2, 0, 2, 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_undo_replacements() {
+        let mut b = Buffer::from_text("abc abc abc");
+        b.insert("x");
+        let range = Range::new(0, 0, 1, 0);
+        let edits = b
+            .replace_edits("abc", "d", false, FindOptions::default(), range)
+            .unwrap();
+        b.apply_replacements(edits);
+        assert_eq!(b.text(), "xd d d");
+        b.undo();
+        assert_eq!(b.text(), "xabc abc abc");
+        b.undo();
+        assert_eq!(b.text(), "abc abc abc");
+    }
+
+    #[test]
+    fn test_invalid_regex() {
+        let b = Buffer::from_text("abc");
+        let range = Range::new(0, 0, 1, 0);
+        assert!(b
+            .replace_edits("(", "", true, FindOptions::default(), range)
+            .is_err());
+    }
+}
@@ -0,0 +1,487 @@
+use crate::{
+    buffer::{Buffer, TextEdit},
+    cursor::{Cursor, Position, Range},
+    syntax::TsNodeExt,
+};
+
+/// Delimiters surrounding a text.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Surround {
+    /// A pair of characters like `()` and `""`.
+    Pair(char, char),
+    /// An HTML tag. The string is the contents of the opening tag, e.g.
+    /// `div class="a"`.
+    Tag(String),
+    /// Any HTML tag. Only used to find the surrounding one.
+    AnyTag,
+}
+
+impl Surround {
+    /// Parses a bracket (`(` or `)`), a quote, an HTML tag like `<div>`, or
+    /// `t` (any tag).
+    pub fn parse(s: &str) -> Option<Surround> {
+        let s = s.trim();
+        if s == "t" {
+            return Some(Surround::AnyTag);
+        }
+
+        if let Some(tag) = s.strip_prefix('<').and_then(|s| s.strip_suffix('>')) {
+            let tag = tag.trim();
+            if !tag.is_empty() {
+                return Some(Surround::Tag(tag.to_owned()));
+            }
+        }
+
+        let mut chars = s.chars();
+        let ch = chars.next()?;
+        if chars.next().is_some() {
+            return None;
+        }
+
+        let (open, close) = match ch {
+            '(' | ')' => ('(', ')'),
+            '{' | '}' => ('{', '}'),
+            '[' | ']' => ('[', ']'),
+            '<' | '>' => ('<', '>'),
+            ch if ch.is_alphanumeric() || ch.is_whitespace() => return None,
+            // Quotes and other symbols like `*`.
+            ch => (ch, ch),
+        };
+
+        Some(Surround::Pair(open, close))
+    }
+
+    pub fn open(&self) -> String {
+        match self {
+            Surround::Pair(open, _) => open.to_string(),
+            Surround::Tag(tag) => format!("<{}>", tag),
+            Surround::AnyTag => String::new(),
+        }
+    }
+
+    pub fn close(&self) -> String {
+        match self {
+            Surround::Pair(_, close) => close.to_string(),
+            Surround::Tag(tag) => format!("</{}>", tag_name(tag)),
+            Surround::AnyTag => String::new(),
+        }
+    }
+}
+
+fn tag_name(tag: &str) -> &str {
+    tag.split_whitespace().next().unwrap_or("")
+}
+
+impl Buffer {
+    /// Wraps each selection, or the word at the cursor, with `surround`.
+    pub fn add_surround(&mut self, surround: &Surround) {
+        if *surround == Surround::AnyTag {
+            return;
+        }
+
+        let open = surround.open();
+        let close = surround.close();
+        let open_len = open.chars().count();
+        let close_len = close.chars().count();
+        self.cursors.foreach(|c, past_cursors| {
+            let range = if c.is_selection() {
+                c.selection()
+            } else {
+                match self.buf.current_word(c.moving_position()) {
+                    Some(range) => range,
+                    None => return,
+                }
+            };
+
+            let front = range.front();
+            let back = range.back();
+            c.select_range(Range::from_positions(back, back));
+            self.buf.edit_at_cursor(c, past_cursors, &close);
+            c.select_range(Range::from_positions(front, front));
+            self.buf.edit_at_cursor(c, past_cursors, &open);
+
+            // Select the surrounded text.
+            let end_x = if back.y == front.y {
+                back.x + open_len + close_len
+            } else {
+                back.x + close_len
+            };
+            c.select(front.y, front.x, back.y, end_x);
+        });
+    }
+
+    /// Removes the nearest pair of `target` surrounding each selection.
+    pub fn delete_surround(&mut self, target: &Surround) {
+        self.replace_surrounds(target, None);
+    }
+
+    /// Replaces the nearest pair of `target` surrounding each selection with
+    /// `replacement`.
+    pub fn change_surround(&mut self, target: &Surround, replacement: &Surround) {
+        if *replacement == Surround::AnyTag {
+            return;
+        }
+
+        self.replace_surrounds(target, Some(replacement));
+    }
+
+    fn replace_surrounds(&mut self, target: &Surround, replacement: Option<&Surround>) {
+        self.update_syntax_tree();
+
+        let (open_text, close_text) = match replacement {
+            Some(replacement) => (replacement.open(), replacement.close()),
+            None => (String::new(), String::new()),
+        };
+
+        // Cursors in the same pair share the edits.
+        let mut edits: Vec<TextEdit> = Vec::new();
+        let selections: Vec<Range> = self.cursors().iter().map(|c| c.selection()).collect();
+        for selection in selections {
+            let (open, close) = match self.find_surround(selection, target) {
+                Some(pair) => pair,
+                None => continue,
+            };
+
+            let new_edits = [
+                TextEdit {
+                    range: open,
+                    new_text: open_text.clone(),
+                },
+                TextEdit {
+                    range: close,
+                    new_text: close_text.clone(),
+                },
+            ];
+
+            if new_edits.iter().all(|edit| edits.contains(edit)) {
+                continue;
+            }
+
+            let overlaps = new_edits.iter().any(|new_edit| {
+                edits
+                    .iter()
+                    .any(|edit| edit.range.overlaps_with(new_edit.range))
+            });
+            if !overlaps {
+                edits.extend(new_edits);
+            }
+        }
+
+        // Apply edits from the bottom of the buffer. Unlike
+        // `apply_text_edits`, don't merge cursors next to the delimiters.
+        edits.sort_by_key(|edit| edit.range.front());
+        let mut cursors = self.cursors().to_vec();
+        for edit in edits.iter().rev() {
+            let range = edit.range;
+            let mut edit_cursor =
+                Cursor::new_selection(range.start.y, range.start.x, range.end.y, range.end.x);
+            // Cursors after the edit are moved by it.
+            let first_after = cursors
+                .iter()
+                .position(|c| c.selection().front() >= range.back())
+                .unwrap_or(cursors.len());
+            self.buf.edit_at_cursor(
+                &mut edit_cursor,
+                &mut cursors[first_after..],
+                &edit.new_text,
+            );
+        }
+
+        self.update_cursors(&cursors);
+    }
+
+    /// Returns the ranges of the opening and closing delimiters of the
+    /// nearest `target` surrounding `selection`.
+    fn find_surround(&mut self, selection: Range, target: &Surround) -> Option<(Range, Range)> {
+        match target {
+            Surround::Pair(open, close) if open != close => {
+                self.find_surrounding_brackets(selection, *open, *close)
+            }
+            Surround::Pair(quote, _) => self
+                .find_surrounding_quotes_by_syntax(selection, *quote)
+                .or_else(|| self.find_surrounding_quotes_in_line(selection, *quote)),
+            Surround::Tag(tag) => self.find_surrounding_tags(selection, Some(tag_name(tag))),
+            Surround::AnyTag => self.find_surrounding_tags(selection, None),
+        }
+    }
+
+    /// Looks for the innermost pair of `open` and `close` surrounding
+    /// `selection`. Brackets in strings and comments are ignored unless
+    /// `selection` is in the same string or comment.
+    fn find_surrounding_brackets(
+        &mut self,
+        selection: Range,
+        open: char,
+        close: char,
+    ) -> Option<(Range, Range)> {
+        let scope = self.string_or_comment_at(selection.front());
+        let mut pos = selection.front();
+        loop {
+            let mut iter = self.buf.char_iter(pos);
+            iter.prev()?;
+            pos = iter.last_position();
+
+            let (opening, ch) = match self.find_bracket_nearby(pos) {
+                Some((iter, opening, ch, _)) if iter.last_position() == pos => (opening, ch),
+                _ => continue,
+            };
+
+            if (ch != open && ch != close) || self.string_or_comment_at(pos) != scope {
+                continue;
+            }
+
+            let matching = self.matching_bracket(pos)?;
+            if !opening {
+                // Skip the pair before the selection.
+                pos = matching.front();
+                continue;
+            }
+
+            if matching.front() >= selection.back() {
+                let open_range = Range::from_positions(pos, Position::new(pos.y, pos.x + 1));
+                return Some((open_range, matching));
+            }
+
+            // The bracket is closed in the selection. Try outer ones.
+        }
+    }
+
+    /// Looks for a syntax node like strings quoted by `quote`.
+    fn find_surrounding_quotes_by_syntax(
+        &self,
+        selection: Range,
+        quote: char,
+    ) -> Option<(Range, Range)> {
+        let mut node = self
+            .syntax()?
+            .tree()
+            .root_node()
+            .descendant_for_point_range(selection.front().into(), selection.back().into());
+        while let Some(current) = node {
+            let range = current.buffer_range();
+            let open_end = Position::new(range.front().y, range.front().x + 1);
+            let close_start = Position::new(range.back().y, range.back().x.saturating_sub(1));
+            let is_quoted = range.back().x > 0
+                && open_end <= close_start
+                && self.buf.char_iter(range.front()).next() == Some(quote)
+                && self.buf.char_iter(range.back()).prev() == Some(quote);
+            if is_quoted && Range::from_positions(open_end, close_start).contains_range(selection) {
+                return Some((
+                    Range::from_positions(range.front(), open_end),
+                    Range::from_positions(close_start, range.back()),
+                ));
+            }
+
+            node = current.parent();
+        }
+
+        None
+    }
+
+    /// Looks for `quote`s before and after `selection` in the same lines.
+    fn find_surrounding_quotes_in_line(
+        &self,
+        selection: Range,
+        quote: char,
+    ) -> Option<(Range, Range)> {
+        let mut iter = self.buf.char_iter(selection.front());
+        let open_pos = loop {
+            match iter.prev()? {
+                '\n' => return None,
+                ch if ch == quote => break iter.last_position(),
+                _ => {}
+            }
+        };
+
+        let mut iter = self.buf.char_iter(selection.back());
+        let close_pos = loop {
+            match iter.next()? {
+                '\n' => return None,
+                ch if ch == quote => break iter.last_position(),
+                _ => {}
+            }
+        };
+
+        Some((
+            Range::from_positions(open_pos, Position::new(open_pos.y, open_pos.x + 1)),
+            Range::from_positions(close_pos, Position::new(close_pos.y, close_pos.x + 1)),
+        ))
+    }
+
+    /// Looks for an HTML (or JSX) element named `name`.
+    fn find_surrounding_tags(
+        &self,
+        selection: Range,
+        name: Option<&str>,
+    ) -> Option<(Range, Range)> {
+        const ELEMENT_KINDS: &[(&str, &str, &str)] = &[
+            ("element", "start_tag", "end_tag"),
+            ("jsx_element", "jsx_opening_element", "jsx_closing_element"),
+        ];
+
+        let mut node = self
+            .syntax()?
+            .tree()
+            .root_node()
+            .descendant_for_point_range(selection.front().into(), selection.back().into());
+        while let Some(current) = node {
+            node = current.parent();
+
+            let (start_tag, end_tag) = match (
+                current.child(0),
+                current.child(current.child_count().saturating_sub(1)),
+            ) {
+                (Some(start_tag), Some(end_tag)) => (start_tag, end_tag),
+                _ => continue,
+            };
+
+            let is_element = ELEMENT_KINDS.iter().any(|(kind, start_kind, end_kind)| {
+                current.kind() == *kind
+                    && start_tag.kind() == *start_kind
+                    && end_tag.kind() == *end_kind
+            });
+            if !is_element || !current.buffer_range().contains_range(selection) {
+                continue;
+            }
+
+            let name_matches = match (name, start_tag.named_child(0)) {
+                (None, _) => true,
+                (Some(name), Some(tag_name)) => self.substr(tag_name.buffer_range()) == name,
+                (Some(_), None) => false,
+            };
+            if name_matches {
+                return Some((start_tag.buffer_range(), end_tag.buffer_range()));
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use noa_languages::get_language_by_name;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::cursor::Cursor;
+
+    fn buffer(text: &str, lang: &str, cursors: &[Cursor]) -> Buffer {
+        let mut b = Buffer::from_text(text);
+        if lang != "plain" {
+            b.set_language(get_language_by_name(lang).unwrap()).unwrap();
+        }
+        b.set_cursors_for_test(cursors);
+        b
+    }
+
+    fn surround(s: &str) -> Surround {
+        Surround::parse(s).unwrap()
+    }
+
+    #[test]
+    fn parse_surround() {
+        assert_eq!(Surround::parse(")"), Some(Surround::Pair('(', ')')));
+        assert_eq!(Surround::parse("\""), Some(Surround::Pair('"', '"')));
+        assert_eq!(
+            Surround::parse("<div id=\"a\">"),
+            Some(Surround::Tag("div id=\"a\"".to_owned()))
+        );
+        assert_eq!(Surround::parse("t"), Some(Surround::AnyTag));
+        assert_eq!(Surround::parse("<>"), None);
+        assert_eq!(Surround::parse("a"), None);
+        assert_eq!(surround("<div id=\"a\">").close(), "</div>");
+    }
+
+    #[test]
+    fn add_surround() {
+        let mut b = buffer(
+            "abc def\nxyz",
+            "plain",
+            &[
+                Cursor::new_selection(0, 0, 0, 3),
+                Cursor::new(0, 5),
+                Cursor::new(1, 1),
+            ],
+        );
+        b.add_surround(&surround("("));
+        assert_eq!(b.text(), "(abc) (def)\n(xyz)");
+        assert_eq!(
+            b.cursors(),
+            &[
+                Cursor::new_selection(0, 0, 0, 5),
+                Cursor::new_selection(0, 6, 0, 11),
+                Cursor::new_selection(1, 0, 1, 5)
+            ]
+        );
+
+        let mut b = buffer("a\nb", "plain", &[Cursor::new_selection(0, 0, 1, 1)]);
+        b.add_surround(&surround("<p class=\"x\">"));
+        assert_eq!(b.text(), "<p class=\"x\">a\nb</p>");
+        assert_eq!(b.cursors(), &[Cursor::new_selection(0, 0, 1, 5)]);
+    }
+
+    #[test]
+    fn delete_surrounding_brackets() {
+        let mut b = buffer(
+            "f(a, [b], c) (d)",
+            "plain",
+            &[Cursor::new(0, 3), Cursor::new(0, 6), Cursor::new(0, 14)],
+        );
+        b.delete_surround(&surround(")"));
+        assert_eq!(b.text(), "fa, [b], c d");
+        assert_eq!(
+            b.cursors(),
+            &[Cursor::new(0, 2), Cursor::new(0, 5), Cursor::new(0, 11)]
+        );
+
+        // The selection contains a pair.
+        let mut b = buffer("{a {b} c}", "plain", &[Cursor::new_selection(0, 2, 0, 6)]);
+        b.delete_surround(&surround("{"));
+        assert_eq!(b.text(), "a {b} c");
+        assert_eq!(b.cursors(), &[Cursor::new_selection(0, 1, 0, 5)]);
+
+        // Not surrounded.
+        let mut b = buffer("(a) b", "plain", &[Cursor::new(0, 4)]);
+        b.delete_surround(&surround("("));
+        assert_eq!(b.text(), "(a) b");
+
+        // Brackets in strings.
+        let mut b = buffer("f(\")\", x, \"(\")", "rust", &[Cursor::new(0, 7)]);
+        b.delete_surround(&surround("("));
+        assert_eq!(b.text(), "f\")\", x, \"(\"");
+    }
+
+    #[test]
+    fn change_surrounding_quotes() {
+        let mut b = buffer(
+            "f(\"a b\", 'c')",
+            "rust",
+            &[Cursor::new(0, 4), Cursor::new(0, 10)],
+        );
+        b.change_surround(&surround("\""), &surround("["));
+        assert_eq!(b.text(), "f([a b], 'c')");
+
+        // Quotes in a comment.
+        let mut b = buffer("// 'abc'", "rust", &[Cursor::new(0, 5)]);
+        b.change_surround(&surround("'"), &surround("\""));
+        assert_eq!(b.text(), "// \"abc\"");
+        assert_eq!(b.cursors(), &[Cursor::new(0, 5)]);
+    }
+
+    #[test]
+    fn change_surrounding_tags() {
+        let mut b = buffer(
+            "<div id=\"a\"><p>x</p></div>",
+            "html",
+            &[Cursor::new(0, 16)],
+        );
+        b.change_surround(&surround("t"), &surround("<b>"));
+        assert_eq!(b.text(), "<div id=\"a\"><b>x</b></div>");
+        assert_eq!(b.cursors(), &[Cursor::new(0, 16)]);
+
+        b.delete_surround(&surround("<div>"));
+        assert_eq!(b.text(), "<b>x</b>");
+        assert_eq!(b.cursors(), &[Cursor::new(0, 4)]);
+    }
+}
@@ -0,0 +1,405 @@
+use std::{cmp::Reverse, ops::ControlFlow};
+
+use noa_languages::tree_sitter;
+
+use crate::{
+    buffer::Buffer,
+    cursor::{Position, Range},
+    syntax::TsNodeExt,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TextObjectKind {
+    Function,
+    Class,
+    Parameter,
+    Comment,
+    Block,
+}
+
+impl TextObjectKind {
+    /// The capture name prefix in nvim-treesitter's `textobjects.scm`.
+    fn capture_name(self) -> &'static str {
+        match self {
+            TextObjectKind::Function => "function",
+            TextObjectKind::Class => "class",
+            TextObjectKind::Parameter => "parameter",
+            TextObjectKind::Comment => "comment",
+            TextObjectKind::Block => "block",
+        }
+    }
+
+    /// Determines the kind of a node. Used when the language does not have
+    /// a text objects query.
+    fn from_node(node: &tree_sitter::Node<'_>) -> Option<TextObjectKind> {
+        let kind = match node.kind() {
+            "function_item"
+            | "function_definition"
+            | "function_declaration"
+            | "method_definition"
+            | "method_declaration"
+            | "arrow_function"
+            | "function_expression"
+            | "function"
+            | "lambda"
+            | "closure_expression" => TextObjectKind::Function,
+            "class_definition"
+            | "class_declaration"
+            | "class"
+            | "struct_item"
+            | "enum_item"
+            | "union_item"
+            | "trait_item"
+            | "impl_item"
+            | "struct_specifier"
+            | "union_specifier"
+            | "enum_specifier"
+            | "interface_declaration" => TextObjectKind::Class,
+            "block" | "statement_block" | "compound_statement" => TextObjectKind::Block,
+            kind if kind.contains("comment") => TextObjectKind::Comment,
+            _ if node.is_named() && node.parent().is_some_and(is_parameter_list) => {
+                TextObjectKind::Parameter
+            }
+            _ => return None,
+        };
+
+        Some(kind)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TextObjectScope {
+    /// The contents, e.g. the body of a function without braces.
+    Inside,
+    /// The whole object, e.g. a function including its signature.
+    Around,
+}
+
+impl TextObjectScope {
+    fn capture_suffix(self) -> &'static str {
+        match self {
+            TextObjectScope::Inside => "inner",
+            TextObjectScope::Around => "outer",
+        }
+    }
+}
+
+fn is_parameter_list(node: tree_sitter::Node<'_>) -> bool {
+    matches!(
+        node.kind(),
+        "parameters"
+            | "formal_parameters"
+            | "parameter_list"
+            | "arguments"
+            | "argument_list"
+            | "type_parameters"
+            | "type_arguments"
+    )
+}
+
+/// Returns the range between the brackets of `node`, or the whole node if
+/// it's not surrounded by brackets (e.g. a Python block).
+fn inner_range(node: tree_sitter::Node<'_>) -> Range {
+    let count = node.child_count();
+    let first = node.child(0).filter(|n| !n.is_named());
+    let last = node
+        .child(count.saturating_sub(1))
+        .filter(|n| !n.is_named());
+    match (first, last) {
+        (Some(first), Some(last)) if count >= 2 => {
+            Range::from_positions(first.buffer_range().back(), last.buffer_range().front())
+        }
+        _ => node.buffer_range(),
+    }
+}
+
+/// Returns the range of a parameter including the separating comma.
+fn parameter_around_range(node: tree_sitter::Node<'_>) -> Range {
+    let range = node.buffer_range();
+    if let Some(comma) = node.next_sibling().filter(|n| n.kind() == ",") {
+        // Remove the whitespace before the next parameter too.
+        let end = match comma.next_named_sibling() {
+            Some(next) => next.buffer_range().front(),
+            None => comma.buffer_range().back(),
+        };
+        return Range::from_positions(range.front(), end);
+    }
+
+    if let Some(comma) = node.prev_sibling().filter(|n| n.kind() == ",") {
+        return Range::from_positions(comma.buffer_range().front(), range.back());
+    }
+
+    range
+}
+
+/// Computes the range of a text object from the syntax node.
+fn text_object_range(
+    node: tree_sitter::Node<'_>,
+    kind: TextObjectKind,
+    scope: TextObjectScope,
+) -> Range {
+    match (kind, scope) {
+        (TextObjectKind::Function | TextObjectKind::Class, TextObjectScope::Inside) => {
+            match node.child_by_field_name("body") {
+                Some(body) => inner_range(body),
+                None => node.buffer_range(),
+            }
+        }
+        (TextObjectKind::Block, TextObjectScope::Inside) => inner_range(node),
+        (TextObjectKind::Parameter, TextObjectScope::Around) => parameter_around_range(node),
+        _ => node.buffer_range(),
+    }
+}
+
+impl Buffer {
+    /// Returns the ranges of the text objects in the buffer. They're defined
+    /// by the text objects query if the language has one. Otherwise, guessed
+    /// from the syntax node kinds.
+    pub fn text_objects(&mut self, kind: TextObjectKind, scope: TextObjectScope) -> Vec<Range> {
+        self.update_syntax_tree();
+        let syntax = match self.syntax() {
+            Some(syntax) => syntax,
+            None => return Vec::new(),
+        };
+
+        let mut ranges = Vec::new();
+        match syntax.textobjects_query() {
+            Some(query) => {
+                let name = format!("{}.{}", kind.capture_name(), scope.capture_suffix());
+                // Some objects like comments only have the outer variant.
+                let fallback = format!("{}.outer", kind.capture_name());
+                let mut fallback_ranges = Vec::new();
+                query.query_ranges(syntax.tree(), self.raw_buffer(), None, |range, capture| {
+                    if capture == name {
+                        ranges.push(range);
+                    } else if capture == fallback {
+                        fallback_ranges.push(range);
+                    }
+                });
+
+                if ranges.is_empty() {
+                    ranges = fallback_ranges;
+                }
+            }
+            None => {
+                syntax.visit_all_nodes(|node, _| {
+                    if TextObjectKind::from_node(node) == Some(kind) {
+                        ranges.push(text_object_range(*node, kind, scope));
+                    }
+                    ControlFlow::Continue(())
+                });
+            }
+        }
+
+        ranges.sort_by_key(|range| (range.front(), Reverse(range.back())));
+        ranges.dedup();
+        ranges
+    }
+
+    /// Selects the innermost text object containing each selection. If the
+    /// selection is already the text object, the outer one is selected.
+    pub fn select_text_object(&mut self, kind: TextObjectKind, scope: TextObjectScope) {
+        let ranges = self.text_objects(kind, scope);
+        self.update_cursors_with(|c, _| {
+            let selection = c.selection();
+            let innermost = ranges
+                .iter()
+                .filter(|range| range.contains_range(selection) && **range != selection)
+                .min_by_key(|range| (Reverse(range.front()), range.back()));
+
+            if let Some(range) = innermost {
+                c.select_range(*range);
+            }
+        });
+    }
+
+    /// Moves each cursor to the beginning of the next text object.
+    pub fn move_to_next_text_object(&mut self, kind: TextObjectKind) {
+        let starts = self.text_object_starts(kind);
+        self.update_cursors_with(|c, _| {
+            let pos = c.moving_position();
+            if let Some(next) = starts.iter().find(|start| **start > pos) {
+                c.move_to_pos(*next);
+            }
+        });
+    }
+
+    /// Moves each cursor to the beginning of the previous text object.
+    pub fn move_to_prev_text_object(&mut self, kind: TextObjectKind) {
+        let starts = self.text_object_starts(kind);
+        self.update_cursors_with(|c, _| {
+            let pos = c.moving_position();
+            if let Some(prev) = starts.iter().rev().find(|start| **start < pos) {
+                c.move_to_pos(*prev);
+            }
+        });
+    }
+
+    fn text_object_starts(&mut self, kind: TextObjectKind) -> Vec<Position> {
+        let mut starts: Vec<Position> = self
+            .text_objects(kind, TextObjectScope::Around)
+            .iter()
+            .map(|range| range.front())
+            .collect();
+        starts.dedup();
+        starts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use noa_languages::get_language_by_name;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::cursor::Cursor;
+
+    const RUST_SOURCE: &str = concat!(
+        "struct A { x: i32 }\n",
+        "impl A {\n",
+        "    fn f(a: i32, b: u8) -> i32 {\n",
+        "        // c\n",
+        "        a\n",
+        "    }\n",
+        "\n",
+        "    fn g() {}\n",
+        "}\n",
+    );
+
+    /// A Rust buffer without the text objects query, i.e. text objects are
+    /// guessed from the node kinds regardless of the downloaded queries.
+    fn rust_buffer(text: &str) -> Buffer {
+        let mut b = Buffer::from_text(text);
+        b.set_language(get_language_by_name("rust").unwrap())
+            .unwrap();
+        b.syntax
+            .as_mut()
+            .unwrap()
+            .set_textobjects_query_for_test(None);
+        b
+    }
+
+    fn selected_strs(b: &Buffer) -> Vec<Cow<'_, str>> {
+        b.cursors()
+            .iter()
+            .map(|c| Cow::from(b.substr(c.selection())))
+            .collect()
+    }
+
+    #[test]
+    fn select_functions() {
+        let mut b = rust_buffer(RUST_SOURCE);
+        b.set_cursors_for_test(&[Cursor::new(4, 8)]);
+        b.select_text_object(TextObjectKind::Function, TextObjectScope::Inside);
+        assert_eq!(selected_strs(&b), vec!["\n        // c\n        a\n    "]);
+
+        b.set_cursors_for_test(&[Cursor::new(4, 8), Cursor::new(7, 12)]);
+        b.select_text_object(TextObjectKind::Function, TextObjectScope::Around);
+        assert_eq!(
+            selected_strs(&b),
+            vec![
+                "fn f(a: i32, b: u8) -> i32 {\n        // c\n        a\n    }",
+                "fn g() {}"
+            ]
+        );
+    }
+
+    #[test]
+    fn select_classes() {
+        let mut b = rust_buffer(RUST_SOURCE);
+        b.set_cursors_for_test(&[Cursor::new(0, 11)]);
+        b.select_text_object(TextObjectKind::Class, TextObjectScope::Inside);
+        assert_eq!(selected_strs(&b), vec![" x: i32 "]);
+        b.select_text_object(TextObjectKind::Class, TextObjectScope::Around);
+        assert_eq!(selected_strs(&b), vec!["struct A { x: i32 }"]);
+
+        // In an impl block.
+        b.set_cursors_for_test(&[Cursor::new(6, 0)]);
+        b.select_text_object(TextObjectKind::Class, TextObjectScope::Inside);
+        assert_eq!(
+            selected_strs(&b),
+            vec!["\n    fn f(a: i32, b: u8) -> i32 {\n        // c\n        a\n    }\n\n    fn g() {}\n"]
+        );
+        // Not in a class.
+        b.set_cursors_for_test(&[Cursor::new(9, 0)]);
+        b.select_text_object(TextObjectKind::Class, TextObjectScope::Inside);
+        assert_eq!(selected_strs(&b), vec![""]);
+    }
+
+    #[test]
+    fn select_parameters() {
+        let mut b = rust_buffer(RUST_SOURCE);
+        b.set_cursors_for_test(&[Cursor::new(2, 9)]);
+        b.select_text_object(TextObjectKind::Parameter, TextObjectScope::Inside);
+        assert_eq!(selected_strs(&b), vec!["a: i32"]);
+
+        b.set_cursors_for_test(&[Cursor::new(2, 9)]);
+        b.select_text_object(TextObjectKind::Parameter, TextObjectScope::Around);
+        assert_eq!(selected_strs(&b), vec!["a: i32, "]);
+
+        // The last parameter.
+        b.set_cursors_for_test(&[Cursor::new(2, 18)]);
+        b.select_text_object(TextObjectKind::Parameter, TextObjectScope::Around);
+        assert_eq!(selected_strs(&b), vec![", b: u8"]);
+    }
+
+    #[test]
+    fn select_comments_and_blocks() {
+        let mut b = rust_buffer(RUST_SOURCE);
+        b.set_cursors_for_test(&[Cursor::new(3, 10)]);
+        b.select_text_object(TextObjectKind::Comment, TextObjectScope::Around);
+        assert_eq!(selected_strs(&b), vec!["// c"]);
+
+        b.set_cursors_for_test(&[Cursor::new(4, 8)]);
+        b.select_text_object(TextObjectKind::Block, TextObjectScope::Around);
+        assert_eq!(selected_strs(&b), vec!["{\n        // c\n        a\n    }"]);
+    }
+
+    #[test]
+    fn move_to_functions() {
+        let mut b = rust_buffer(RUST_SOURCE);
+        b.set_cursors_for_test(&[Cursor::new(0, 0), Cursor::new(4, 0)]);
+        b.move_to_next_text_object(TextObjectKind::Function);
+        assert_eq!(b.cursors(), &[Cursor::new(2, 4), Cursor::new(7, 4)]);
+        b.move_to_next_text_object(TextObjectKind::Function);
+        assert_eq!(b.cursors(), &[Cursor::new(7, 4)]);
+
+        b.move_to_prev_text_object(TextObjectKind::Function);
+        assert_eq!(b.cursors(), &[Cursor::new(2, 4)]);
+        b.move_to_prev_text_object(TextObjectKind::Function);
+        assert_eq!(b.cursors(), &[Cursor::new(2, 4)]);
+
+        b.set_cursors_for_test(&[Cursor::new(4, 0)]);
+        b.move_to_prev_text_object(TextObjectKind::Class);
+        assert_eq!(b.cursors(), &[Cursor::new(1, 0)]);
+    }
+
+    #[test]
+    fn text_objects_query() {
+        let mut b = Buffer::from_text("def f(a, b):\n    return a\n");
+        b.set_language(get_language_by_name("python").unwrap())
+            .unwrap();
+        b.syntax
+            .as_mut()
+            .unwrap()
+            .set_textobjects_query_for_test(Some(concat!(
+                "(function_definition body: (block) @function.inner) @function.outer\n",
+                "((parameters \",\" @_start . (_) @_end) ",
+                "(#make-range! \"parameter.outer\" @_start @_end))\n",
+            )));
+
+        b.set_cursors_for_test(&[Cursor::new(1, 4)]);
+        b.select_text_object(TextObjectKind::Function, TextObjectScope::Inside);
+        assert_eq!(selected_strs(&b), vec!["return a"]);
+        b.select_text_object(TextObjectKind::Function, TextObjectScope::Inside);
+        assert_eq!(selected_strs(&b), vec!["return a"]);
+        b.select_text_object(TextObjectKind::Function, TextObjectScope::Around);
+        assert_eq!(selected_strs(&b), vec!["def f(a, b):\n    return a"]);
+
+        b.set_cursors_for_test(&[Cursor::new(0, 9)]);
+        b.select_text_object(TextObjectKind::Parameter, TextObjectScope::Around);
+        assert_eq!(selected_strs(&b), vec![", b"]);
+    }
+}
@@ -1,16 +1,115 @@
-use crate::{char_iter::CharIter, cursor::Range};
+use std::fmt;
+
+use regex_automata::{
+    hybrid::dfa::{Cache, DFA},
+    meta,
+    nfa::thompson,
+    util::{start, syntax},
+    Anchored, Input, MatchKind,
+};
+
+use crate::{
+    char_iter::CharIter,
+    cursor::{Position, Range},
+    raw_buffer::RawBuffer,
+    word_iter::is_word_char,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CaseMatching {
+    Sensitive,
+    Insensitive,
+    /// Case-insensitive unless the query contains an uppercase letter.
+    Smart,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FindOptions {
+    pub case_matching: CaseMatching,
+    /// Skip matches adjacent to word characters.
+    pub whole_word: bool,
+}
+
+impl FindOptions {
+    pub fn whole_word() -> FindOptions {
+        FindOptions {
+            whole_word: true,
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn is_case_sensitive(&self, query: &str, is_regex: bool) -> bool {
+        match self.case_matching {
+            CaseMatching::Sensitive => true,
+            CaseMatching::Insensitive => false,
+            CaseMatching::Smart => {
+                let mut chars = query.chars();
+                while let Some(ch) = chars.next() {
+                    if is_regex && ch == '\\' {
+                        // Skip escapes like `\S`.
+                        chars.next();
+                    } else if ch.is_uppercase() {
+                        return true;
+                    }
+                }
+
+                false
+            }
+        }
+    }
+}
+
+impl Default for FindOptions {
+    fn default() -> FindOptions {
+        FindOptions {
+            case_matching: CaseMatching::Sensitive,
+            whole_word: false,
+        }
+    }
+}
+
+/// Returns true if `range` is not adjacent to word characters.
+fn is_whole_word(buf: &RawBuffer, range: Range) -> bool {
+    let before = buf.char_iter(range.front()).prev();
+    let after = buf.char_iter(range.back()).next();
+    !before.map(is_word_char).unwrap_or(false) && !after.map(is_word_char).unwrap_or(false)
+}
+
+fn chars_eq(a: char, b: char, case_sensitive: bool) -> bool {
+    if case_sensitive {
+        a == b
+    } else {
+        a == b || a.to_lowercase().eq(b.to_lowercase())
+    }
+}
 
 pub struct FindIter<'a, 'b> {
     chars: CharIter<'a>,
     query: &'b str,
+    case_sensitive: bool,
+    whole_word: bool,
 }
 
 impl<'a, 'b> FindIter<'a, 'b> {
-    pub fn new(chars: CharIter<'a>, query: &'b str) -> FindIter<'a, 'b> {
-        FindIter { chars, query }
+    pub fn new(chars: CharIter<'a>, query: &'b str, options: FindOptions) -> FindIter<'a, 'b> {
+        FindIter {
+            chars,
+            query,
+            case_sensitive: options.is_case_sensitive(query, false),
+            whole_word: options.whole_word,
+        }
     }
 
     pub fn prev(&mut self) -> Option<Range> {
+        loop {
+            let range = self.prev_match()?;
+            if !self.whole_word || is_whole_word(self.chars.buffer(), range) {
+                return Some(range);
+            }
+        }
+    }
+
+    fn prev_match(&mut self) -> Option<Range> {
         if self.query.is_empty() {
             return None;
         }
@@ -26,7 +125,7 @@ impl<'a, 'b> FindIter<'a, 'b> {
             loop {
                 let last_pos = buf_iter.next_position();
                 match (buf_iter.prev(), query_iter.next()) {
-                    (Some(a), Some(b)) if a != b => {
+                    (Some(a), Some(b)) if !chars_eq(a, b, self.case_sensitive) => {
                         break;
                     }
                     (None, Some(_)) => {
@@ -48,12 +147,8 @@ impl<'a, 'b> FindIter<'a, 'b> {
             }
         }
     }
-}
-
-impl<'a, 'b> Iterator for FindIter<'a, 'b> {
-    type Item = Range;
 
-    fn next(&mut self) -> Option<Range> {
+    fn next_match(&mut self) -> Option<Range> {
         if self.query.is_empty() {
             return None;
         }
@@ -68,7 +163,7 @@ impl<'a, 'b> Iterator for FindIter<'a, 'b> {
             let mut n = 0;
             loop {
                 match (buf_iter.next(), query_iter.next()) {
-                    (Some(a), Some(b)) if a != b => {
+                    (Some(a), Some(b)) if !chars_eq(a, b, self.case_sensitive) => {
                         break;
                     }
                     (None, Some(_)) => {
@@ -93,6 +188,351 @@ impl<'a, 'b> Iterator for FindIter<'a, 'b> {
     }
 }
 
+impl<'a, 'b> Iterator for FindIter<'a, 'b> {
+    type Item = Range;
+
+    fn next(&mut self) -> Option<Range> {
+        loop {
+            let range = self.next_match()?;
+            if !self.whole_word || is_whole_word(self.chars.buffer(), range) {
+                return Some(range);
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum FindError {
+    InvalidRegex(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl fmt::Display for FindError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FindError::InvalidRegex(err) => write!(f, "invalid regex: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for FindError {}
+
+/// Finds matches of a regular expression by running lazy DFAs over the rope
+/// chunk by chunk, i.e. without copying the whole buffer into a string.
+///
+/// Empty matches are skipped. Since the DFAs cannot handle Unicode word
+/// boundaries (`\b`) on non-ASCII text, the search falls back to a meta regex
+/// there, which runs on a copy of a few lines at a time.
+pub struct FindRegexIter<'a> {
+    buf: &'a RawBuffer,
+    /// Finds the end of the leftmost-first match.
+    fwd: DFA,
+    fwd_cache: Cache,
+    /// Finds the start of the match from its end.
+    rev: DFA,
+    rev_cache: Cache,
+    /// The byte index where the next search starts (or ends for `prev`).
+    offset: usize,
+    whole_word: bool,
+    pattern: String,
+    syntax: syntax::Config,
+    /// The regex used when the DFAs give up. Built when needed.
+    fallback: Option<meta::Regex>,
+}
+
+/// The number of lines copied at once for the fallback regex. Matches
+/// spanning more lines than this may be missed.
+const FALLBACK_WINDOW_LINES: usize = 256;
+
+/// The lazy DFA gave up, e.g. on a Unicode word boundary in non-ASCII text.
+struct GaveUp;
+
+impl<'a> FindRegexIter<'a> {
+    pub fn new(
+        buf: &'a RawBuffer,
+        pattern: &str,
+        pos: Position,
+        options: FindOptions,
+    ) -> Result<FindRegexIter<'a>, FindError> {
+        let case_insensitive = !options.is_case_sensitive(pattern, true);
+        let syntax = syntax::Config::new().case_insensitive(case_insensitive);
+        let fwd = DFA::builder()
+            .configure(DFA::config().unicode_word_boundary(true))
+            .syntax(syntax)
+            .build(pattern)
+            .map_err(|err| FindError::InvalidRegex(Box::new(err)))?;
+        let rev = DFA::builder()
+            .configure(
+                DFA::config()
+                    .unicode_word_boundary(true)
+                    .match_kind(MatchKind::All),
+            )
+            .syntax(syntax)
+            .thompson(thompson::Config::new().reverse(true))
+            .build(pattern)
+            .map_err(|err| FindError::InvalidRegex(Box::new(err)))?;
+
+        Ok(FindRegexIter {
+            buf,
+            fwd_cache: fwd.create_cache(),
+            fwd,
+            rev_cache: rev.create_cache(),
+            rev,
+            offset: buf.pos_to_byte_index(pos),
+            whole_word: options.whole_word,
+            pattern: pattern.to_owned(),
+            syntax,
+            fallback: None,
+        })
+    }
+
+    /// Returns the previous match which ends before the current position.
+    pub fn prev(&mut self) -> Option<Range> {
+        let rope = self.buf.rope();
+        let end = self.offset;
+
+        // Search forward from the beginning of a line and look for the last
+        // match. If there're no matches, extend the window backwards.
+        let mut y = rope.byte_to_line(end);
+        let mut num_lines = 1;
+        loop {
+            let mut last_match = None;
+            let mut start = rope.line_to_byte(y);
+            while let Some((match_start, match_end)) = self.find_forward(start, end) {
+                last_match = Some((match_start, match_end));
+                start = match_end;
+            }
+
+            if let Some((match_start, match_end)) = last_match {
+                self.offset = match_start;
+                return Some(self.byte_range_to_range(match_start, match_end));
+            }
+
+            if y == 0 {
+                return None;
+            }
+
+            y = y.saturating_sub(num_lines);
+            num_lines *= 2;
+        }
+    }
+
+    fn byte_range_to_range(&self, start: usize, end: usize) -> Range {
+        let rope = self.buf.rope();
+        Range::from_positions(
+            self.buf.char_index_to_pos(rope.byte_to_char(start)),
+            self.buf.char_index_to_pos(rope.byte_to_char(end)),
+        )
+    }
+
+    /// Returns the byte range of the leftmost non-empty match in `start..end`.
+    fn find_forward(&mut self, start: usize, end: usize) -> Option<(usize, usize)> {
+        match self.find_forward_by_dfa(start, end) {
+            Ok(found) => found,
+            Err(GaveUp) => self.find_forward_by_regex(start, end),
+        }
+    }
+
+    fn is_acceptable_match(&self, start: usize, end: usize) -> bool {
+        start < end
+            && (!self.whole_word || is_whole_word(self.buf, self.byte_range_to_range(start, end)))
+    }
+
+    fn find_forward_by_dfa(
+        &mut self,
+        mut start: usize,
+        end: usize,
+    ) -> Result<Option<(usize, usize)>, GaveUp> {
+        let rope = self.buf.rope();
+        loop {
+            let match_end = match self.search_end(start, end)? {
+                Some(match_end) => match_end,
+                None => return Ok(None),
+            };
+            let match_start = match self.search_start(start, match_end)? {
+                Some(match_start) => match_start,
+                None => return Ok(None),
+            };
+            if self.is_acceptable_match(match_start, match_end) {
+                return Ok(Some((match_start, match_end)));
+            }
+
+            // An empty match or not a whole word. Try again from the next
+            // character.
+            if match_start >= end {
+                return Ok(None);
+            }
+
+            start = rope.char_to_byte(rope.byte_to_char(match_start) + 1);
+        }
+    }
+
+    fn find_forward_by_regex(&mut self, start: usize, end: usize) -> Option<(usize, usize)> {
+        if self.fallback.is_none() {
+            let regex = match meta::Builder::new()
+                .syntax(self.syntax)
+                .build(&self.pattern)
+            {
+                Ok(regex) => regex,
+                Err(err) => {
+                    warn!("failed to build a fallback regex: {}", err);
+                    return None;
+                }
+            };
+
+            self.fallback = Some(regex);
+        }
+
+        let regex = self.fallback.as_ref().unwrap();
+        let rope = self.buf.rope();
+        let mut y = rope.byte_to_line(start);
+        loop {
+            // Look for matches starting in `window_start..next_window_start`.
+            // The window extends over the next window for matches spanning
+            // multiple lines, and by a character on both sides so that
+            // assertions like `\b` and `$` see the text around it.
+            let next_y = (y + FALLBACK_WINDOW_LINES).min(rope.len_lines());
+            let window_start = rope.line_to_byte(y);
+            let next_window_start = rope.line_to_byte(next_y);
+            let window_end =
+                rope.line_to_byte((next_y + FALLBACK_WINDOW_LINES).min(rope.len_lines()));
+            let text_start = rope.byte_to_char(window_start).saturating_sub(1);
+            let text_end = (rope.byte_to_char(window_end) + 1).min(rope.len_chars());
+            let text = rope.slice(text_start..text_end).to_string();
+            let offset = rope.char_to_byte(text_start);
+
+            let span_end = end.min(window_end) - offset;
+            let mut at = start.max(window_start) - offset;
+            while at <= span_end {
+                let m = match regex.search(&Input::new(&text).span(at..span_end)) {
+                    Some(m) if m.start() + offset < next_window_start => m,
+                    _ => break,
+                };
+
+                let (match_start, match_end) = (m.start() + offset, m.end() + offset);
+                if self.is_acceptable_match(match_start, match_end) {
+                    return Some((match_start, match_end));
+                }
+
+                // An empty match or not a whole word. Try again from the next
+                // character.
+                at = match text[m.start()..].chars().next() {
+                    Some(ch) => m.start() + ch.len_utf8(),
+                    None => break,
+                };
+            }
+
+            if next_y >= rope.len_lines() || next_window_start >= end {
+                return None;
+            }
+
+            y = next_y;
+        }
+    }
+
+    /// Runs the forward DFA in `start..end` and returns the end of the
+    /// leftmost-first match.
+    fn search_end(&mut self, start: usize, end: usize) -> Result<Option<usize>, GaveUp> {
+        let rope = self.buf.rope();
+        let look_behind = if start > 0 {
+            Some(rope.byte(start - 1))
+        } else {
+            None
+        };
+        let config = start::Config::new()
+            .anchored(Anchored::No)
+            .look_behind(look_behind);
+
+        let dfa = &self.fwd;
+        let cache = &mut self.fwd_cache;
+        let mut sid = dfa.start_state(cache, &config).map_err(|_| GaveUp)?;
+        let mut bytes = rope.bytes_at(start);
+        let mut match_end = None;
+        for at in start..end {
+            let byte = bytes.next().ok_or(GaveUp)?;
+            sid = dfa.next_state(cache, sid, byte).map_err(|_| GaveUp)?;
+            // Note that matches are delayed by one byte.
+            if sid.is_match() {
+                match_end = Some(at);
+            } else if sid.is_dead() {
+                return Ok(match_end);
+            } else if sid.is_quit() {
+                return Err(GaveUp);
+            }
+        }
+
+        // Look ahead a byte to resolve assertions like `$`.
+        sid = match bytes.next() {
+            Some(byte) => dfa.next_state(cache, sid, byte),
+            None => dfa.next_eoi_state(cache, sid),
+        }
+        .map_err(|_| GaveUp)?;
+        if sid.is_quit() {
+            return Err(GaveUp);
+        }
+        if sid.is_match() {
+            match_end = Some(end);
+        }
+
+        Ok(match_end)
+    }
+
+    /// Runs the reverse DFA from `end` back to `min` and returns the leftmost
+    /// start of the match ending at `end`.
+    fn search_start(&mut self, min: usize, end: usize) -> Result<Option<usize>, GaveUp> {
+        let rope = self.buf.rope();
+        let look_behind = if end < rope.len_bytes() {
+            Some(rope.byte(end))
+        } else {
+            None
+        };
+        let config = start::Config::new()
+            .anchored(Anchored::Yes)
+            .look_behind(look_behind);
+
+        let dfa = &self.rev;
+        let cache = &mut self.rev_cache;
+        let mut sid = dfa.start_state(cache, &config).map_err(|_| GaveUp)?;
+        let mut bytes = rope.bytes_at(end);
+        let mut match_start = None;
+        for at in (min..end).rev() {
+            let byte = bytes.prev().ok_or(GaveUp)?;
+            sid = dfa.next_state(cache, sid, byte).map_err(|_| GaveUp)?;
+            if sid.is_match() {
+                match_start = Some(at + 1);
+            } else if sid.is_dead() {
+                return Ok(match_start);
+            } else if sid.is_quit() {
+                return Err(GaveUp);
+            }
+        }
+
+        sid = match bytes.prev() {
+            Some(byte) => dfa.next_state(cache, sid, byte),
+            None => dfa.next_eoi_state(cache, sid),
+        }
+        .map_err(|_| GaveUp)?;
+        if sid.is_quit() {
+            return Err(GaveUp);
+        }
+        if sid.is_match() {
+            match_start = Some(min);
+        }
+
+        Ok(match_start)
+    }
+}
+
+impl<'a> Iterator for FindRegexIter<'a> {
+    type Item = Range;
+
+    fn next(&mut self) -> Option<Range> {
+        let len = self.buf.rope().len_bytes();
+        let (match_start, match_end) = self.find_forward(self.offset, len)?;
+        self.offset = match_end;
+        Some(self.byte_range_to_range(match_start, match_end))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{buffer::Buffer, cursor::Position};
@@ -103,31 +543,31 @@ mod tests {
     #[test]
     fn test_find_next() {
         let b = Buffer::from_text("");
-        let mut iter = b.find_iter("A", Position::new(0, 0));
+        let mut iter = b.find_iter("A", Position::new(0, 0), FindOptions::default());
         assert_eq!(iter.next(), None);
 
         let b = Buffer::from_text("AAAA");
-        let mut iter = b.find_iter("", Position::new(0, 0));
+        let mut iter = b.find_iter("", Position::new(0, 0), FindOptions::default());
         assert_eq!(iter.next(), None);
-        let mut iter = b.find_iter("B", Position::new(0, 0));
+        let mut iter = b.find_iter("B", Position::new(0, 0), FindOptions::default());
         assert_eq!(iter.next(), None);
-        let mut iter = b.find_iter("A", Position::new(0, 0));
+        let mut iter = b.find_iter("A", Position::new(0, 0), FindOptions::default());
         assert_eq!(iter.next(), Some(Range::new(0, 0, 0, 1)));
         assert_eq!(iter.next(), Some(Range::new(0, 1, 0, 2)));
         assert_eq!(iter.next(), Some(Range::new(0, 2, 0, 3)));
         assert_eq!(iter.next(), Some(Range::new(0, 3, 0, 4)));
         assert_eq!(iter.next(), None);
-        let mut iter = b.find_iter("A", Position::new(0, 2));
+        let mut iter = b.find_iter("A", Position::new(0, 2), FindOptions::default());
         assert_eq!(iter.next(), Some(Range::new(0, 2, 0, 3)));
         assert_eq!(iter.next(), Some(Range::new(0, 3, 0, 4)));
         assert_eq!(iter.next(), None);
-        let mut iter = b.find_iter("AA", Position::new(0, 0));
+        let mut iter = b.find_iter("AA", Position::new(0, 0), FindOptions::default());
         assert_eq!(iter.next(), Some(Range::new(0, 0, 0, 2)));
         assert_eq!(iter.next(), Some(Range::new(0, 2, 0, 4)));
         assert_eq!(iter.next(), None);
 
         let b = Buffer::from_text("AxAxA");
-        let mut iter = b.find_iter("A", Position::new(0, 0));
+        let mut iter = b.find_iter("A", Position::new(0, 0), FindOptions::default());
         assert_eq!(iter.next(), Some(Range::new(0, 0, 0, 1)));
         assert_eq!(iter.next(), Some(Range::new(0, 2, 0, 3)));
         assert_eq!(iter.next(), Some(Range::new(0, 4, 0, 5)));
@@ -137,34 +577,233 @@ mod tests {
     #[test]
     fn test_find_prev() {
         let b = Buffer::from_text("");
-        let mut iter = b.find_iter("A", Position::new(0, 0));
+        let mut iter = b.find_iter("A", Position::new(0, 0), FindOptions::default());
         assert_eq!(iter.prev(), None);
 
         let b = Buffer::from_text("AAAA");
-        let mut iter = b.find_iter("", Position::new(0, 4));
+        let mut iter = b.find_iter("", Position::new(0, 4), FindOptions::default());
         assert_eq!(iter.prev(), None);
-        let mut iter = b.find_iter("B", Position::new(0, 4));
+        let mut iter = b.find_iter("B", Position::new(0, 4), FindOptions::default());
         assert_eq!(iter.prev(), None);
-        let mut iter = b.find_iter("A", Position::new(0, 4));
+        let mut iter = b.find_iter("A", Position::new(0, 4), FindOptions::default());
         assert_eq!(iter.prev(), Some(Range::new(0, 3, 0, 4)));
         assert_eq!(iter.prev(), Some(Range::new(0, 2, 0, 3)));
         assert_eq!(iter.prev(), Some(Range::new(0, 1, 0, 2)));
         assert_eq!(iter.prev(), Some(Range::new(0, 0, 0, 1)));
         assert_eq!(iter.prev(), None);
-        let mut iter = b.find_iter("A", Position::new(0, 2));
+        let mut iter = b.find_iter("A", Position::new(0, 2), FindOptions::default());
         assert_eq!(iter.prev(), Some(Range::new(0, 1, 0, 2)));
         assert_eq!(iter.prev(), Some(Range::new(0, 0, 0, 1)));
         assert_eq!(iter.prev(), None);
-        let mut iter = b.find_iter("AA", Position::new(0, 4));
+        let mut iter = b.find_iter("AA", Position::new(0, 4), FindOptions::default());
         assert_eq!(iter.prev(), Some(Range::new(0, 2, 0, 4)));
         assert_eq!(iter.prev(), Some(Range::new(0, 0, 0, 2)));
         assert_eq!(iter.prev(), None);
 
         let b = Buffer::from_text("AxAxA");
-        let mut iter = b.find_iter("A", Position::new(0, 5));
+        let mut iter = b.find_iter("A", Position::new(0, 5), FindOptions::default());
         assert_eq!(iter.prev(), Some(Range::new(0, 4, 0, 5)));
         assert_eq!(iter.prev(), Some(Range::new(0, 2, 0, 3)));
         assert_eq!(iter.prev(), Some(Range::new(0, 0, 0, 1)));
         assert_eq!(iter.prev(), None);
     }
+
+    #[test]
+    fn test_find_options() {
+        let insensitive = FindOptions {
+            case_matching: CaseMatching::Insensitive,
+            ..Default::default()
+        };
+        let smart = FindOptions {
+            case_matching: CaseMatching::Smart,
+            ..Default::default()
+        };
+
+        let b = Buffer::from_text("Foo foo FOO");
+        let pos = Position::new(0, 0);
+        assert_eq!(b.find_iter("foo", pos, FindOptions::default()).count(), 1);
+        assert_eq!(b.find_iter("foo", pos, insensitive).count(), 3);
+        assert_eq!(b.find_iter("foo", pos, smart).count(), 3);
+        assert_eq!(b.find_iter("Foo", pos, smart).count(), 1);
+        assert_eq!(b.find_regex_iter("f[o]+", pos, smart).unwrap().count(), 3);
+        assert_eq!(b.find_regex_iter(r"\Wfoo", pos, smart).unwrap().count(), 2);
+        assert_eq!(b.find_regex_iter("F[o]+", pos, smart).unwrap().count(), 1);
+
+        let b = Buffer::from_text("foo foo_bar foo2 (foo)");
+        let mut iter = b.find_iter("foo", pos, FindOptions::whole_word());
+        assert_eq!(iter.next(), Some(Range::new(0, 0, 0, 3)));
+        assert_eq!(iter.next(), Some(Range::new(0, 18, 0, 21)));
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.prev(), Some(Range::new(0, 18, 0, 21)));
+        assert_eq!(iter.prev(), Some(Range::new(0, 0, 0, 3)));
+        assert_eq!(iter.prev(), None);
+
+        let mut iter = b
+            .find_regex_iter("fo+", pos, FindOptions::whole_word())
+            .unwrap();
+        assert_eq!(iter.next(), Some(Range::new(0, 0, 0, 3)));
+        assert_eq!(iter.next(), Some(Range::new(0, 18, 0, 21)));
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.prev(), Some(Range::new(0, 18, 0, 21)));
+    }
+
+    #[test]
+    fn test_find_regex_next() {
+        let b = Buffer::from_text("");
+        let mut iter = b
+            .find_regex_iter("A", Position::new(0, 0), FindOptions::default())
+            .unwrap();
+        assert_eq!(iter.next(), None);
+
+        let b = Buffer::from_text("a1 b22\nc333");
+        let mut iter = b
+            .find_regex_iter("[0-9]+", Position::new(0, 0), FindOptions::default())
+            .unwrap();
+        assert_eq!(iter.next(), Some(Range::new(0, 1, 0, 2)));
+        assert_eq!(iter.next(), Some(Range::new(0, 4, 0, 6)));
+        assert_eq!(iter.next(), Some(Range::new(1, 1, 1, 4)));
+        assert_eq!(iter.next(), None);
+
+        let mut iter = b
+            .find_regex_iter("[0-9]+", Position::new(0, 5), FindOptions::default())
+            .unwrap();
+        assert_eq!(iter.next(), Some(Range::new(0, 5, 0, 6)));
+
+        // Empty matches are skipped.
+        let mut iter = b
+            .find_regex_iter("[0-9]*", Position::new(0, 0), FindOptions::default())
+            .unwrap();
+        assert_eq!(iter.next(), Some(Range::new(0, 1, 0, 2)));
+
+        // Assertions.
+        let mut iter = b
+            .find_regex_iter("[a-z][0-9]+$", Position::new(0, 0), FindOptions::default())
+            .unwrap();
+        assert_eq!(iter.next(), Some(Range::new(1, 0, 1, 4)));
+        let mut iter = b
+            .find_regex_iter("(?m)^[a-z]", Position::new(0, 1), FindOptions::default())
+            .unwrap();
+        assert_eq!(iter.next(), Some(Range::new(1, 0, 1, 1)));
+
+        assert!(b
+            .find_regex_iter("(", Position::new(0, 0), FindOptions::default())
+            .is_err());
+    }
+
+    #[test]
+    fn test_find_regex_prev() {
+        let b = Buffer::from_text("AAAA");
+        let mut iter = b
+            .find_regex_iter("AA", Position::new(0, 4), FindOptions::default())
+            .unwrap();
+        assert_eq!(iter.prev(), Some(Range::new(0, 2, 0, 4)));
+        assert_eq!(iter.prev(), Some(Range::new(0, 0, 0, 2)));
+        assert_eq!(iter.prev(), None);
+
+        let b = Buffer::from_text("a1 b22\nc333\n\nd");
+        let mut iter = b
+            .find_regex_iter("[0-9]+", Position::new(3, 1), FindOptions::default())
+            .unwrap();
+        assert_eq!(iter.prev(), Some(Range::new(1, 1, 1, 4)));
+        assert_eq!(iter.prev(), Some(Range::new(0, 4, 0, 6)));
+        assert_eq!(iter.next(), Some(Range::new(0, 4, 0, 6)));
+        assert_eq!(iter.prev(), Some(Range::new(0, 4, 0, 6)));
+        assert_eq!(iter.prev(), Some(Range::new(0, 1, 0, 2)));
+        assert_eq!(iter.prev(), None);
+
+        let mut iter = b
+            .find_regex_iter("[0-9]+", Position::new(0, 5), FindOptions::default())
+            .unwrap();
+        assert_eq!(iter.prev(), Some(Range::new(0, 4, 0, 5)));
+    }
+
+    #[test]
+    fn test_find_regex_unicode_word_boundary() {
+        let b = Buffer::from_text("café über cafe");
+        let mut iter = b
+            .find_regex_iter(r"\b\w+\b", Position::new(0, 0), FindOptions::default())
+            .unwrap();
+        assert_eq!(iter.next(), Some(Range::new(0, 0, 0, 4)));
+        assert_eq!(iter.next(), Some(Range::new(0, 5, 0, 9)));
+        assert_eq!(iter.next(), Some(Range::new(0, 10, 0, 14)));
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.prev(), Some(Range::new(0, 10, 0, 14)));
+        assert_eq!(iter.prev(), Some(Range::new(0, 5, 0, 9)));
+
+        // `ü` is a word character.
+        let mut iter = b
+            .find_regex_iter(r"\bber", Position::new(0, 0), FindOptions::default())
+            .unwrap();
+        assert_eq!(iter.next(), None);
+        let mut iter = b
+            .find_regex_iter(r"ber\b", Position::new(0, 0), FindOptions::default())
+            .unwrap();
+        assert_eq!(iter.next(), Some(Range::new(0, 6, 0, 9)));
+    }
+
+    #[test]
+    fn test_find_regex_fallback() {
+        // A shorter match which ends before the current position.
+        let b = Buffer::from_text("über aaaa");
+        let mut iter = b
+            .find_regex_iter(r"\ba+", Position::new(0, 7), FindOptions::default())
+            .unwrap();
+        assert_eq!(iter.prev(), Some(Range::new(0, 5, 0, 7)));
+
+        // Not a whole word. Try again from the next character.
+        let b = Buffer::from_text("é xab b");
+        let mut iter = b
+            .find_regex_iter(r"\w?b\b", Position::new(0, 0), FindOptions::whole_word())
+            .unwrap();
+        assert_eq!(iter.next(), Some(Range::new(0, 6, 0, 7)));
+
+        // Matches beyond the first window.
+        let y = FALLBACK_WINDOW_LINES * 3;
+        let b = Buffer::from_text(&format!("{}über", "é\n".repeat(y)));
+        let mut iter = b
+            .find_regex_iter(r"\bü\w+", Position::new(0, 0), FindOptions::default())
+            .unwrap();
+        assert_eq!(iter.next(), Some(Range::new(y, 0, y, 4)));
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.prev(), Some(Range::new(y, 0, y, 4)));
+
+        // A match across windows.
+        let y = FALLBACK_WINDOW_LINES - 1;
+        let b = Buffer::from_text(&format!("{}x\nü", "é\n".repeat(y)));
+        let mut iter = b
+            .find_regex_iter(r"x\n\bü", Position::new(0, 0), FindOptions::default())
+            .unwrap();
+        assert_eq!(iter.next(), Some(Range::new(y, 0, y + 1, 1)));
+    }
+
+    #[test]
+    fn test_find_regex_multiline() {
+        let b = Buffer::from_text("fn a() {\n}\nfn b() {\n    x\n}\n");
+        let mut iter = b
+            .find_regex_iter(r"\{\n\s*x", Position::new(0, 0), FindOptions::default())
+            .unwrap();
+        assert_eq!(iter.next(), Some(Range::new(2, 7, 3, 5)));
+        let mut iter = b
+            .find_regex_iter(r"(?s)\{.*?\}", Position::new(0, 0), FindOptions::default())
+            .unwrap();
+        assert_eq!(iter.next(), Some(Range::new(0, 7, 1, 1)));
+        assert_eq!(iter.next(), Some(Range::new(2, 7, 4, 1)));
+        assert_eq!(iter.prev(), Some(Range::new(2, 7, 4, 1)));
+    }
+
+    #[test]
+    fn test_find_regex_across_chunks() {
+        let text = format!("{}é needle {}", "x".repeat(5000), "y".repeat(5000));
+        let b = Buffer::from_text(&text);
+        assert!(b.raw_buffer().rope().chunks().count() > 1);
+
+        let mut iter = b
+            .find_regex_iter("n[a-z]+e", Position::new(0, 0), FindOptions::default())
+            .unwrap();
+        assert_eq!(iter.next(), Some(Range::new(0, 5002, 0, 5008)));
+        let mut iter = b
+            .find_regex_iter("é n", Position::new(0, 10009), FindOptions::default())
+            .unwrap();
+        assert_eq!(iter.prev(), Some(Range::new(0, 5000, 0, 5003)));
+    }
 }
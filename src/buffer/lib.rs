@@ -8,6 +8,7 @@ pub mod buffer;
 pub mod char_iter;
 pub mod cursor;
 pub mod display_width;
+pub mod encoding;
 pub mod extras;
 pub mod find;
 pub mod grapheme_iter;
@@ -17,4 +18,5 @@ pub mod raw_buffer;
 pub mod reflow_iter;
 pub mod scroll;
 pub mod syntax;
+pub mod undo_tree;
 pub mod word_iter;
@@ -1,16 +1,33 @@
 use std::ops::Deref;
 
+use serde::{Deserialize, Serialize};
+
 use crate::{
     cursor::{Cursor, Position, Range},
     raw_buffer::RawBuffer,
 };
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub struct Change {
     pub range: Range,
     pub byte_range: std::ops::Range<usize>,
     pub new_pos: Position,
     pub insert_text: String,
+    pub removed_text: String,
+}
+
+impl Change {
+    /// Returns a change which reverts `self`.
+    pub fn inverse(&self) -> Change {
+        let start_byte = self.byte_range.start;
+        Change {
+            range: Range::from_positions(self.range.front(), self.new_pos),
+            byte_range: start_byte..(start_byte + self.insert_text.len()),
+            new_pos: self.range.back(),
+            insert_text: self.removed_text.clone(),
+            removed_text: self.insert_text.clone(),
+        }
+    }
 }
 
 /// An internal mutable buffer implementation supporting primitive operations
@@ -18,6 +35,8 @@ pub struct Change {
 pub struct MutRawBuffer {
     raw: RawBuffer,
     changes: Vec<Change>,
+    /// Changes not yet saved into the undo history.
+    undo_changes: Vec<Change>,
 }
 
 impl MutRawBuffer {
@@ -25,6 +44,7 @@ impl MutRawBuffer {
         MutRawBuffer {
             raw: RawBuffer::new(),
             changes: Vec::new(),
+            undo_changes: Vec::new(),
         }
     }
 
@@ -32,6 +52,7 @@ impl MutRawBuffer {
         MutRawBuffer {
             raw: raw_buffer,
             changes: Vec::new(),
+            undo_changes: Vec::new(),
         }
     }
 
@@ -39,6 +60,7 @@ impl MutRawBuffer {
         MutRawBuffer {
             raw: RawBuffer::from_text(text),
             changes: Vec::new(),
+            undo_changes: Vec::new(),
         }
     }
 
@@ -46,6 +68,7 @@ impl MutRawBuffer {
         Ok(MutRawBuffer {
             raw: RawBuffer::from_reader(reader)?,
             changes: Vec::new(),
+            undo_changes: Vec::new(),
         })
     }
 
@@ -59,6 +82,11 @@ impl MutRawBuffer {
         changes
     }
 
+    /// Returns the changes made since the last call of this method.
+    pub fn take_undo_changes(&mut self) -> Vec<Change> {
+        std::mem::take(&mut self.undo_changes)
+    }
+
     /// Replaces the text at the `range` with `new_text`.
     ///
     /// This is the only method that modifies the buffer.
@@ -87,13 +115,16 @@ impl MutRawBuffer {
 
     pub fn edit(&mut self, range: Range, new_text: &str) -> &Change {
         let new_pos = Position::position_after_edit(range, new_text);
-        self.changes.push(Change {
+        let change = Change {
             range,
             insert_text: new_text.to_owned(),
+            removed_text: self.raw.substr(range),
             new_pos,
             byte_range: self.raw.pos_to_byte_index(range.front())
                 ..self.raw.pos_to_byte_index(range.back()),
-        });
+        };
+        self.undo_changes.push(change.clone());
+        self.changes.push(change);
 
         self.edit_without_recording(range, new_text);
         self.changes.last().unwrap()
@@ -175,4 +206,20 @@ mod tests {
         buffer.edit(Range::new(0, 1, 0, 3), "");
         assert_eq!(buffer.text(), "ADEFG");
     }
+
+    #[test]
+    fn test_inverse_change() {
+        // AB|C       AB|X
+        // D|EF  =>   Y|EF
+        let mut buffer = MutRawBuffer::from_text("ABC\nDEF");
+        let change = buffer.edit(Range::new(0, 2, 1, 1), "X\nY").clone();
+        assert_eq!(buffer.text(), "ABX\nYEF");
+        assert_eq!(change.new_pos, Position::new(1, 1));
+        assert_eq!(change.removed_text, "C\nD");
+
+        let inverse = change.inverse();
+        assert_eq!(inverse.range, Range::new(0, 2, 1, 1));
+        buffer.edit(inverse.range, &inverse.insert_text);
+        assert_eq!(buffer.text(), "ABC\nDEF");
+    }
 }
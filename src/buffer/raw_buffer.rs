@@ -1,9 +1,12 @@
 use std::{cmp::min, fmt};
 
+use noa_editorconfig::{detect_end_of_line, EndOfLine};
+
 use crate::{
     char_iter::CharIter,
     cursor::{Position, Range},
-    find::FindIter,
+    encoding::{decode, detect_encoding, with_bom_of, Encoding, FileEncoding},
+    find::{FindError, FindIter, FindOptions, FindRegexIter},
     grapheme_iter::{BidirectionalGraphemeIter, GraphemeIter},
     paragraph_iter::{ParagraphIndex, ParagraphIter},
     reflow_iter::ReflowIter,
@@ -33,10 +36,36 @@ impl RawBuffer {
         }
     }
 
+    /// Reads a text. CRLFs are converted into LF, and so are CRs if they're
+    /// the line ending of the text.
     pub fn from_reader<T: std::io::Read>(reader: T) -> std::io::Result<RawBuffer> {
-        Ok(RawBuffer {
-            rope: ropey::Rope::from_reader(reader)?,
-        })
+        RawBuffer::from_reader_with_encoding(reader, None).map(|(buffer, _)| buffer)
+    }
+
+    /// Reads a text in `encoding`, or in the detected one if it's `None`.
+    /// CRLFs are converted into LF, and so are CRs if they're the line ending
+    /// of the text.
+    pub fn from_reader_with_encoding<T: std::io::Read>(
+        mut reader: T,
+        encoding: Option<&'static Encoding>,
+    ) -> std::io::Result<(RawBuffer, FileEncoding)> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        let encoding = match encoding {
+            Some(encoding) => with_bom_of(&bytes, encoding),
+            None => detect_encoding(&bytes),
+        };
+        let mut text = decode(&bytes, encoding)?;
+        if text.contains('\r') {
+            text = match detect_end_of_line(&text) {
+                Some(EndOfLine::Cr) => text.replace("\r\n", "\n").replace('\r', "\n"),
+                // Keep stray CRs as they are: converting them into LF would
+                // change the file on save.
+                _ => text.replace("\r\n", "\n"),
+            };
+        }
+
+        Ok((RawBuffer::from_text(&text), encoding))
     }
 
     pub fn rope(&self) -> &ropey::Rope {
@@ -47,6 +76,31 @@ impl RawBuffer {
         self.rope.write_to(writer)
     }
 
+    /// Writes the text with LFs converted into `eol`.
+    pub fn write_to_with_eol(
+        &self,
+        mut writer: impl std::io::Write,
+        eol: EndOfLine,
+    ) -> std::io::Result<()> {
+        if eol == EndOfLine::Lf {
+            return self.write_to(writer);
+        }
+
+        for chunk in self.rope.chunks() {
+            let mut lines = chunk.split('\n');
+            if let Some(first) = lines.next() {
+                writer.write_all(first.as_bytes())?;
+            }
+
+            for line in lines {
+                writer.write_all(eol.as_str().as_bytes())?;
+                writer.write_all(line.as_bytes())?;
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn is_empty(&self) -> bool {
         self.rope.len_bytes() == 0
     }
@@ -245,8 +299,23 @@ impl RawBuffer {
     }
 
     /// Returns an iterator which returns occurrences of the given string.
-    pub fn find_iter<'a, 'b>(&'a self, query: &'b str, pos: Position) -> FindIter<'a, 'b> {
-        FindIter::new(self.char_iter(pos), query)
+    pub fn find_iter<'a, 'b>(
+        &'a self,
+        query: &'b str,
+        pos: Position,
+        options: FindOptions,
+    ) -> FindIter<'a, 'b> {
+        FindIter::new(self.char_iter(pos), query, options)
+    }
+
+    /// Returns an iterator of matches of a regular expression `pattern`.
+    pub fn find_regex_iter(
+        &self,
+        pattern: &str,
+        pos: Position,
+        options: FindOptions,
+    ) -> Result<FindRegexIter<'_>, FindError> {
+        FindRegexIter::new(self, pattern, pos, options)
     }
 
     pub(crate) fn rope_slice(&self, range: Range) -> ropey::RopeSlice<'_> {
@@ -314,6 +383,37 @@ impl From<ropey::Rope> for RawBuffer {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_end_of_line() {
+        let buffer = RawBuffer::from_reader("a\r\nb\rc\r".as_bytes()).unwrap();
+        assert_eq!(buffer.text(), "a\nb\nc\n");
+
+        for (eol, expected) in [
+            (EndOfLine::Lf, "a\nb\nc\n"),
+            (EndOfLine::CrLf, "a\r\nb\r\nc\r\n"),
+            (EndOfLine::Cr, "a\rb\rc\r"),
+        ] {
+            let mut written = Vec::new();
+            buffer.write_to_with_eol(&mut written, eol).unwrap();
+            assert_eq!(String::from_utf8(written).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_stray_cr() {
+        for (text, eol) in [
+            ("a\nb\rc\nd\n", EndOfLine::Lf),
+            ("a\r\nb\rc\r\nd\r\n", EndOfLine::CrLf),
+        ] {
+            let buffer = RawBuffer::from_reader(text.as_bytes()).unwrap();
+            assert_eq!(buffer.text(), "a\nb\rc\nd\n");
+
+            let mut written = Vec::new();
+            buffer.write_to_with_eol(&mut written, eol).unwrap();
+            assert_eq!(String::from_utf8(written).unwrap(), text);
+        }
+    }
+
     #[test]
     fn test_substr() {
         let buffer = RawBuffer::from_text("...AB...");
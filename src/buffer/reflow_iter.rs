@@ -143,7 +143,7 @@ impl<'a> Iterator for ReflowIter<'a> {
     }
 }
 
-fn width_to_next_tab_stop(x: usize, tab_width: usize) -> usize {
+pub(crate) fn width_to_next_tab_stop(x: usize, tab_width: usize) -> usize {
     let level = x / tab_width + 1;
     tab_width * level - x
 }
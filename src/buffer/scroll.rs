@@ -5,7 +5,7 @@ use crate::{
     reflow_iter::ScreenPosition,
 };
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Scroll {
     pub paragraph_index: ParagraphIndex,
     pub y_in_paragraph: usize,
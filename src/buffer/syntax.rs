@@ -8,8 +8,8 @@ use crate::{
 
 use noa_languages::{
     tree_sitter::{
-        self, get_highlights_query, get_tree_sitter_parser, InputEdit, Node, QueryCursor,
-        TextProvider,
+        self, get_highlights_query, get_indents_query, get_textobjects_query,
+        get_tree_sitter_parser, InputEdit, Node, QueryCursor, QueryPredicateArg, TextProvider,
     },
     Language,
 };
@@ -72,6 +72,83 @@ impl Query {
         }
     }
 
+    /// Calls `callback` with each captured node and the capture name.
+    pub fn query_nodes<'tree, F>(
+        &self,
+        tree: &'tree tree_sitter::Tree,
+        buffer: &RawBuffer,
+        query_range: Option<Range>,
+        mut callback: F,
+    ) where
+        F: FnMut(Node<'tree>, &str),
+    {
+        let mut cursor = QueryCursor::new();
+        if let Some(range) = query_range {
+            cursor.set_point_range(range.into());
+        }
+
+        let matches = cursor.matches(&self.raw_query, tree.root_node(), RopeTextProvider(buffer));
+        for m in matches {
+            for cap in m.captures {
+                if let Some(name) = self.raw_query.capture_names().get(cap.index as usize) {
+                    callback(cap.node, name);
+                }
+            }
+        }
+    }
+
+    /// Calls `callback` with each captured range and the capture name,
+    /// including ranges defined by `#make-range!` in nvim-treesitter queries:
+    ///
+    /// ```scm
+    /// (#make-range! "function.inner" @_start @_end)
+    /// ```
+    pub fn query_ranges<F>(
+        &self,
+        tree: &tree_sitter::Tree,
+        buffer: &RawBuffer,
+        query_range: Option<Range>,
+        mut callback: F,
+    ) where
+        F: FnMut(Range, &str),
+    {
+        let mut cursor = QueryCursor::new();
+        if let Some(range) = query_range {
+            cursor.set_point_range(range.into());
+        }
+
+        let matches = cursor.matches(&self.raw_query, tree.root_node(), RopeTextProvider(buffer));
+        for m in matches {
+            for cap in m.captures {
+                if let Some(name) = self.raw_query.capture_names().get(cap.index as usize) {
+                    callback(cap.node.buffer_range(), name);
+                }
+            }
+
+            for predicate in self.raw_query.general_predicates(m.pattern_index) {
+                let (name, start, end) = match (&*predicate.operator, predicate.args.as_slice()) {
+                    (
+                        "make-range!",
+                        [QueryPredicateArg::String(name), QueryPredicateArg::Capture(start), QueryPredicateArg::Capture(end)],
+                    ) => (name, *start, *end),
+                    _ => continue,
+                };
+
+                // Captures in a quantified pattern like `(_)*` may appear
+                // multiple times.
+                let start = m.nodes_for_capture_index(start).next();
+                let end = m.nodes_for_capture_index(end).last();
+                if let (Some(start), Some(end)) = (start, end) {
+                    let range = Range::from_positions(
+                        start.buffer_range().front(),
+                        end.buffer_range().back(),
+                    );
+                    callback(range, name);
+                }
+            }
+        }
+    }
+
     pub fn captures<F>(
         &self,
         tree: &tree_sitter::Tree,
@@ -173,9 +250,28 @@ impl SyntaxParser {
     }
 }
 
+/// Compiles a query which is not required for syntax highlighting. Returns
+/// `None` if it's not available or broken.
+fn optional_query(
+    ts_lang: tree_sitter::Language,
+    lang: &Language,
+    kind: &str,
+    query_str: Option<&str>,
+) -> Option<Query> {
+    match Query::new(ts_lang, query_str?) {
+        Ok(query) => Some(query),
+        Err(err) => {
+            warn!("failed to load {} query for {}: {:?}", kind, lang.name, err);
+            None
+        }
+    }
+}
+
 pub struct Syntax {
-    tree: tree_sitter::Tree,
+    parser: SyntaxParser,
     highlight_query: Query,
+    indents_query: Option<Query>,
+    textobjects_query: Option<Query>,
 }
 
 impl Syntax {
@@ -186,19 +282,59 @@ impl Syntax {
             get_highlights_query(lang.name).unwrap_or(""),
         )
         .map_err(ParserError::QueryError)?;
+        let indents_query = optional_query(
+            parser.ts_lang,
+            lang,
+            "indents",
+            get_indents_query(lang.name),
+        );
+        let textobjects_query = optional_query(
+            parser.ts_lang,
+            lang,
+            "textobjects",
+            get_textobjects_query(lang.name),
+        );
 
         Ok(Syntax {
-            tree: parser.tree,
+            parser,
             highlight_query,
+            indents_query,
+            textobjects_query,
         })
     }
 
     pub fn tree(&self) -> &tree_sitter::Tree {
-        &self.tree
+        self.parser.tree()
     }
 
     pub fn set_tree(&mut self, tree: tree_sitter::Tree) {
-        self.tree = tree;
+        self.parser.tree = tree;
+    }
+
+    pub fn parse_fully(&mut self, buffer: &RawBuffer) {
+        self.parser.parse_fully(buffer);
+    }
+
+    pub fn parse_incrementally(&mut self, buffer: &RawBuffer, changes: &[Change]) {
+        self.parser.parse_incrementally(buffer, changes);
+    }
+
+    pub fn indents_query(&self) -> Option<&Query> {
+        self.indents_query.as_ref()
+    }
+
+    pub fn textobjects_query(&self) -> Option<&Query> {
+        self.textobjects_query.as_ref()
+    }
+
+    #[cfg(test)]
+    pub(crate) fn set_textobjects_query_for_test(&mut self, query: Option<&str>) {
+        self.textobjects_query = query.map(|query| Query::new(self.parser.ts_lang, query).unwrap());
+    }
+
+    #[cfg(test)]
+    pub(crate) fn set_indents_query_for_test(&mut self, query: &str) {
+        self.indents_query = Some(Query::new(self.parser.ts_lang, query).unwrap());
     }
 
     pub fn query_highlight<F>(&self, buffer: &RawBuffer, range: Range, mut callback: F)
@@ -236,7 +372,7 @@ impl Syntax {
     where
         F: FnMut(&tree_sitter::Node<'_>, Range) -> ControlFlow<()>,
     {
-        let root = self.tree.root_node();
+        let root = self.tree().root_node();
         self.visit_ts_node(root, &mut root.walk(), &mut callback);
     }
 
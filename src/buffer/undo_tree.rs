@@ -0,0 +1,326 @@
+use std::time::SystemTime;
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::{
+    cursor::{CursorId, CursorSet},
+    mut_raw_buffer::Change,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub struct UndoNodeId(usize);
+
+/// A buffer state in the undo tree.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct UndoNode {
+    parent: Option<UndoNodeId>,
+    children: Vec<UndoNodeId>,
+    /// The child to be visited by redo.
+    last_visited_child: Option<UndoNodeId>,
+    /// The changes from the parent state to this state.
+    pub(crate) changes: Vec<Change>,
+    pub(crate) cursors_before: CursorSet,
+    pub(crate) cursors_after: CursorSet,
+    saved_at: SystemTime,
+}
+
+impl UndoNode {
+    /// The number of lines added by the changes (negative if removed).
+    fn line_delta(&self) -> isize {
+        self.changes
+            .iter()
+            .map(|change| {
+                change.insert_text.matches('\n').count() as isize
+                    - change.removed_text.matches('\n').count() as isize
+            })
+            .sum()
+    }
+}
+
+/// A step to move from a state to its neighbor.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum UndoStep {
+    /// Reverts the changes of the node, i.e. moves to its parent.
+    Revert(UndoNodeId),
+    /// Applies the changes of the node, i.e. moves from its parent.
+    Apply(UndoNodeId),
+}
+
+/// A leaf in the undo tree.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct UndoBranch {
+    pub id: UndoNodeId,
+    pub saved_at: SystemTime,
+    /// The difference of the number of lines from the current state.
+    pub line_delta: isize,
+    pub is_current: bool,
+}
+
+/// The undo history. Unlike a linear undo stack, making changes after undo
+/// creates a new branch instead of discarding the undone changes.
+#[derive(Serialize)]
+pub struct UndoTree {
+    nodes: Vec<UndoNode>,
+    current: UndoNodeId,
+}
+
+impl<'de> Deserialize<'de> for UndoTree {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<UndoTree, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(rename = "UndoTree")]
+        struct Fields {
+            nodes: Vec<UndoNode>,
+            current: UndoNodeId,
+        }
+
+        // Keep the cursor IDs consistent across the nodes.
+        let Fields { nodes, current } =
+            CursorId::with_deserialized_ids(|| Fields::deserialize(deserializer))?;
+        Ok(UndoTree { nodes, current })
+    }
+}
+
+impl UndoTree {
+    pub fn new() -> UndoTree {
+        let root = UndoNode {
+            parent: None,
+            children: Vec::new(),
+            last_visited_child: None,
+            changes: Vec::new(),
+            cursors_before: CursorSet::new(),
+            cursors_after: CursorSet::new(),
+            saved_at: SystemTime::now(),
+        };
+
+        UndoTree {
+            nodes: vec![root],
+            current: UndoNodeId(0),
+        }
+    }
+
+    pub fn current(&self) -> UndoNodeId {
+        self.current
+    }
+
+    pub fn saved_at(&self, id: UndoNodeId) -> SystemTime {
+        self.node(id).saved_at
+    }
+
+    pub(crate) fn node(&self, id: UndoNodeId) -> &UndoNode {
+        &self.nodes[id.0]
+    }
+
+    /// Adds a new state as a child of the current one and makes it current.
+    pub(crate) fn push(
+        &mut self,
+        changes: Vec<Change>,
+        cursors_before: CursorSet,
+        cursors_after: CursorSet,
+        saved_at: SystemTime,
+    ) -> UndoNodeId {
+        let id = UndoNodeId(self.nodes.len());
+        self.nodes.push(UndoNode {
+            parent: Some(self.current),
+            children: Vec::new(),
+            last_visited_child: None,
+            changes,
+            cursors_before,
+            cursors_after,
+            saved_at,
+        });
+
+        let parent = &mut self.nodes[self.current.0];
+        parent.children.push(id);
+        parent.last_visited_child = Some(id);
+        self.current = id;
+        id
+    }
+
+    /// The state to be visited by undo.
+    pub fn undo_target(&self) -> Option<UndoNodeId> {
+        self.node(self.current).parent
+    }
+
+    /// The state to be visited by redo.
+    pub fn redo_target(&self) -> Option<UndoNodeId> {
+        self.node(self.current).last_visited_child
+    }
+
+    /// Returns the next (or previous if `forward` is false) sibling of the
+    /// current state.
+    pub fn sibling_target(&self, forward: bool) -> Option<UndoNodeId> {
+        let parent = self.node(self.node(self.current).parent?);
+        let index = parent.children.iter().position(|id| *id == self.current)?;
+        if forward {
+            parent.children.get(index + 1).copied()
+        } else {
+            index.checked_sub(1).map(|i| parent.children[i])
+        }
+    }
+
+    /// Returns the latest state saved at or before `time`. Returns the root if
+    /// there's no such state.
+    pub fn state_at(&self, time: SystemTime) -> UndoNodeId {
+        self.nodes
+            .iter()
+            .rposition(|node| node.saved_at <= time)
+            .map(UndoNodeId)
+            .unwrap_or(UndoNodeId(0))
+    }
+
+    /// Returns the leaves, i.e. the latest states of each branch, in the
+    /// order they were created.
+    pub fn branches(&self) -> Vec<UndoBranch> {
+        let mut branches = Vec::new();
+        for (i, node) in self.nodes.iter().enumerate() {
+            let id = UndoNodeId(i);
+            if !node.children.is_empty() {
+                continue;
+            }
+
+            let line_delta = self
+                .path_to(id)
+                .iter()
+                .map(|step| match step {
+                    UndoStep::Revert(id) => -self.node(*id).line_delta(),
+                    UndoStep::Apply(id) => self.node(*id).line_delta(),
+                })
+                .sum();
+
+            branches.push(UndoBranch {
+                id,
+                saved_at: node.saved_at,
+                line_delta,
+                is_current: id == self.current,
+            });
+        }
+
+        branches
+    }
+
+    /// Returns the steps to move from the current state to `target`.
+    pub(crate) fn path_to(&self, target: UndoNodeId) -> Vec<UndoStep> {
+        let ancestors = |mut id: UndoNodeId| {
+            let mut ids = vec![id];
+            while let Some(parent) = self.node(id).parent {
+                ids.push(parent);
+                id = parent;
+            }
+            ids
+        };
+
+        let mut from = ancestors(self.current);
+        let mut to = ancestors(target);
+
+        // Drop the common ancestors.
+        while from.len() > 1 && to.len() > 1 && from[from.len() - 2] == to[to.len() - 2] {
+            from.pop();
+            to.pop();
+        }
+
+        let mut steps = Vec::new();
+        if from != to {
+            steps.extend(
+                from[..from.len() - 1]
+                    .iter()
+                    .map(|id| UndoStep::Revert(*id)),
+            );
+            steps.extend(
+                to[..to.len() - 1]
+                    .iter()
+                    .rev()
+                    .map(|id| UndoStep::Apply(*id)),
+            );
+        }
+
+        steps
+    }
+
+    /// Makes `target` current. Redo follows the path to it from now on.
+    pub(crate) fn set_current(&mut self, target: UndoNodeId) {
+        let mut id = target;
+        while let Some(parent) = self.node(id).parent {
+            self.nodes[parent.0].last_visited_child = Some(id);
+            id = parent;
+        }
+
+        self.current = target;
+    }
+}
+
+impl Default for UndoTree {
+    fn default() -> UndoTree {
+        UndoTree::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn push(tree: &mut UndoTree, secs: u64) -> UndoNodeId {
+        let saved_at = tree.saved_at(UndoNodeId(0)) + Duration::from_secs(secs);
+        tree.push(Vec::new(), CursorSet::new(), CursorSet::new(), saved_at)
+    }
+
+    #[test]
+    fn test_path_to() {
+        // root -> a -> b
+        //          \-> c
+        let mut tree = UndoTree::new();
+        let root = tree.current();
+        let a = push(&mut tree, 1);
+        let b = push(&mut tree, 2);
+        tree.set_current(a);
+        let c = push(&mut tree, 3);
+
+        assert_eq!(tree.path_to(c), vec![]);
+        assert_eq!(
+            tree.path_to(b),
+            vec![UndoStep::Revert(c), UndoStep::Apply(b)]
+        );
+        assert_eq!(
+            tree.path_to(root),
+            vec![UndoStep::Revert(c), UndoStep::Revert(a)]
+        );
+
+        tree.set_current(root);
+        assert_eq!(tree.redo_target(), Some(a));
+        tree.set_current(b);
+        tree.set_current(root);
+        assert_eq!(
+            tree.path_to(b),
+            vec![UndoStep::Apply(a), UndoStep::Apply(b)]
+        );
+    }
+
+    #[test]
+    fn test_siblings() {
+        let mut tree = UndoTree::new();
+        let a = push(&mut tree, 1);
+        tree.set_current(tree.undo_target().unwrap());
+        let b = push(&mut tree, 2);
+        assert_eq!(tree.sibling_target(false), Some(a));
+        assert_eq!(tree.sibling_target(true), None);
+        tree.set_current(a);
+        assert_eq!(tree.sibling_target(true), Some(b));
+        assert_eq!(tree.sibling_target(false), None);
+    }
+
+    #[test]
+    fn test_state_at() {
+        let mut tree = UndoTree::new();
+        let root = tree.current();
+        let t0 = tree.saved_at(root);
+        let a = push(&mut tree, 10);
+        let b = push(&mut tree, 20);
+        assert_eq!(tree.state_at(t0 + Duration::from_secs(15)), a);
+        assert_eq!(tree.state_at(t0 + Duration::from_secs(20)), b);
+        assert_eq!(tree.state_at(t0 + Duration::from_secs(60)), b);
+        assert_eq!(tree.state_at(t0 - Duration::from_secs(60)), root);
+    }
+}
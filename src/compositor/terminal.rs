@@ -13,7 +13,7 @@ use crossterm::{
 };
 use futures::{channel::oneshot, StreamExt};
 
-pub use crossterm::event::{KeyCode, KeyModifiers, MouseButton, MouseEvent};
+pub use crossterm::event::{KeyCode, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
 use tokio::{sync::mpsc::UnboundedSender, task::JoinHandle};
 
 use crate::canvas::DrawOp;
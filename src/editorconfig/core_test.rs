@@ -0,0 +1,153 @@
+//! Runs the test cases in `fixtures/core-test`, laid out in the same way as
+//! the editorconfig-core-test suite
+//! (<https://github.com/editorconfig/editorconfig-core-test>): each suite
+//! directory has `.in` files used in place of `.editorconfig` and a
+//! `CMakeLists.txt` with `new_ec_test` / `new_ec_test_multiline` calls. A
+//! test case resolves the properties for a path and matches the output of
+//! the `editorconfig` command (`key=value` lines) against the regex.
+//!
+//! See `fixtures/core-test/README.md` for where the cases come from.
+
+use std::path::Path;
+
+use regex::Regex;
+
+use crate::resolve_properties;
+
+/// Test cases known to fail, as `suite/name`. They're still run, and fail
+/// if they start passing so that the list is kept up to date.
+const KNOWN_FAILURES: &[&str] = &[];
+
+struct TestCase {
+    name: String,
+    config_name: String,
+    path: String,
+    regex: String,
+}
+
+/// Splits the arguments of a CMake command call into words, unescaping
+/// quoted arguments.
+fn split_cmake_args(args: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut chars = args.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            ch if ch.is_whitespace() => {}
+            '"' => {
+                let mut word = String::new();
+                while let Some(ch) = chars.next() {
+                    match ch {
+                        '"' => break,
+                        '\\' => match chars.next() {
+                            Some('t') => word.push('\t'),
+                            Some('n') => word.push('\n'),
+                            Some('r') => word.push('\r'),
+                            Some(ch) => word.push(ch),
+                            None => {}
+                        },
+                        _ => word.push(ch),
+                    }
+                }
+                words.push(word);
+            }
+            _ => {
+                let mut word = ch.to_string();
+                while let Some(ch) = chars.next_if(|ch| !ch.is_whitespace()) {
+                    word.push(ch);
+                }
+                words.push(word);
+            }
+        }
+    }
+    words
+}
+
+fn parse_cmake_lists(text: &str) -> Vec<TestCase> {
+    let mut cases = Vec::new();
+    for line in text.lines() {
+        let line = line.trim();
+        let args = match line
+            .strip_prefix("new_ec_test(")
+            .or_else(|| line.strip_prefix("new_ec_test_multiline("))
+            .and_then(|rest| rest.strip_suffix(')'))
+        {
+            Some(args) => args,
+            None => continue,
+        };
+
+        let mut words = split_cmake_args(args).into_iter();
+        cases.push(TestCase {
+            name: words.next().unwrap(),
+            config_name: words.next().unwrap(),
+            path: words.next().unwrap(),
+            regex: words.next().unwrap(),
+        });
+    }
+    cases
+}
+
+fn run_suite(suite: &str) {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("fixtures/core-test")
+        .join(suite);
+    let cases = parse_cmake_lists(&std::fs::read_to_string(dir.join("CMakeLists.txt")).unwrap());
+    assert!(!cases.is_empty());
+
+    let mut unexpected = Vec::new();
+    for case in cases {
+        let props =
+            resolve_properties(&dir.join(&case.path), &case.config_name).unwrap_or_default();
+        let output: String = props
+            .iter()
+            .map(|(key, value)| format!("{}={}\n", key, value))
+            .collect();
+
+        let passed = Regex::new(&case.regex).unwrap().is_match(&output);
+        let id = format!("{}/{}", suite, case.name);
+        let known_failure = KNOWN_FAILURES.contains(&id.as_str());
+        if passed == known_failure {
+            unexpected.push(format!(
+                "{} ({}): {} /{}/, got {:?}",
+                id,
+                case.path,
+                if passed {
+                    "unexpectedly matched"
+                } else {
+                    "expected"
+                },
+                case.regex,
+                output
+            ));
+        }
+    }
+
+    assert!(unexpected.is_empty(), "{}", unexpected.join("\n"));
+}
+
+#[test]
+fn test_split_cmake_args() {
+    assert_eq!(
+        split_cmake_args(r#"a b.in "c\;d" "^k=v[ \t\n\r]*$""#),
+        vec!["a", "b.in", "c;d", "^k=v[ \t\n\r]*$"]
+    );
+}
+
+#[test]
+fn test_glob() {
+    run_suite("glob");
+}
+
+#[test]
+fn test_parser() {
+    run_suite("parser");
+}
+
+#[test]
+fn test_properties() {
+    run_suite("properties");
+}
+
+#[test]
+fn test_filetree() {
+    run_suite("filetree");
+}
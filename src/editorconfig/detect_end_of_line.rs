@@ -0,0 +1,47 @@
+use crate::EndOfLine;
+
+/// Returns the most used line ending in `text`.
+pub fn detect_end_of_line(text: &str) -> Option<EndOfLine> {
+    let mut lf = 0;
+    let mut crlf = 0;
+    let mut cr = 0;
+    let mut chars = text.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\n' => lf += 1,
+            '\r' if chars.peek() == Some(&'\n') => {
+                chars.next();
+                crlf += 1;
+            }
+            '\r' => cr += 1,
+            _ => {}
+        }
+    }
+
+    // Prefer LF in a tie.
+    [
+        (EndOfLine::Cr, cr),
+        (EndOfLine::CrLf, crlf),
+        (EndOfLine::Lf, lf),
+    ]
+    .into_iter()
+    .filter(|(_, count)| *count > 0)
+    .max_by_key(|(_, count)| *count)
+    .map(|(eol, _)| eol)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_end_of_line() {
+        assert_eq!(detect_end_of_line(""), None);
+        assert_eq!(detect_end_of_line("abc"), None);
+        assert_eq!(detect_end_of_line("a\nb\n"), Some(EndOfLine::Lf));
+        assert_eq!(detect_end_of_line("a\r\nb\r\n"), Some(EndOfLine::CrLf));
+        assert_eq!(detect_end_of_line("a\rb\r"), Some(EndOfLine::Cr));
+        assert_eq!(detect_end_of_line("a\r\nb\r\nc\n"), Some(EndOfLine::CrLf));
+        assert_eq!(detect_end_of_line("a\r\nb\n"), Some(EndOfLine::Lf));
+    }
+}
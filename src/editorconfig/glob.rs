@@ -0,0 +1,214 @@
+use regex::Regex;
+
+fn count_unescaped(glob: &[char], target: char) -> usize {
+    let mut count = 0;
+    let mut escaped = false;
+    for ch in glob {
+        if *ch == target && !escaped {
+            count += 1;
+        }
+        escaped = *ch == '\\' && !escaped;
+    }
+    count
+}
+
+/// Parses `n1..n2` in `{n1..n2}`.
+fn parse_numeric_range(s: &[char]) -> Option<(i64, i64)> {
+    let s: String = s.iter().collect();
+    let (min, max) = s.split_once("..")?;
+    Some((min.parse().ok()?, max.parse().ok()?))
+}
+
+/// Translates a glob into a regex. Numeric ranges (`{n1..n2}`) are
+/// translated into capture groups and their ranges are returned since they
+/// can't be checked by the regex.
+fn translate(glob: &[char], nested: bool) -> (String, Vec<(i64, i64)>) {
+    let mut regex = String::new();
+    let mut ranges = Vec::new();
+    let matching_braces = count_unescaped(glob, '{') == count_unescaped(glob, '}');
+    let mut brace_level = 0;
+    let mut in_brackets = false;
+    let mut escaped = false;
+    let mut i = 0;
+    while i < glob.len() {
+        let ch = glob[i];
+        i += 1;
+
+        if escaped {
+            regex.push_str(&regex::escape(&ch.to_string()));
+            escaped = false;
+            continue;
+        }
+
+        match ch {
+            '\\' => {
+                escaped = true;
+            }
+            '*' if !in_brackets => {
+                if glob.get(i) == Some(&'*') {
+                    regex.push_str(".*");
+                    i += 1;
+                } else {
+                    regex.push_str("[^/]*");
+                }
+            }
+            '?' if !in_brackets => {
+                regex.push_str("[^/]");
+            }
+            '[' if !in_brackets => {
+                // A bracket without the closing one or with a slash is not a
+                // character class.
+                let class_len = glob[i..].iter().position(|c| *c == ']');
+                match class_len {
+                    Some(len) if !glob[i..i + len].contains(&'/') => {
+                        in_brackets = true;
+                        if matches!(glob.get(i), Some('!' | '^')) {
+                            regex.push_str("[^");
+                            i += 1;
+                        } else {
+                            regex.push('[');
+                        }
+                    }
+                    _ => {
+                        regex.push_str("\\[");
+                    }
+                }
+            }
+            ']' if in_brackets => {
+                regex.push(']');
+                in_brackets = false;
+            }
+            '-' if in_brackets => {
+                regex.push('-');
+            }
+            '{' if !in_brackets => {
+                // Look for the closing brace or a comma.
+                let mut j = i;
+                let mut inner_escaped = false;
+                let mut has_comma = false;
+                while j < glob.len() && (glob[j] != '}' || inner_escaped) {
+                    if glob[j] == ',' && !inner_escaped {
+                        has_comma = true;
+                        break;
+                    }
+                    inner_escaped = glob[j] == '\\' && !inner_escaped;
+                    j += 1;
+                }
+
+                if !has_comma && j < glob.len() {
+                    if let Some(range) = parse_numeric_range(&glob[i..j]) {
+                        regex.push_str("([+-]?\\d+)");
+                        ranges.push(range);
+                    } else {
+                        // `{single}` matches itself.
+                        let (inner_regex, inner_ranges) = translate(&glob[i..j], true);
+                        regex.push_str(&format!("\\{{{}\\}}", inner_regex));
+                        ranges.extend(inner_ranges);
+                    }
+                    i = j + 1;
+                } else if matching_braces {
+                    regex.push_str("(?:");
+                    brace_level += 1;
+                } else {
+                    regex.push_str("\\{");
+                }
+            }
+            ',' if brace_level > 0 && !in_brackets => {
+                regex.push('|');
+            }
+            '}' if brace_level > 0 && !in_brackets => {
+                regex.push(')');
+                brace_level -= 1;
+            }
+            '/' if glob[i..].starts_with(&['*', '*', '/']) => {
+                // `a/**/b` matches `a/b`.
+                regex.push_str("(?:/|/.*/)");
+                i += 3;
+            }
+            _ => {
+                regex.push_str(&regex::escape(&ch.to_string()));
+            }
+        }
+    }
+
+    if !nested {
+        regex = format!("(?s)^{}$", regex);
+    }
+
+    (regex, ranges)
+}
+
+/// Returns true if `path` matches the whole `glob`.
+pub fn matches_pattern(glob: &str, path: &str) -> bool {
+    let glob: Vec<char> = glob.chars().collect();
+    let (regex, ranges) = translate(&glob, false);
+    let captures = match Regex::new(&regex).ok().and_then(|re| re.captures(path)) {
+        Some(captures) => captures,
+        None => return false,
+    };
+
+    ranges.iter().enumerate().all(|(i, (min, max))| {
+        let number = match captures.get(i + 1) {
+            Some(number) => number.as_str(),
+            // In an alternative not taken.
+            None => return true,
+        };
+
+        let digits = number.trim_start_matches(['+', '-']);
+        if digits.len() > 1 && digits.starts_with('0') {
+            return false;
+        }
+
+        matches!(number.parse::<i64>(), Ok(n) if *min <= n && n <= *max)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_brackets() {
+        assert!(matches_pattern("[ab].c", "a.c"));
+        assert!(!matches_pattern("[ab].c", "c.c"));
+        assert!(matches_pattern("[!ab].c", "c.c"));
+        assert!(!matches_pattern("[!ab].c", "a.c"));
+        assert!(matches_pattern("[a-c].c", "b.c"));
+        assert!(matches_pattern("[a/b].c", "[a/b].c"));
+        assert!(!matches_pattern("[a/b].c", "a.c"));
+        assert!(matches_pattern("[ab.c", "[ab.c"));
+    }
+
+    #[test]
+    fn test_braces() {
+        assert!(matches_pattern("{single}.c", "{single}.c"));
+        assert!(matches_pattern("{a,{b,c}}.c", "c.c"));
+        assert!(matches_pattern("{a\\,b,c}.c", "a,b.c"));
+        assert!(!matches_pattern("{a\\,b,c}.c", "a.c"));
+        assert!(matches_pattern("{.c", "{.c"));
+        assert!(matches_pattern("{a,b.c", "{a,b.c"));
+    }
+
+    #[test]
+    fn test_numeric_ranges() {
+        assert!(matches_pattern("file{3..120}", "file3"));
+        assert!(matches_pattern("file{3..120}", "file120"));
+        assert!(!matches_pattern("file{3..120}", "file1"));
+        assert!(!matches_pattern("file{3..120}", "file121"));
+        assert!(!matches_pattern("file{3..120}", "file060"));
+        assert!(!matches_pattern("file{3..120}", "file5a"));
+        assert!(matches_pattern("file{-5..-1}", "file-3"));
+        assert!(matches_pattern("{a,{1..3}}", "2"));
+    }
+
+    #[test]
+    fn test_stars() {
+        assert!(matches_pattern("a/**/b", "a/b"));
+        assert!(matches_pattern("a/**/b", "a/x/y/b"));
+        assert!(!matches_pattern("a/*/b", "a/x/y/b"));
+        assert!(matches_pattern("a?c", "abc"));
+        assert!(!matches_pattern("a?c", "a/c"));
+        assert!(matches_pattern("a\\*c", "a*c"));
+        assert!(!matches_pattern("a\\*c", "abc"));
+    }
+}
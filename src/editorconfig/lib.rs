@@ -1,15 +1,17 @@
 #[macro_use]
 extern crate log;
 
-use std::{
-    env::current_dir,
-    error::Error,
-    path::{Path, PathBuf},
-};
+use std::{env::current_dir, error::Error, path::Path};
 
+#[cfg(test)]
+mod core_test;
+mod detect_end_of_line;
 mod detect_indent;
+mod glob;
 
+pub use detect_end_of_line::detect_end_of_line;
 pub use detect_indent::detect_indent_style;
+use glob::matches_pattern;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum IndentStyle {
@@ -24,35 +26,147 @@ pub enum EndOfLine {
     CrLf,
 }
 
+impl EndOfLine {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EndOfLine::Cr => "\r",
+            EndOfLine::Lf => "\n",
+            EndOfLine::CrLf => "\r\n",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            EndOfLine::Cr => "CR",
+            EndOfLine::Lf => "LF",
+            EndOfLine::CrLf => "CRLF",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Charset {
+    Latin1,
+    Utf8,
+    Utf8Bom,
+    Utf16Be,
+    Utf16Le,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct EditorConfig {
     pub indent_style: IndentStyle,
+    /// `true` if `indent_style` is set in .editorconfig files, not the
+    /// default or guessed one.
+    pub indent_style_is_explicit: bool,
     pub indent_size: usize,
     pub tab_width: usize,
-    // TODO: Implement `end_of_line`.
     pub end_of_line: EndOfLine,
+    /// `None` if not specified: the encoding is detected from the contents.
+    pub charset: Option<Charset>,
+    pub trim_trailing_whitespace: bool,
     pub insert_final_newline: bool,
+    /// `None` if not specified or `off`.
+    pub max_line_length: Option<usize>,
 }
 
 impl EditorConfig {
+    /// Resolves the config from .editorconfig files. Unspecified properties
+    /// are guessed from the contents of the file.
     pub fn resolve_or_guess(source_file: &Path) -> EditorConfig {
-        EditorConfig::resolve(source_file).unwrap_or_else(|| {
-            read_to_string_4k(source_file)
-                .ok()
-                .and_then(|text| detect_indent_style(&text))
+        let text = read_to_string_4k(source_file).ok();
+        let guessed = EditorConfig {
+            end_of_line: text
+                .as_deref()
+                .and_then(detect_end_of_line)
+                .unwrap_or(EndOfLine::Lf),
+            ..Default::default()
+        };
+
+        EditorConfig::resolve_with_base(source_file, guessed).unwrap_or_else(|| {
+            text.as_deref()
+                .and_then(detect_indent_style)
                 .map(|(indent_style, indent_size)| EditorConfig {
                     indent_style,
                     indent_size,
-                    ..Default::default()
+                    ..guessed
                 })
-                .unwrap_or_default()
+                .unwrap_or(guessed)
         })
     }
 
     pub fn resolve(source_file: &Path) -> Option<EditorConfig> {
+        EditorConfig::resolve_with_base(source_file, EditorConfig::default())
+    }
+
+    fn resolve_with_base(source_file: &Path, base: EditorConfig) -> Option<EditorConfig> {
         current_dir()
             .ok()
-            .and_then(|cwd| resolve_config(&cwd.join(source_file)))
+            .and_then(|cwd| resolve_config(&cwd.join(source_file), base))
+    }
+
+    /// Overrides properties by `props`. Invalid values are ignored.
+    fn apply_properties(mut self, props: &[(String, String)]) -> EditorConfig {
+        for (key, value) in props {
+            let value = value.as_str();
+            match key.as_str() {
+                "indent_style" => match value {
+                    "space" => {
+                        self.indent_style = IndentStyle::Space;
+                        self.indent_style_is_explicit = true;
+                    }
+                    "tab" => {
+                        self.indent_style = IndentStyle::Tab;
+                        self.indent_style_is_explicit = true;
+                    }
+                    _ => {}
+                },
+                "indent_size" => {
+                    if let Ok(value) = value.parse::<usize>() {
+                        self.indent_size = value;
+                    }
+                }
+                "tab_width" => {
+                    if let Ok(value) = value.parse::<usize>() {
+                        self.tab_width = value;
+                    }
+                }
+                "end_of_line" => match value {
+                    "cr" => self.end_of_line = EndOfLine::Cr,
+                    "lf" => self.end_of_line = EndOfLine::Lf,
+                    "crlf" => self.end_of_line = EndOfLine::CrLf,
+                    _ => {}
+                },
+                "charset" => match value {
+                    "latin1" => self.charset = Some(Charset::Latin1),
+                    "utf-8" => self.charset = Some(Charset::Utf8),
+                    "utf-8-bom" => self.charset = Some(Charset::Utf8Bom),
+                    "utf-16be" => self.charset = Some(Charset::Utf16Be),
+                    "utf-16le" => self.charset = Some(Charset::Utf16Le),
+                    _ => {}
+                },
+                "trim_trailing_whitespace" => match value {
+                    "true" => self.trim_trailing_whitespace = true,
+                    "false" => self.trim_trailing_whitespace = false,
+                    _ => {}
+                },
+                "insert_final_newline" => match value {
+                    "true" => self.insert_final_newline = true,
+                    "false" => self.insert_final_newline = false,
+                    _ => {}
+                },
+                "max_line_length" => {
+                    if value == "off" {
+                        self.max_line_length = None;
+                    } else if let Ok(value) = value.parse::<usize>() {
+                        self.max_line_length = Some(value);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        self
     }
 }
 
@@ -60,22 +174,67 @@ impl Default for EditorConfig {
     fn default() -> EditorConfig {
         EditorConfig {
             indent_style: IndentStyle::Space,
+            indent_style_is_explicit: false,
             indent_size: 4,
             tab_width: 8,
             end_of_line: EndOfLine::Lf,
+            charset: None,
+            trim_trailing_whitespace: false,
             insert_final_newline: false,
+            max_line_length: None,
+        }
+    }
+}
+
+const CONFIG_FILE_NAME: &str = ".editorconfig";
+const MAX_SECTION_NAME_LEN: usize = 4096;
+const MAX_KEY_LEN: usize = 1024;
+const MAX_VALUE_LEN: usize = 4096;
+
+/// Properties whose values are case insensitive.
+const CASE_INSENSITIVE_PROPERTIES: &[&str] = &[
+    "indent_style",
+    "indent_size",
+    "end_of_line",
+    "charset",
+    "trim_trailing_whitespace",
+    "insert_final_newline",
+];
+
+/// Properties in the order of appearance. Keys are in lowercase.
+type Properties = Vec<(String, String)>;
+
+fn get_property<'a>(props: &'a Properties, key: &str) -> Option<&'a str> {
+    props
+        .iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v.as_str())
+}
+
+/// Sets a property. `unset` removes the property set so far.
+fn set_property(props: &mut Properties, key: &str, value: &str) {
+    let value = if CASE_INSENSITIVE_PROPERTIES.contains(&key) || value.eq_ignore_ascii_case("unset")
+    {
+        value.to_ascii_lowercase()
+    } else {
+        value.to_owned()
+    };
+
+    let index = props.iter().position(|(k, _)| k == key);
+    match (index, value.as_str()) {
+        (Some(index), "unset") => {
+            props.remove(index);
         }
+        (None, "unset") => {}
+        (Some(index), _) => props[index].1 = value,
+        (None, _) => props.push((key.to_owned(), value)),
     }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Default)]
 struct Rule {
     pattern: String,
-    indent_style: Option<IndentStyle>,
-    indent_size: Option<usize>,
-    tab_width: Option<usize>,
-    end_of_line: Option<EndOfLine>,
-    insert_final_newline: Option<bool>,
+    properties: Properties,
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -84,168 +243,109 @@ struct ConfigFile {
     rules: Vec<Rule>,
 }
 
+/// Returns the name in a section header line (`[name]`).
+fn parse_section_header(line: &str) -> Option<&str> {
+    let rest = line.strip_prefix('[')?;
+
+    // The name can't contain unescaped `#` and `;`: they start a comment.
+    let mut escaped = false;
+    let mut name_end = rest.len();
+    for (i, ch) in rest.char_indices() {
+        if (ch == '#' || ch == ';') && !escaped {
+            name_end = i;
+            break;
+        }
+        escaped = ch == '\\' && !escaped;
+    }
+
+    let name = &rest[..rest[..name_end].rfind(']')?];
+    if name.is_empty() {
+        None
+    } else {
+        Some(name)
+    }
+}
+
 fn parse_config(body: &str) -> ConfigFile {
     let mut root = false;
     let mut rules = Vec::new();
-    let mut rule: Rule = Default::default();
-    let mut pattern = None;
+    // `None` in the preamble or in an ignored section.
+    let mut rule: Option<Rule> = None;
+    let mut in_preamble = true;
+
+    for line in body.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            // An empty or comment line. Just ignore it.
+            continue;
+        }
 
-    for mut line in body.split('\n') {
-        line = line.trim_start();
-        if line.starts_with('[') {
+        if let Some(name) = parse_section_header(line) {
             // [pattern]
-            if let Some(index) = line.find(']') {
-                if let Some(pattern) = pattern {
-                    rule.pattern = pattern;
-                    rules.push(rule);
-                }
-                pattern = Some(line[1..index].to_string());
-                rule = Default::default();
+            rules.extend(rule.take());
+            in_preamble = false;
+            if name.chars().count() <= MAX_SECTION_NAME_LEN {
+                rule = Some(Rule {
+                    pattern: name.to_owned(),
+                    properties: Vec::new(),
+                });
             }
-        } else if line.starts_with('#') {
-            // A comment line. Just ignore it.
-        } else if let Some(index) = line.find('=') {
+        } else if let Some((key, value)) = line.split_once('=') {
             // key = value
-            if index < line.len() {
-                let key = line[..index].trim();
-                let mut value = line[(index + 1)..].trim();
+            let key = key.trim().to_ascii_lowercase();
+            let mut value = value.trim();
 
-                // Remove a comment.
-                if let Some(index) = value.find('#') {
-                    value = &value[..index - 1];
-                }
+            // Remove a comment.
+            if let Some(index) = value.find(" #").or_else(|| value.find(" ;")) {
+                value = value[..index].trim_end();
+            }
 
-                match key {
-                    "root" => {
-                        root = value == "true";
-                    }
-                    "insert_final_newline" => {
-                        rule.insert_final_newline = Some(value == "true");
-                    }
-                    "indent_style" => match value {
-                        "space" => {
-                            rule.indent_style = Some(IndentStyle::Space);
-                        }
-                        "tab" => {
-                            rule.indent_style = Some(IndentStyle::Tab);
-                        }
-                        _ => {}
-                    },
-                    "end_of_line" => match value {
-                        "cr" => {
-                            rule.end_of_line = Some(EndOfLine::Cr);
-                        }
-                        "lf" => {
-                            rule.end_of_line = Some(EndOfLine::Lf);
-                        }
-                        "crlf" => {
-                            rule.end_of_line = Some(EndOfLine::CrLf);
-                        }
-                        _ => {}
-                    },
-                    "indent_size" => {
-                        if let Ok(value) = value.parse::<usize>() {
-                            rule.indent_size = Some(value);
-                        }
-                    }
-                    "tab_width" => {
-                        if let Ok(value) = value.parse::<usize>() {
-                            rule.tab_width = Some(value);
-                        }
-                    }
-                    _ => {}
+            if key.is_empty()
+                || key.chars().count() > MAX_KEY_LEN
+                || value.chars().count() > MAX_VALUE_LEN
+            {
+                continue;
+            }
+
+            if in_preamble {
+                if key == "root" {
+                    root = value.eq_ignore_ascii_case("true");
                 }
+            } else if let Some(rule) = &mut rule {
+                rule.properties.push((key, value.to_owned()));
             }
         }
     }
 
-    if let Some(pattern) = pattern {
-        rule.pattern = pattern;
-        rules.push(rule);
-    }
-
+    rules.extend(rule);
     ConfigFile { root, rules }
 }
 
-fn skip(s: &str, n: usize) -> &str {
-    if s.len() <= n {
-        ""
+/// Returns true if the section `pattern` in the .editorconfig matches
+/// `relative_path`, the path from the directory of the .editorconfig.
+fn matches_section(pattern: &str, relative_path: &str) -> bool {
+    let pattern = pattern.replace("\\#", "#").replace("\\;", ";");
+    let glob = if pattern.contains('/') {
+        // Relative to the directory of the .editorconfig.
+        format!("/{}", pattern.strip_prefix('/').unwrap_or(&pattern))
     } else {
-        &s[n..]
-    }
-}
+        // Matches files in any directory.
+        format!("**/{}", pattern)
+    };
 
-fn matches_pattern(pattern: &str, path: &str) -> bool {
-    if pattern.is_empty() && path.is_empty() {
-        return true;
-    }
-
-    if pattern.is_empty() || path.is_empty() {
-        return false;
-    }
-
-    if pattern.starts_with("**") {
-        return matches_pattern(pattern, skip(path, 1))
-            || matches_pattern(skip(pattern, 2), skip(path, 1))
-            || matches_pattern(skip(pattern, 2), path);
-    } else if pattern.starts_with('*') {
-        if path.starts_with('/') {
-            return matches_pattern(skip(pattern, 1), path);
-        } else {
-            return matches_pattern(pattern, skip(path, 1))
-                || matches_pattern(skip(pattern, 1), skip(path, 1))
-                || matches_pattern(skip(pattern, 1), path);
-        }
-    } else if pattern.starts_with('{') && pattern.contains('}') {
-        let mut prev_comma = 0;
-        let remaining = skip(pattern, pattern.find('}').unwrap() + 1);
-        while let Some(mut comma) = skip(pattern, prev_comma + 1).find(|c| c == ',' || c == '}') {
-            comma += prev_comma + 1;
-            let inner_pattern = &pattern[(prev_comma + 1)..comma];
-            let pat = format!("{}{}", inner_pattern, remaining);
-            if matches_pattern(&pat, path) {
-                return true;
-            }
-
-            if pattern[comma..].starts_with('}') {
-                break;
-            }
-
-            prev_comma = comma;
-        }
-        false
-    } else {
-        // Skip until a special character.
-        let mut i = 0;
-        for ch in pattern.chars() {
-            if ch == '{' || ch == '*' || ch == ']' {
-                break;
-            }
-            i += 1;
-        }
-
-        let span = &pattern[0..i];
-        if i > 0 && path.starts_with(span) {
-            return matches_pattern(skip(pattern, i), skip(path, i));
-        } else {
-            false
-        }
-    }
+    matches_pattern(&glob, &format!("/{}", relative_path))
 }
 
-fn resolve_config(source_file: &Path) -> Option<EditorConfig> {
+/// Reads `config_name` files in the ancestor directories and collects the
+/// properties for the source file. Returns `None` if no sections match.
+fn resolve_properties(source_file: &Path, config_name: &str) -> Option<Properties> {
     assert!(source_file.is_absolute());
 
     // Read and parse all .editconfig files...
     let mut configs = Vec::new();
     for dir in source_file.parent().unwrap().ancestors() {
-        let mut path = PathBuf::from(dir);
-        path.push(".editorconfig");
-        if let Ok(mut file) = std::fs::File::open(&path) {
-            use std::io::Read;
-            let mut body = String::with_capacity(512);
-            file.read_to_string(&mut body).ok();
-
+        let path = dir.join(config_name);
+        if let Ok(body) = std::fs::read_to_string(&path) {
             let config = parse_config(&body);
             let is_root = config.root;
             configs.push((dir.to_path_buf(), config));
@@ -256,38 +356,67 @@ fn resolve_config(source_file: &Path) -> Option<EditorConfig> {
         }
     }
 
-    // Visit from the root and determine the config for the source file.
-    let mut ret = EditorConfig::default();
+    // Visit from the root and determine the properties for the source file.
+    let mut props = Vec::new();
     let mut matched_any = false;
     trace!("config: {:#?}", configs);
     for (dir, config) in configs.iter().rev() {
+        let relative_path = source_file
+            .strip_prefix(dir)
+            .unwrap()
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+
         for rule in &config.rules {
-            let relative_path = if rule.pattern.starts_with('*') {
-                // Handle [*.foo] patterns (i.e. matching all files in all dirs)
-                source_file.file_name().unwrap().to_str().unwrap()
-            } else {
-                source_file.strip_prefix(dir).unwrap().to_str().unwrap()
-            };
-
-            if matches_pattern(&rule.pattern, relative_path) {
-                trace!("applying {}/.editorconfig", dir.as_path().display());
-                ret.indent_style = rule.indent_style.unwrap_or(ret.indent_style);
-                ret.indent_size = rule.indent_size.unwrap_or(ret.indent_size);
-                ret.tab_width = rule.tab_width.unwrap_or(ret.tab_width);
-                ret.insert_final_newline = rule
-                    .insert_final_newline
-                    .unwrap_or(ret.insert_final_newline);
+            if matches_section(&rule.pattern, &relative_path) {
+                trace!("applying [{}] in {}", rule.pattern, dir.display());
+                for (key, value) in &rule.properties {
+                    set_property(&mut props, key, value);
+                }
 
                 matched_any = true;
             }
         }
     }
 
-    if matched_any {
-        Some(ret)
-    } else {
-        None
+    if !matched_any {
+        return None;
+    }
+
+    // Fill indent_size and tab_width from each other.
+    if get_property(&props, "indent_style") == Some("tab")
+        && get_property(&props, "indent_size").is_none()
+    {
+        set_property(&mut props, "indent_size", "tab");
+    }
+
+    match (
+        get_property(&props, "indent_size").map(str::to_owned),
+        get_property(&props, "tab_width").map(str::to_owned),
+    ) {
+        (Some(indent_size), None) if indent_size != "tab" => {
+            set_property(&mut props, "tab_width", &indent_size);
+        }
+        (Some(indent_size), Some(tab_width)) if indent_size == "tab" => {
+            set_property(&mut props, "indent_size", &tab_width);
+        }
+        _ => {}
     }
+
+    Some(props)
+}
+
+/// Applies .editorconfig files on top of `base`.
+fn resolve_config(source_file: &Path, base: EditorConfig) -> Option<EditorConfig> {
+    let props = resolve_properties(source_file, CONFIG_FILE_NAME)?;
+    let mut config = base.apply_properties(&props);
+    if get_property(&props, "indent_size") == Some("tab") {
+        config.indent_size = config.tab_width;
+    }
+
+    Some(config)
 }
 
 fn read_to_string_4k(path: &Path) -> Result<String, Box<dyn Error>> {
@@ -297,7 +426,8 @@ fn read_to_string_4k(path: &Path) -> Result<String, Box<dyn Error>> {
     let mut buf = vec![0; 4096];
     let n = file.read(&mut buf)?;
     buf.truncate(n);
-    let str = String::from_utf8(buf)?;
+    // The last character may be cut off in the middle.
+    let str = String::from_utf8_lossy(&buf).into_owned();
     Ok(str)
 }
 
@@ -306,6 +436,13 @@ mod tests {
     use super::*;
     use pretty_assertions::assert_eq;
 
+    fn props(props: &[(&str, &str)]) -> Properties {
+        props
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
     #[test]
     fn test_parse_config() {
         assert_eq!(
@@ -335,33 +472,70 @@ mod tests {
                 rules: vec![
                     Rule {
                         pattern: "*.rs".to_owned(),
-                        indent_style: None,
-                        indent_size: Some(4),
-                        tab_width: None,
-                        end_of_line: None,
-                        insert_final_newline: None,
+                        properties: props(&[("indent_size", "4")]),
                     },
                     Rule {
                         pattern: "*.md".to_owned(),
-                        indent_style: Some(IndentStyle::Tab),
-                        indent_size: None,
-                        tab_width: Some(8),
-                        end_of_line: Some(EndOfLine::CrLf),
-                        insert_final_newline: Some(false),
+                        properties: props(&[
+                            ("indent_style", "tab"),
+                            ("tab_width", "8"),
+                            ("end_of_line", "crlf"),
+                            ("insert_final_newline", "false"),
+                        ]),
                     },
                     Rule {
                         pattern: "broken".to_owned(),
-                        indent_style: None,
-                        indent_size: None,
-                        tab_width: None,
-                        end_of_line: None,
-                        insert_final_newline: None,
+                        properties: props(&[("foo", "")]),
                     }
                 ]
             }
         );
     }
 
+    #[test]
+    fn test_parse_config_limits() {
+        let long_key = "k".repeat(MAX_KEY_LEN + 1);
+        let long_value = "v".repeat(MAX_VALUE_LEN + 1);
+        let config = parse_config(&format!(
+            "[*]\n{} = a\nb = {}\n{} = c\nd = {}\n",
+            long_key,
+            long_value,
+            &long_key[1..],
+            &long_value[1..]
+        ));
+
+        assert_eq!(
+            config.rules[0].properties,
+            props(&[(&long_key[1..], "c"), ("d", &long_value[1..])])
+        );
+    }
+
+    #[test]
+    fn test_apply_properties() {
+        let config = EditorConfig::default().apply_properties(&props(&[
+            ("indent_style", "tab"),
+            ("charset", "utf-8-bom"),
+            ("trim_trailing_whitespace", "true"),
+            ("max_line_length", "100"),
+            ("tab_width", "invalid"),
+        ]));
+
+        assert_eq!(
+            config,
+            EditorConfig {
+                indent_style: IndentStyle::Tab,
+                indent_style_is_explicit: true,
+                charset: Some(Charset::Utf8Bom),
+                trim_trailing_whitespace: true,
+                max_line_length: Some(100),
+                ..Default::default()
+            }
+        );
+
+        let config = config.apply_properties(&props(&[("max_line_length", "off")]));
+        assert_eq!(config.max_line_length, None);
+    }
+
     #[test]
     fn test_matches_pattern() {
         assert!(matches_pattern("lib/bar/baz.js", "lib/bar/baz.js"));
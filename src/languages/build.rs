@@ -6,6 +6,8 @@ mod languages;
 use languages::{TreeSitter, LANGUAGES};
 
 const NVIM_TREESITTER_REPO: &str = "https://github.com/nvim-treesitter/nvim-treesitter";
+const NVIM_TREESITTER_TEXTOBJECTS_REPO: &str =
+    "https://github.com/nvim-treesitter/nvim-treesitter-textobjects";
 
 fn git_clone_and_pull(repo_url: &str, repo_dir: &Path) {
     if !repo_dir.exists() {
@@ -43,9 +45,16 @@ fn extract_inherits_in_scm(scm_path: &Path) -> Vec<String> {
 }
 
 fn get_query_path(lang_name: &str, scm_name: &str) -> String {
+    // Text objects are maintained in a separate repository.
+    let repo_dir = if scm_name == "textobjects" {
+        "nvim_treesitter_textobjects"
+    } else {
+        "nvim_treesitter"
+    };
+
     format!(
-        "tree_sitter/nvim_treesitter/queries/{}/{}.scm",
-        lang_name, scm_name
+        "tree_sitter/{}/queries/{}/{}.scm",
+        repo_dir, lang_name, scm_name
     )
 }
 
@@ -55,6 +64,11 @@ fn main() {
 
     let nvim_treesitter_dir = Path::new("tree_sitter/nvim_treesitter");
     git_clone_and_pull(NVIM_TREESITTER_REPO, nvim_treesitter_dir);
+    let nvim_treesitter_textobjects_dir = Path::new("tree_sitter/nvim_treesitter_textobjects");
+    git_clone_and_pull(
+        NVIM_TREESITTER_TEXTOBJECTS_REPO,
+        nvim_treesitter_textobjects_dir,
+    );
 
     let grammars_dir = Path::new("tree_sitter/grammars");
     for lang in LANGUAGES {
@@ -135,7 +149,7 @@ fn main() {
     mod_rs.push_str("    }\n");
     mod_rs.push_str("}\n\n");
 
-    for scm_name in &["highlights", "indents"] {
+    for scm_name in &["highlights", "indents", "textobjects"] {
         mod_rs.push_str(&format!(
             "pub fn get_{}_query(name: &str) -> Option<&str> {{\n",
             scm_name
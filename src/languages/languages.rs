@@ -10,13 +10,77 @@ pub struct TreeSitter {
     pub sources: &'static [&'static str],
 }
 
+/// A tree-sitter node whose text is written in another language, e.g.
+/// `<script>` in HTML.
+pub struct Injection {
+    pub parent_kind: &'static str,
+    pub kind: &'static str,
+    pub language: &'static str,
+}
+
+/// A pair of characters: the closing one is inserted automatically when the
+/// opening one is typed.
+pub struct AutoPair {
+    pub open: char,
+    pub close: char,
+    /// Node kinds in which the pair is not closed automatically.
+    pub not_in: &'static [&'static str],
+}
+
+/// Strings and comments in the supported languages.
+const STRING_AND_COMMENT_KINDS: &[&str] = &[
+    "string",
+    "string_literal",
+    "raw_string_literal",
+    "interpreted_string_literal",
+    "char_literal",
+    "template_string",
+    "string_value",
+    "raw_string",
+    "double_quote_scalar",
+    "single_quote_scalar",
+    "comment",
+    "line_comment",
+    "block_comment",
+];
+
+const fn auto_pair(open: char, close: char) -> AutoPair {
+    AutoPair {
+        open,
+        close,
+        not_in: STRING_AND_COMMENT_KINDS,
+    }
+}
+
+const DEFAULT_AUTO_PAIRS: &[AutoPair] = &[
+    auto_pair('"', '"'),
+    auto_pair('\'', '\''),
+    auto_pair('`', '`'),
+    auto_pair('{', '}'),
+    auto_pair('(', ')'),
+    auto_pair('[', ']'),
+];
+
+/// `'` is not closed in Rust since it's also used in lifetimes.
+const RUST_AUTO_PAIRS: &[AutoPair] = &[
+    auto_pair('"', '"'),
+    auto_pair('{', '}'),
+    auto_pair('(', ')'),
+    auto_pair('[', ']'),
+];
+
 pub struct Language {
     pub name: &'static str,
     pub filenames: &'static [&'static str],
     pub extensions: &'static [&'static str],
     pub line_comment: Option<&'static str>,
+    /// The opening and closing delimiters of a block comment.
+    pub block_comment: Option<(&'static str, &'static str)>,
     /// `\1` is replaced with the finder query.
     pub heutristic_search_regex: Option<&'static str>,
+    /// Nodes written in other languages.
+    pub injections: &'static [Injection],
+    pub auto_pairs: &'static [AutoPair],
     pub tree_sitter: Option<TreeSitter>,
 }
 
@@ -40,7 +104,10 @@ pub static LANGUAGES: &[Language] = &[
         filenames: &[],
         extensions: &[],
         line_comment: None,
+        block_comment: None,
         heutristic_search_regex: None,
+        injections: &[],
+        auto_pairs: DEFAULT_AUTO_PAIRS,
         tree_sitter: None,
     },
     Language {
@@ -48,7 +115,10 @@ pub static LANGUAGES: &[Language] = &[
         filenames: &[],
         extensions: &["rs"],
         line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
         heutristic_search_regex: Some(r"(type|struct|enum|trait|static|const|fn)\s\1"),
+        injections: &[],
+        auto_pairs: RUST_AUTO_PAIRS,
         tree_sitter: Some(TreeSitter {
             url: "https://github.com/tree-sitter/tree-sitter-rust",
             sources: &["src/parser.c", "src/scanner.c"],
@@ -60,7 +130,10 @@ pub static LANGUAGES: &[Language] = &[
         filenames: &[],
         extensions: &["c", "h"],
         line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
         heutristic_search_regex: None,
+        injections: &[],
+        auto_pairs: DEFAULT_AUTO_PAIRS,
         tree_sitter: Some(TreeSitter {
             url: "https://github.com/tree-sitter/tree-sitter-c",
             sources: &["src/parser.c"],
@@ -72,7 +145,10 @@ pub static LANGUAGES: &[Language] = &[
         filenames: &[],
         extensions: &["cpp", "cxx", "hpp", "hxx"],
         line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
         heutristic_search_regex: None,
+        injections: &[],
+        auto_pairs: DEFAULT_AUTO_PAIRS,
         tree_sitter: Some(TreeSitter {
             url: "https://github.com/tree-sitter/tree-sitter-cpp",
             sources: &["src/parser.c", "src/scanner.cc"],
@@ -84,7 +160,10 @@ pub static LANGUAGES: &[Language] = &[
         filenames: &[],
         extensions: &["js"],
         line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
         heutristic_search_regex: None,
+        injections: &[],
+        auto_pairs: DEFAULT_AUTO_PAIRS,
         tree_sitter: Some(TreeSitter {
             url: "https://github.com/tree-sitter/tree-sitter-javascript",
             sources: &["src/parser.c", "src/scanner.c"],
@@ -96,7 +175,10 @@ pub static LANGUAGES: &[Language] = &[
         filenames: &[],
         extensions: &["py"],
         line_comment: Some("#"),
+        block_comment: None,
         heutristic_search_regex: None,
+        injections: &[],
+        auto_pairs: DEFAULT_AUTO_PAIRS,
         tree_sitter: Some(TreeSitter {
             url: "https://github.com/tree-sitter/tree-sitter-python",
             sources: &["src/parser.c", "src/scanner.cc"],
@@ -108,7 +190,10 @@ pub static LANGUAGES: &[Language] = &[
         filenames: &[],
         extensions: &["go"],
         line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
         heutristic_search_regex: None,
+        injections: &[],
+        auto_pairs: DEFAULT_AUTO_PAIRS,
         tree_sitter: Some(TreeSitter {
             url: "https://github.com/tree-sitter/tree-sitter-go",
             sources: &["src/parser.c"],
@@ -120,7 +205,10 @@ pub static LANGUAGES: &[Language] = &[
         filenames: &[],
         extensions: &["sh", "bash"],
         line_comment: Some("#"),
+        block_comment: None,
         heutristic_search_regex: None,
+        injections: &[],
+        auto_pairs: DEFAULT_AUTO_PAIRS,
         tree_sitter: Some(TreeSitter {
             url: "https://github.com/tree-sitter/tree-sitter-bash",
             sources: &["src/parser.c", "src/scanner.cc"],
@@ -132,7 +220,21 @@ pub static LANGUAGES: &[Language] = &[
         filenames: &[],
         extensions: &["html"],
         line_comment: None,
+        block_comment: Some(("<!--", "-->")),
         heutristic_search_regex: None,
+        injections: &[
+            Injection {
+                parent_kind: "script_element",
+                kind: "raw_text",
+                language: "javascript",
+            },
+            Injection {
+                parent_kind: "style_element",
+                kind: "raw_text",
+                language: "css",
+            },
+        ],
+        auto_pairs: DEFAULT_AUTO_PAIRS,
         tree_sitter: Some(TreeSitter {
             url: "https://github.com/tree-sitter/tree-sitter-html",
             sources: &["src/parser.c", "src/scanner.cc"],
@@ -144,7 +246,10 @@ pub static LANGUAGES: &[Language] = &[
         filenames: &[],
         extensions: &["css"],
         line_comment: None,
+        block_comment: Some(("/*", "*/")),
         heutristic_search_regex: None,
+        injections: &[],
+        auto_pairs: DEFAULT_AUTO_PAIRS,
         tree_sitter: Some(TreeSitter {
             url: "https://github.com/tree-sitter/tree-sitter-css",
             sources: &["src/parser.c", "src/scanner.c"],
@@ -156,7 +261,10 @@ pub static LANGUAGES: &[Language] = &[
         filenames: &[],
         extensions: &["scss"],
         line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
         heutristic_search_regex: None,
+        injections: &[],
+        auto_pairs: DEFAULT_AUTO_PAIRS,
         tree_sitter: Some(TreeSitter {
             url: "https://github.com/serenadeai/tree-sitter-scss",
             sources: &["src/parser.c", "src/scanner.c"],
@@ -168,7 +276,10 @@ pub static LANGUAGES: &[Language] = &[
         filenames: &[],
         extensions: &["ts"],
         line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
         heutristic_search_regex: None,
+        injections: &[],
+        auto_pairs: DEFAULT_AUTO_PAIRS,
         tree_sitter: Some(TreeSitter {
             url: "https://github.com/tree-sitter/tree-sitter-typescript",
             sources: &["src/parser.c", "src/scanner.c"],
@@ -180,7 +291,10 @@ pub static LANGUAGES: &[Language] = &[
         filenames: &[],
         extensions: &["tsx"],
         line_comment: Some("//"),
+        block_comment: Some(("/*", "*/")),
         heutristic_search_regex: None,
+        injections: &[],
+        auto_pairs: DEFAULT_AUTO_PAIRS,
         tree_sitter: Some(TreeSitter {
             url: "https://github.com/tree-sitter/tree-sitter-typescript",
             sources: &["src/parser.c", "src/scanner.c"],
@@ -192,7 +306,10 @@ pub static LANGUAGES: &[Language] = &[
         filenames: &[],
         extensions: &["md"],
         line_comment: None,
+        block_comment: Some(("<!--", "-->")),
         heutristic_search_regex: None,
+        injections: &[],
+        auto_pairs: DEFAULT_AUTO_PAIRS,
         tree_sitter: Some(TreeSitter {
             url: "https://github.com/MDeiml/tree-sitter-markdown",
             sources: &["src/parser.c", "src/scanner.cc"],
@@ -204,7 +321,10 @@ pub static LANGUAGES: &[Language] = &[
         filenames: &[],
         extensions: &["toml"],
         line_comment: Some("#"),
+        block_comment: None,
         heutristic_search_regex: None,
+        injections: &[],
+        auto_pairs: DEFAULT_AUTO_PAIRS,
         tree_sitter: Some(TreeSitter {
             url: "https://github.com/ikatyang/tree-sitter-toml",
             sources: &["src/parser.c", "src/scanner.c"],
@@ -216,7 +336,10 @@ pub static LANGUAGES: &[Language] = &[
         filenames: &[],
         extensions: &["json"],
         line_comment: None,
+        block_comment: None,
         heutristic_search_regex: None,
+        injections: &[],
+        auto_pairs: DEFAULT_AUTO_PAIRS,
         tree_sitter: Some(TreeSitter {
             url: "https://github.com/tree-sitter/tree-sitter-json",
             sources: &["src/parser.c"],
@@ -228,7 +351,10 @@ pub static LANGUAGES: &[Language] = &[
         filenames: &[],
         extensions: &["yml", "yaml"],
         line_comment: Some("#"),
+        block_comment: None,
         heutristic_search_regex: None,
+        injections: &[],
+        auto_pairs: DEFAULT_AUTO_PAIRS,
         tree_sitter: Some(TreeSitter {
             url: "https://github.com/ikatyang/tree-sitter-yaml",
             sources: &["src/parser.c", "src/scanner.cc"],
@@ -240,7 +366,10 @@ pub static LANGUAGES: &[Language] = &[
         filenames: &["Makefile"],
         extensions: &["mk", "makefile"],
         line_comment: Some("#"),
+        block_comment: None,
         heutristic_search_regex: None,
+        injections: &[],
+        auto_pairs: DEFAULT_AUTO_PAIRS,
         tree_sitter: Some(TreeSitter {
             url: "https://github.com/alemuller/tree-sitter-make",
             sources: &["src/parser.c"],
@@ -252,7 +381,10 @@ pub static LANGUAGES: &[Language] = &[
         filenames: &["Dockerfile"],
         extensions: &["dockerfile"],
         line_comment: Some("#"),
+        block_comment: None,
         heutristic_search_regex: None,
+        injections: &[],
+        auto_pairs: DEFAULT_AUTO_PAIRS,
         tree_sitter: Some(TreeSitter {
             url: "https://github.com/camdencheek/tree-sitter-dockerfile",
             sources: &["src/parser.c"],
@@ -264,7 +396,10 @@ pub static LANGUAGES: &[Language] = &[
         filenames: &[],
         extensions: &[],
         line_comment: None,
+        block_comment: None,
         heutristic_search_regex: None,
+        injections: &[],
+        auto_pairs: DEFAULT_AUTO_PAIRS,
         tree_sitter: Some(TreeSitter {
             url: "https://github.com/tree-sitter/tree-sitter-regex",
             sources: &["src/parser.c"],
@@ -276,7 +411,10 @@ pub static LANGUAGES: &[Language] = &[
         filenames: &[],
         extensions: &[],
         line_comment: None,
+        block_comment: None,
         heutristic_search_regex: None,
+        injections: &[],
+        auto_pairs: DEFAULT_AUTO_PAIRS,
         tree_sitter: Some(TreeSitter {
             url: "https://github.com/stsewd/tree-sitter-comment",
             sources: &["src/parser.c", "src/scanner.c"],
@@ -1,14 +1,18 @@
 use anyhow::{Context, Result};
-use noa_buffer::cursor::{Position, Range};
+use noa_buffer::{
+    cursor::{Position, Range},
+    find::FindOptions,
+};
 use noa_compositor::compositor::Compositor;
 
 use crate::{
     clipboard::{ClipboardData, SystemClipboardData},
-    editor::Editor,
+    document::Document,
+    editor::{Editor, PasteCycle},
     notify_warn,
 };
 
-use super::Action;
+use super::{clipboard_history::paste, Action};
 
 pub struct Save;
 
@@ -155,6 +159,18 @@ impl Action for MoveToPrevWord {
     }
 }
 
+/// Returns the range to be searched: the selection if any, or the word at the
+/// main cursor. The latter is searched as a whole word.
+fn current_word_and_find_options(doc: &Document) -> Option<(Range, FindOptions)> {
+    let c = doc.main_cursor();
+    if c.is_selection() {
+        Some((c.selection(), FindOptions::default()))
+    } else {
+        doc.current_word(c.moving_position())
+            .map(|range| (range, FindOptions::whole_word()))
+    }
+}
+
 pub struct FindCurrentWord;
 
 impl Action for FindCurrentWord {
@@ -162,22 +178,29 @@ impl Action for FindCurrentWord {
         "find_current_word"
     }
 
-    fn run(&self, _editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
-        // TODO:
-        // let doc = editor.current_document_mut();
-        // let buffer = doc.buffer_mut();
-        // buffer.clear_secondary_cursors();
-        // let c = buffer.main_cursor();
-        // let word_range = if c.is_selection() {
-        //     Some(c.selection())
-        // } else {
-        //     buffer.current_word(c.moving_position())
-        // };
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        let doc = editor.current_document_mut();
 
-        // if let Some(word_range) = word_range {
-        //     let text = buffer.substr(word_range);
-        //     editor.find_query.set_text(&text);
-        // }
+        doc.clear_secondary_cursors();
+        let (word_range, options) = match current_word_and_find_options(doc) {
+            Some(result) => result,
+            None => return Ok(()),
+        };
+
+        // Select the next occurrence. Wrap around if it's the last one.
+        let text = doc.substr(word_range);
+        let next = doc
+            .find_iter(&text, word_range.back(), options)
+            .next()
+            .or_else(|| doc.find_iter(&text, Position::new(0, 0), options).next());
+        if let Some(range) = next {
+            doc.select_main_cursor_range(range);
+        }
+
+        // Highlight the occurrences. The replace mode reuses the query too.
+        editor.find_query = text;
+        editor.find_options = options;
+        editor.find_regex = false;
         Ok(())
     }
 }
@@ -221,16 +244,10 @@ impl Action for SelectAllCurrentWord {
         let doc = editor.current_document_mut();
 
         doc.clear_secondary_cursors();
-        let c = doc.main_cursor();
-        let word_range = if c.is_selection() {
-            Some(c.selection())
-        } else {
-            doc.current_word(c.moving_position())
-        };
-
-        if let Some(word_range) = word_range {
+        if let Some((word_range, options)) = current_word_and_find_options(doc) {
             let text = doc.substr(word_range);
-            let selections: Vec<Range> = doc.find_iter(&text, Position::new(0, 0)).collect();
+            let selections: Vec<Range> =
+                doc.find_iter(&text, Position::new(0, 0), options).collect();
             for selection in selections {
                 doc.add_cursor(selection);
             }
@@ -240,6 +257,49 @@ impl Action for SelectAllCurrentWord {
     }
 }
 
+pub struct AddNextOccurrence;
+
+impl Action for AddNextOccurrence {
+    fn name(&self) -> &'static str {
+        "add_next_occurrence"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        if !editor.current_document_mut().add_next_occurrence() {
+            notify_warn!("no more occurrences");
+        }
+        Ok(())
+    }
+}
+
+pub struct SkipOccurrence;
+
+impl Action for SkipOccurrence {
+    fn name(&self) -> &'static str {
+        "skip_occurrence"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        if !editor.current_document_mut().skip_occurrence() {
+            notify_warn!("no more occurrences");
+        }
+        Ok(())
+    }
+}
+
+pub struct RemoveLastAddedCursor;
+
+impl Action for RemoveLastAddedCursor {
+    fn name(&self) -> &'static str {
+        "remove_last_added_cursor"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        editor.current_document_mut().remove_last_added_cursor();
+        Ok(())
+    }
+}
+
 pub struct SelectPrevWord;
 
 impl Action for SelectPrevWord {
@@ -390,6 +450,7 @@ impl Action for Cut {
             .copy_into_clipboard(ClipboardData::from_buffer(&doc.buffer))
             .with_context(|| "failed to copy to clipboard")?;
 
+        editor.current_document_mut().delete_if_not_empty();
         Ok(())
     }
 }
@@ -402,19 +463,11 @@ impl Action for Copy {
     }
 
     fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
-        match editor
+        let doc = editor.current_document();
+        editor
             .clipboard
-            .copy_from_clipboard()
-            .with_context(|| "failed to copy from clipboard")?
-        {
-            SystemClipboardData::Ours(ClipboardData { texts }) => {
-                let strs: Vec<&str> = texts.iter().map(|s| s.as_str()).collect();
-                editor.current_document_mut().insert_multiple(&strs);
-            }
-            SystemClipboardData::Others(string) => {
-                editor.current_document_mut().insert(&string);
-            }
-        }
+            .copy_into_clipboard(ClipboardData::from_buffer(&doc.buffer))
+            .with_context(|| "failed to copy to clipboard")?;
 
         Ok(())
     }
@@ -428,7 +481,33 @@ impl Action for Paste {
     }
 
     fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
-        editor.current_document_mut().truncate();
+        let data = editor
+            .clipboard
+            .copy_from_clipboard()
+            .with_context(|| "failed to copy from clipboard")?;
+
+        // The history entry to be pasted by `paste_previous` next.
+        let next_index = match data {
+            SystemClipboardData::Ours(data) => {
+                paste(editor, &data);
+                1
+            }
+            SystemClipboardData::Others(string) => {
+                paste(
+                    editor,
+                    &ClipboardData {
+                        texts: vec![string],
+                        is_block: false,
+                    },
+                );
+                0
+            }
+        };
+
+        editor.paste_cycle = Some(PasteCycle {
+            next_index,
+            buffer_after: editor.current_document().raw_buffer().clone(),
+        });
         Ok(())
     }
 }
@@ -441,7 +520,12 @@ impl Action for Undo {
     }
 
     fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
-        if !editor.current_document_mut().undo() {
+        let doc = editor.current_document_mut();
+        let undone = match doc.large_file.as_mut() {
+            Some(large_file) => large_file.undo(),
+            None => doc.undo(),
+        };
+        if !undone {
             notify_warn!("no more undo");
         }
         Ok(())
@@ -469,7 +553,12 @@ impl Action for Redo {
     }
 
     fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
-        if !editor.current_document_mut().redo() {
+        let doc = editor.current_document_mut();
+        let redone = match doc.large_file.as_mut() {
+            Some(large_file) => large_file.redo(),
+            None => doc.redo(),
+        };
+        if !redone {
             notify_warn!("no more redo");
         }
         Ok(())
@@ -503,6 +592,32 @@ impl Action for CommentOut {
     }
 }
 
+pub struct BlockCommentOut;
+
+impl Action for BlockCommentOut {
+    fn name(&self) -> &'static str {
+        "block_comment_out"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        editor.current_document_mut().toggle_block_comment_out();
+        Ok(())
+    }
+}
+
+pub struct ReflowParagraph;
+
+impl Action for ReflowParagraph {
+    fn name(&self) -> &'static str {
+        "reflow_paragraph"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        editor.current_document_mut().reflow_paragraph();
+        Ok(())
+    }
+}
+
 pub struct ExpandSelection;
 
 impl Action for ExpandSelection {
@@ -515,3 +630,16 @@ impl Action for ExpandSelection {
         Ok(())
     }
 }
+
+pub struct ShrinkSelection;
+
+impl Action for ShrinkSelection {
+    fn name(&self) -> &'static str {
+        "shrink_selection"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        editor.current_document_mut().shrink_selections();
+        Ok(())
+    }
+}
@@ -0,0 +1,59 @@
+use anyhow::Result;
+
+use noa_compositor::compositor::Compositor;
+
+use crate::editor::Editor;
+
+use super::Action;
+
+pub struct SelectBlockUp;
+
+impl Action for SelectBlockUp {
+    fn name(&self) -> &'static str {
+        "select_block_up"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        editor.current_document_mut().extend_block_selection(-1, 0);
+        Ok(())
+    }
+}
+
+pub struct SelectBlockDown;
+
+impl Action for SelectBlockDown {
+    fn name(&self) -> &'static str {
+        "select_block_down"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        editor.current_document_mut().extend_block_selection(1, 0);
+        Ok(())
+    }
+}
+
+pub struct SelectBlockLeft;
+
+impl Action for SelectBlockLeft {
+    fn name(&self) -> &'static str {
+        "select_block_left"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        editor.current_document_mut().extend_block_selection(0, -1);
+        Ok(())
+    }
+}
+
+pub struct SelectBlockRight;
+
+impl Action for SelectBlockRight {
+    fn name(&self) -> &'static str {
+        "select_block_right"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        editor.current_document_mut().extend_block_selection(0, 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,102 @@
+use anyhow::Result;
+use noa_compositor::compositor::Compositor;
+
+use crate::{
+    clipboard::{clipboard_history, ClipboardData},
+    editor::{Editor, PasteCycle},
+    notify_warn,
+    views::selector_view::{SelectorItem, SelectorView},
+};
+
+use super::Action;
+
+/// Pastes `data` as a separate undo step so that `paste_previous` can
+/// replace it.
+pub(super) fn paste(editor: &mut Editor, data: &ClipboardData) {
+    let doc = editor.current_document_mut();
+    doc.save_undo();
+
+    let strs: Vec<&str> = data.texts.iter().map(|s| s.as_str()).collect();
+    if data.is_block && doc.cursors().len() == 1 {
+        // Keep the shape of the block.
+        doc.paste_block(&strs);
+    } else {
+        doc.insert_multiple(&strs);
+    }
+
+    doc.save_undo();
+}
+
+fn paste_history_entry(editor: &mut Editor, history: &[ClipboardData], index: usize) {
+    paste(editor, &history[index]);
+    editor.paste_cycle = Some(PasteCycle {
+        next_index: index + 1,
+        buffer_after: editor.current_document().raw_buffer().clone(),
+    });
+}
+
+pub struct PastePrevious;
+
+impl Action for PastePrevious {
+    fn name(&self) -> &'static str {
+        "paste_previous"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        let history = clipboard_history();
+        if history.is_empty() {
+            notify_warn!("clipboard history is empty");
+            return Ok(());
+        }
+
+        // Replace the text pasted right before with the previous entry.
+        let cycle = editor.paste_cycle.take();
+        let doc = editor.current_document_mut();
+        let index = match cycle {
+            Some(cycle) if *doc.raw_buffer() == cycle.buffer_after => {
+                doc.undo();
+                cycle.next_index
+            }
+            _ => 1,
+        };
+
+        let index = if index < history.len() { index } else { 0 };
+        paste_history_entry(editor, &history, index);
+        Ok(())
+    }
+}
+
+pub struct PasteFromHistory;
+
+impl Action for PasteFromHistory {
+    fn name(&self) -> &'static str {
+        "paste_from_history"
+    }
+
+    fn run(&self, _editor: &mut Editor, compositor: &mut Compositor<Editor>) -> Result<()> {
+        let history = clipboard_history();
+        if history.is_empty() {
+            notify_warn!("clipboard history is empty");
+            return Ok(());
+        }
+
+        let items = history
+            .iter()
+            .map(|data| SelectorItem {
+                label: data.preview().to_owned(),
+                detail: if data.texts.len() > 1 {
+                    format!("{} cursors", data.texts.len())
+                } else {
+                    format!("{} lines", data.texts[0].lines().count())
+                },
+            })
+            .collect();
+
+        compositor
+            .get_mut_surface_by_name::<SelectorView>("selector")
+            .open("clipboard history", items, 0, move |editor, _, index| {
+                paste_history_entry(editor, &history, index);
+            });
+        Ok(())
+    }
+}
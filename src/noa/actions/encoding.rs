@@ -0,0 +1,85 @@
+use anyhow::Result;
+use noa_buffer::encoding::Encoding;
+use noa_compositor::compositor::Compositor;
+
+use crate::{
+    editor::Editor,
+    notify_error, notify_warn,
+    views::selector_view::{SelectorItem, SelectorView},
+};
+
+use super::Action;
+
+/// Encodings selectable in `reopen_with_encoding`.
+const ENCODINGS: &[(&str, &str)] = &[
+    ("UTF-8", "Unicode"),
+    ("UTF-16LE", "Unicode"),
+    ("UTF-16BE", "Unicode"),
+    ("Shift_JIS", "Japanese"),
+    ("EUC-JP", "Japanese"),
+    ("ISO-2022-JP", "Japanese"),
+    ("GBK", "Simplified Chinese"),
+    ("gb18030", "Simplified Chinese"),
+    ("Big5", "Traditional Chinese"),
+    ("EUC-KR", "Korean"),
+    ("windows-1252", "Western (Latin-1)"),
+    ("windows-1251", "Cyrillic"),
+    ("KOI8-R", "Cyrillic"),
+];
+
+pub struct ReopenWithEncoding;
+
+impl Action for ReopenWithEncoding {
+    fn name(&self) -> &'static str {
+        "reopen_with_encoding"
+    }
+
+    fn run(&self, editor: &mut Editor, compositor: &mut Compositor<Editor>) -> Result<()> {
+        let doc = editor.current_document();
+        if doc.is_dirty() {
+            notify_warn!("save the changes before reopening");
+            return Ok(());
+        }
+
+        let current = doc.encoding().encoding.name();
+        let items = ENCODINGS
+            .iter()
+            .map(|(name, detail)| SelectorItem {
+                label: name.to_string(),
+                detail: detail.to_string(),
+            })
+            .collect();
+        let selected = ENCODINGS
+            .iter()
+            .position(|(name, _)| *name == current)
+            .unwrap_or(0);
+
+        compositor
+            .get_mut_surface_by_name::<SelectorView>("selector")
+            .open(
+                "reopen with encoding",
+                items,
+                selected,
+                |editor, _, index| {
+                    let encoding = Encoding::for_label(ENCODINGS[index].0.as_bytes()).unwrap();
+                    if let Err(err) = editor.current_document_mut().reopen_with_encoding(encoding) {
+                        notify_error!("failed to reopen: {}", err);
+                    }
+                },
+            );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encoding_names() {
+        for (name, _) in ENCODINGS {
+            let encoding = Encoding::for_label(name.as_bytes()).unwrap();
+            assert_eq!(encoding.name(), *name);
+        }
+    }
+}
@@ -0,0 +1,49 @@
+use anyhow::Result;
+use noa_compositor::compositor::Compositor;
+use noa_editorconfig::EndOfLine;
+
+use crate::{
+    editor::Editor,
+    notify_info,
+    views::selector_view::{SelectorItem, SelectorView},
+};
+
+use super::Action;
+
+const LINE_ENDINGS: &[(EndOfLine, &str)] = &[
+    (EndOfLine::Lf, "Unix"),
+    (EndOfLine::CrLf, "Windows"),
+    (EndOfLine::Cr, "classic Mac OS"),
+];
+
+pub struct SetLineEnding;
+
+impl Action for SetLineEnding {
+    fn name(&self) -> &'static str {
+        "set_line_ending"
+    }
+
+    fn run(&self, editor: &mut Editor, compositor: &mut Compositor<Editor>) -> Result<()> {
+        let current = editor.current_document().editorconfig().end_of_line;
+        let items = LINE_ENDINGS
+            .iter()
+            .map(|(eol, detail)| SelectorItem {
+                label: eol.name().to_owned(),
+                detail: detail.to_string(),
+            })
+            .collect();
+        let selected = LINE_ENDINGS
+            .iter()
+            .position(|(eol, _)| *eol == current)
+            .unwrap_or(0);
+
+        compositor
+            .get_mut_surface_by_name::<SelectorView>("selector")
+            .open("line ending", items, selected, |editor, _, index| {
+                let eol = LINE_ENDINGS[index].0;
+                editor.current_document_mut().set_end_of_line(eol);
+                notify_info!("the file will be saved with {}", eol.name());
+            });
+        Ok(())
+    }
+}
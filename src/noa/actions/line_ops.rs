@@ -0,0 +1,108 @@
+use anyhow::Result;
+use noa_buffer::{buffer::Buffer, extras::line_ops::SortOrder};
+use noa_compositor::compositor::Compositor;
+
+use crate::editor::Editor;
+
+use super::Action;
+
+/// Applies `f` to the current buffer as a separate undo step.
+fn edit_lines(editor: &mut Editor, f: impl FnOnce(&mut Buffer)) {
+    let doc = editor.current_document_mut();
+    doc.save_undo();
+    f(doc);
+    doc.save_undo();
+}
+
+pub struct SortLines;
+
+impl Action for SortLines {
+    fn name(&self) -> &'static str {
+        "sort_lines"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        edit_lines(editor, |buffer| buffer.sort_lines(SortOrder::Lexical));
+        Ok(())
+    }
+}
+
+pub struct SortLinesNatural;
+
+impl Action for SortLinesNatural {
+    fn name(&self) -> &'static str {
+        "sort_lines_natural"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        edit_lines(editor, |buffer| buffer.sort_lines(SortOrder::Natural));
+        Ok(())
+    }
+}
+
+pub struct SortLinesCaseInsensitive;
+
+impl Action for SortLinesCaseInsensitive {
+    fn name(&self) -> &'static str {
+        "sort_lines_case_insensitive"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        edit_lines(editor, |buffer| {
+            buffer.sort_lines(SortOrder::CaseInsensitive)
+        });
+        Ok(())
+    }
+}
+
+pub struct ReverseLines;
+
+impl Action for ReverseLines {
+    fn name(&self) -> &'static str {
+        "reverse_lines"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        edit_lines(editor, Buffer::reverse_lines);
+        Ok(())
+    }
+}
+
+pub struct UniqueLines;
+
+impl Action for UniqueLines {
+    fn name(&self) -> &'static str {
+        "unique_lines"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        edit_lines(editor, Buffer::unique_lines);
+        Ok(())
+    }
+}
+
+pub struct ShuffleLines;
+
+impl Action for ShuffleLines {
+    fn name(&self) -> &'static str {
+        "shuffle_lines"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        edit_lines(editor, Buffer::shuffle_lines);
+        Ok(())
+    }
+}
+
+pub struct JoinLines;
+
+impl Action for JoinLines {
+    fn name(&self) -> &'static str {
+        "join_lines"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        edit_lines(editor, Buffer::join_lines);
+        Ok(())
+    }
+}
@@ -0,0 +1,179 @@
+use anyhow::{anyhow, Result};
+use noa_compositor::compositor::Compositor;
+
+use crate::{
+    editor::Editor,
+    macros::{MacroReplay, DEFAULT_REGISTER},
+    notify_error, notify_info, notify_warn,
+    views::selector_view::{SelectorItem, SelectorView},
+};
+
+use super::Action;
+
+/// Actions not to be recorded into a macro.
+pub const MACRO_ACTIONS: &[&str] = &[
+    "toggle_macro_recording",
+    "replay_macro",
+    "replay_macro_n_times",
+    "replay_macro_at_every_cursor",
+    "select_macro",
+];
+
+fn parse_count(s: &str) -> Result<usize> {
+    match s.trim().parse() {
+        Ok(count) if count > 0 => Ok(count),
+        _ => Err(anyhow!("invalid count: \"{}\"", s.trim())),
+    }
+}
+
+fn request_replay(editor: &mut Editor, times: usize, at_every_cursor: bool) {
+    if editor.macros.is_recording() {
+        notify_warn!("can't replay a macro while recording");
+        return;
+    }
+
+    let register = editor.macros.last_register().to_owned();
+    editor.macros.request_replay(MacroReplay {
+        register,
+        times,
+        at_every_cursor,
+    });
+}
+
+pub struct ToggleMacroRecording;
+
+impl Action for ToggleMacroRecording {
+    fn name(&self) -> &'static str {
+        "toggle_macro_recording"
+    }
+
+    fn run(&self, editor: &mut Editor, compositor: &mut Compositor<Editor>) -> Result<()> {
+        if let Some((register, num_steps)) = editor.macros.stop_recording() {
+            editor.macros.save()?;
+            notify_info!("recorded {} steps into \"{}\"", num_steps, register);
+            return Ok(());
+        }
+
+        compositor
+            .get_mut_surface_by_name::<SelectorView>("selector")
+            .open_prompt("record macro", |editor, _, input| {
+                let register = match input.trim() {
+                    "" => DEFAULT_REGISTER,
+                    name => name,
+                };
+
+                editor.macros.start_recording(register);
+            });
+        Ok(())
+    }
+}
+
+pub struct ReplayMacro;
+
+impl Action for ReplayMacro {
+    fn name(&self) -> &'static str {
+        "replay_macro"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        request_replay(editor, 1, false);
+        Ok(())
+    }
+}
+
+pub struct ReplayMacroNTimes;
+
+impl Action for ReplayMacroNTimes {
+    fn name(&self) -> &'static str {
+        "replay_macro_n_times"
+    }
+
+    fn run(&self, editor: &mut Editor, compositor: &mut Compositor<Editor>) -> Result<()> {
+        // Don't record the input to the prompt.
+        if editor.macros.is_recording() {
+            notify_warn!("can't replay a macro while recording");
+            return Ok(());
+        }
+
+        compositor
+            .get_mut_surface_by_name::<SelectorView>("selector")
+            .open_prompt("replay times", |editor, _, input| {
+                match parse_count(input) {
+                    Ok(times) => request_replay(editor, times, false),
+                    Err(err) => notify_error!("{}", err),
+                }
+            });
+        Ok(())
+    }
+}
+
+pub struct ReplayMacroAtEveryCursor;
+
+impl Action for ReplayMacroAtEveryCursor {
+    fn name(&self) -> &'static str {
+        "replay_macro_at_every_cursor"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        request_replay(editor, 1, true);
+        Ok(())
+    }
+}
+
+pub struct SelectMacro;
+
+impl Action for SelectMacro {
+    fn name(&self) -> &'static str {
+        "select_macro"
+    }
+
+    fn run(&self, editor: &mut Editor, compositor: &mut Compositor<Editor>) -> Result<()> {
+        // Don't record the input to the selector.
+        if editor.macros.is_recording() {
+            notify_warn!("can't replay a macro while recording");
+            return Ok(());
+        }
+
+        let names = editor.macros.register_names();
+        if names.is_empty() {
+            notify_warn!("no macros recorded");
+            return Ok(());
+        }
+
+        let items = names
+            .iter()
+            .map(|name| SelectorItem {
+                label: name.clone(),
+                detail: format!("{} steps", editor.macros.get(name).unwrap().len()),
+            })
+            .collect();
+        let selected = names
+            .iter()
+            .position(|name| name == editor.macros.last_register())
+            .unwrap_or(0);
+
+        compositor
+            .get_mut_surface_by_name::<SelectorView>("selector")
+            .open("macros", items, selected, move |editor, _, index| {
+                editor.macros.request_replay(MacroReplay {
+                    register: names[index].clone(),
+                    times: 1,
+                    at_every_cursor: false,
+                });
+            });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_count() {
+        assert_eq!(parse_count("3").unwrap(), 3);
+        assert_eq!(parse_count(" 10 ").unwrap(), 10);
+        assert!(parse_count("0").is_err());
+        assert!(parse_count("x").is_err());
+    }
+}
@@ -2,16 +2,27 @@ use std::{any::Any, collections::HashMap};
 
 use anyhow::{anyhow, Result};
 
+use noa_buffer::extras::text_objects::{TextObjectKind, TextObjectScope};
 use noa_compositor::compositor::Compositor;
 use once_cell::sync::Lazy;
 
-use crate::{editor::Editor, notify_error};
+use crate::{editor::Editor, macros::MacroStep, notify_error};
 
 mod basic_editing;
+mod block_selection;
 mod change_case;
+mod clipboard_history;
+mod encoding;
 mod goto;
+mod line_ending;
+mod line_ops;
 mod linemap;
+mod macros;
 mod scrolling;
+mod search;
+mod surround;
+mod text_objects;
+mod undo_tree;
 
 pub const ACTIONS: &[&dyn Action] = &[
     &basic_editing::Save,
@@ -28,12 +39,21 @@ pub const ACTIONS: &[&dyn Action] = &[
     &basic_editing::FindCurrentWord,
     &basic_editing::FindCurrentWordGlobally,
     &basic_editing::SelectAllCurrentWord,
+    &basic_editing::AddNextOccurrence,
+    &basic_editing::SkipOccurrence,
+    &basic_editing::RemoveLastAddedCursor,
+    &search::Search,
+    &search::Replace,
     &basic_editing::SelectPrevWord,
     &basic_editing::SelectNextWord,
     &basic_editing::MoveLineUp,
     &basic_editing::MoveLinesDown,
     &basic_editing::AddCursorsUp,
     &basic_editing::AddCursorsDown,
+    &block_selection::SelectBlockUp,
+    &block_selection::SelectBlockDown,
+    &block_selection::SelectBlockLeft,
+    &block_selection::SelectBlockRight,
     &basic_editing::DuplicateLinesUp,
     &basic_editing::DuplicateLinesDown,
     &basic_editing::SelectUntilBeginningOfLine,
@@ -41,19 +61,112 @@ pub const ACTIONS: &[&dyn Action] = &[
     &basic_editing::Cut,
     &basic_editing::Copy,
     &basic_editing::Paste,
+    &clipboard_history::PastePrevious,
+    &clipboard_history::PasteFromHistory,
     &basic_editing::Undo,
     &basic_editing::UndoCursors,
     &basic_editing::Redo,
+    &undo_tree::NextUndoBranch,
+    &undo_tree::PrevUndoBranch,
+    &undo_tree::UndoEarlier,
+    &undo_tree::UndoLater,
+    &undo_tree::OpenUndoTree,
     &basic_editing::SoftWrap,
     &basic_editing::CommentOut,
+    &basic_editing::BlockCommentOut,
+    &basic_editing::ReflowParagraph,
+    &line_ops::SortLines,
+    &line_ops::SortLinesNatural,
+    &line_ops::SortLinesCaseInsensitive,
+    &line_ops::ReverseLines,
+    &line_ops::UniqueLines,
+    &line_ops::ShuffleLines,
+    &line_ops::JoinLines,
+    &surround::AddSurround,
+    &surround::DeleteSurround,
+    &surround::ChangeSurround,
     &basic_editing::ExpandSelection,
+    &basic_editing::ShrinkSelection,
+    &text_objects::SelectTextObject {
+        name: "select_inside_function",
+        kind: TextObjectKind::Function,
+        scope: TextObjectScope::Inside,
+    },
+    &text_objects::SelectTextObject {
+        name: "select_around_function",
+        kind: TextObjectKind::Function,
+        scope: TextObjectScope::Around,
+    },
+    &text_objects::SelectTextObject {
+        name: "select_inside_class",
+        kind: TextObjectKind::Class,
+        scope: TextObjectScope::Inside,
+    },
+    &text_objects::SelectTextObject {
+        name: "select_around_class",
+        kind: TextObjectKind::Class,
+        scope: TextObjectScope::Around,
+    },
+    &text_objects::SelectTextObject {
+        name: "select_inside_parameter",
+        kind: TextObjectKind::Parameter,
+        scope: TextObjectScope::Inside,
+    },
+    &text_objects::SelectTextObject {
+        name: "select_around_parameter",
+        kind: TextObjectKind::Parameter,
+        scope: TextObjectScope::Around,
+    },
+    &text_objects::SelectTextObject {
+        name: "select_inside_comment",
+        kind: TextObjectKind::Comment,
+        scope: TextObjectScope::Inside,
+    },
+    &text_objects::SelectTextObject {
+        name: "select_around_comment",
+        kind: TextObjectKind::Comment,
+        scope: TextObjectScope::Around,
+    },
+    &text_objects::SelectTextObject {
+        name: "select_inside_block",
+        kind: TextObjectKind::Block,
+        scope: TextObjectScope::Inside,
+    },
+    &text_objects::SelectTextObject {
+        name: "select_around_block",
+        kind: TextObjectKind::Block,
+        scope: TextObjectScope::Around,
+    },
+    &text_objects::MoveToNextTextObject {
+        name: "move_to_next_function",
+        kind: TextObjectKind::Function,
+    },
+    &text_objects::MoveToPrevTextObject {
+        name: "move_to_prev_function",
+        kind: TextObjectKind::Function,
+    },
+    &text_objects::MoveToNextTextObject {
+        name: "move_to_next_class",
+        kind: TextObjectKind::Class,
+    },
+    &text_objects::MoveToPrevTextObject {
+        name: "move_to_prev_class",
+        kind: TextObjectKind::Class,
+    },
     &change_case::ToUpperCase,
     &change_case::ToLowerCase,
+    &line_ending::SetLineEnding,
+    &encoding::ReopenWithEncoding,
     &linemap::MoveToNextDiff,
     &linemap::MoveToPrevDiff,
     &scrolling::PageUp,
     &scrolling::PageDown,
     &goto::GoToLine,
+    &macros::ToggleMacroRecording,
+    &macros::ReplayMacro,
+    &macros::ReplayMacroNTimes,
+    &macros::ReplayMacroAtEveryCursor,
+    &macros::SelectMacro,
 ];
 
 pub trait Action: Any + Send + Sync {
@@ -75,7 +188,15 @@ pub fn execute_action(
     action: &str,
 ) -> Result<()> {
     match ACTION_MAP.get(action) {
-        Some(action) => action.run(editor, compositor),
+        Some(action) => {
+            if !macros::MACRO_ACTIONS.contains(&action.name()) {
+                editor
+                    .macros
+                    .record(MacroStep::Action(action.name().to_owned()));
+            }
+
+            action.run(editor, compositor)
+        }
         None => Err(anyhow!("unknown action \"{}\"", action)),
     }
 }
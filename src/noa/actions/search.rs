@@ -0,0 +1,43 @@
+use anyhow::Result;
+
+use noa_compositor::compositor::Compositor;
+
+use crate::{
+    editor::Editor,
+    views::{buffer_view::BufferView, metaline_view::MetaLine},
+};
+
+use super::Action;
+
+pub struct Search;
+
+impl Action for Search {
+    fn name(&self) -> &'static str {
+        "search"
+    }
+
+    fn run(&self, editor: &mut Editor, compositor: &mut Compositor<Editor>) -> Result<()> {
+        compositor
+            .get_mut_surface_by_name::<MetaLine>("meta_line")
+            .start_search(editor);
+        Ok(())
+    }
+}
+
+pub struct Replace;
+
+impl Action for Replace {
+    fn name(&self) -> &'static str {
+        "replace"
+    }
+
+    fn run(&self, editor: &mut Editor, compositor: &mut Compositor<Editor>) -> Result<()> {
+        compositor
+            .get_mut_surface_by_name::<MetaLine>("meta_line")
+            .start_replace(editor);
+        compositor
+            .get_mut_surface_by_name::<BufferView>("buffer")
+            .adjust_scroll(editor.current_document_mut());
+        Ok(())
+    }
+}
@@ -0,0 +1,96 @@
+use anyhow::Result;
+use noa_buffer::extras::surround::Surround;
+use noa_compositor::compositor::Compositor;
+
+use crate::{editor::Editor, notify_error, views::selector_view::SelectorView};
+
+use super::Action;
+
+fn parse_surround(input: &str) -> Option<Surround> {
+    let surround = Surround::parse(input);
+    if surround.is_none() {
+        notify_error!("invalid surround: {}", input);
+    }
+    surround
+}
+
+/// Parses a target and a replacement separated by a whitespace (`( [`), or
+/// two characters (`([`).
+fn parse_change(input: &str) -> Option<(Surround, Surround)> {
+    let mut words = input.split_whitespace();
+    let (target, replacement) = match (words.next(), words.next(), words.next()) {
+        (Some(target), Some(replacement), None) => (target.to_owned(), replacement.to_owned()),
+        (Some(chars), None, None) if chars.chars().count() == 2 => {
+            let mut chars = chars.chars();
+            (
+                chars.next().unwrap().to_string(),
+                chars.next().unwrap().to_string(),
+            )
+        }
+        _ => {
+            notify_error!("expected a target and a replacement: {}", input);
+            return None;
+        }
+    };
+
+    Some((parse_surround(&target)?, parse_surround(&replacement)?))
+}
+
+pub struct AddSurround;
+
+impl Action for AddSurround {
+    fn name(&self) -> &'static str {
+        "surround"
+    }
+
+    fn run(&self, _editor: &mut Editor, compositor: &mut Compositor<Editor>) -> Result<()> {
+        compositor
+            .get_mut_surface_by_name::<SelectorView>("selector")
+            .open_prompt("surround with", |editor, _, input| {
+                if let Some(surround) = parse_surround(input) {
+                    editor.current_document_mut().add_surround(&surround);
+                }
+            });
+        Ok(())
+    }
+}
+
+pub struct DeleteSurround;
+
+impl Action for DeleteSurround {
+    fn name(&self) -> &'static str {
+        "delete_surround"
+    }
+
+    fn run(&self, _editor: &mut Editor, compositor: &mut Compositor<Editor>) -> Result<()> {
+        compositor
+            .get_mut_surface_by_name::<SelectorView>("selector")
+            .open_prompt("delete surrounding", |editor, _, input| {
+                if let Some(target) = parse_surround(input) {
+                    editor.current_document_mut().delete_surround(&target);
+                }
+            });
+        Ok(())
+    }
+}
+
+pub struct ChangeSurround;
+
+impl Action for ChangeSurround {
+    fn name(&self) -> &'static str {
+        "change_surround"
+    }
+
+    fn run(&self, _editor: &mut Editor, compositor: &mut Compositor<Editor>) -> Result<()> {
+        compositor
+            .get_mut_surface_by_name::<SelectorView>("selector")
+            .open_prompt("change surrounding (from to)", |editor, _, input| {
+                if let Some((target, replacement)) = parse_change(input) {
+                    editor
+                        .current_document_mut()
+                        .change_surround(&target, &replacement);
+                }
+            });
+        Ok(())
+    }
+}
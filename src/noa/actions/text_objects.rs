@@ -0,0 +1,62 @@
+use anyhow::Result;
+use noa_buffer::extras::text_objects::{TextObjectKind, TextObjectScope};
+use noa_compositor::compositor::Compositor;
+
+use crate::editor::Editor;
+
+use super::Action;
+
+pub struct SelectTextObject {
+    pub name: &'static str,
+    pub kind: TextObjectKind,
+    pub scope: TextObjectScope,
+}
+
+impl Action for SelectTextObject {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        editor
+            .current_document_mut()
+            .select_text_object(self.kind, self.scope);
+        Ok(())
+    }
+}
+
+pub struct MoveToNextTextObject {
+    pub name: &'static str,
+    pub kind: TextObjectKind,
+}
+
+impl Action for MoveToNextTextObject {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        editor
+            .current_document_mut()
+            .move_to_next_text_object(self.kind);
+        Ok(())
+    }
+}
+
+pub struct MoveToPrevTextObject {
+    pub name: &'static str,
+    pub kind: TextObjectKind,
+}
+
+impl Action for MoveToPrevTextObject {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        editor
+            .current_document_mut()
+            .move_to_prev_text_object(self.kind);
+        Ok(())
+    }
+}
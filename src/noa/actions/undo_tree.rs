@@ -0,0 +1,176 @@
+use std::time::{Duration, SystemTime};
+
+use anyhow::{anyhow, Result};
+use noa_compositor::compositor::Compositor;
+
+use crate::{
+    editor::Editor,
+    notify_error, notify_warn,
+    views::selector_view::{SelectorItem, SelectorView},
+};
+
+use super::Action;
+
+/// Parses a duration like "30s", "5m", "2h", or "1d". A number without
+/// a unit is in seconds.
+fn parse_duration(s: &str) -> Result<Duration> {
+    let s = s.trim();
+    let (num, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => s.split_at(index),
+        None => (s, "s"),
+    };
+
+    let num: u64 = num
+        .parse()
+        .map_err(|_| anyhow!("invalid duration: \"{}\"", s))?;
+    let secs = match unit.trim() {
+        "s" => num,
+        "m" => num * 60,
+        "h" => num * 60 * 60,
+        "d" => num * 24 * 60 * 60,
+        _ => return Err(anyhow!("invalid duration unit: \"{}\"", unit)),
+    };
+
+    Ok(Duration::from_secs(secs))
+}
+
+fn format_elapsed(time: SystemTime) -> String {
+    let secs = time.elapsed().map(|d| d.as_secs()).unwrap_or(0);
+    if secs < 60 {
+        format!("{}s ago", secs)
+    } else if secs < 60 * 60 {
+        format!("{}m ago", secs / 60)
+    } else if secs < 24 * 60 * 60 {
+        format!("{}h ago", secs / (60 * 60))
+    } else {
+        format!("{}d ago", secs / (24 * 60 * 60))
+    }
+}
+
+pub struct NextUndoBranch;
+
+impl Action for NextUndoBranch {
+    fn name(&self) -> &'static str {
+        "next_undo_branch"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        if !editor.current_document_mut().next_undo_branch() {
+            notify_warn!("no next undo branch");
+        }
+        Ok(())
+    }
+}
+
+pub struct PrevUndoBranch;
+
+impl Action for PrevUndoBranch {
+    fn name(&self) -> &'static str {
+        "prev_undo_branch"
+    }
+
+    fn run(&self, editor: &mut Editor, _compositor: &mut Compositor<Editor>) -> Result<()> {
+        if !editor.current_document_mut().prev_undo_branch() {
+            notify_warn!("no previous undo branch");
+        }
+        Ok(())
+    }
+}
+
+pub struct UndoEarlier;
+
+impl Action for UndoEarlier {
+    fn name(&self) -> &'static str {
+        "undo_earlier"
+    }
+
+    fn run(&self, _editor: &mut Editor, compositor: &mut Compositor<Editor>) -> Result<()> {
+        compositor
+            .get_mut_surface_by_name::<SelectorView>("selector")
+            .open_prompt("earlier", |editor, _, input| match parse_duration(input) {
+                Ok(duration) => {
+                    if !editor.current_document_mut().undo_earlier(duration) {
+                        notify_warn!("no earlier changes");
+                    }
+                }
+                Err(err) => notify_error!("{}", err),
+            });
+        Ok(())
+    }
+}
+
+pub struct UndoLater;
+
+impl Action for UndoLater {
+    fn name(&self) -> &'static str {
+        "undo_later"
+    }
+
+    fn run(&self, _editor: &mut Editor, compositor: &mut Compositor<Editor>) -> Result<()> {
+        compositor
+            .get_mut_surface_by_name::<SelectorView>("selector")
+            .open_prompt("later", |editor, _, input| match parse_duration(input) {
+                Ok(duration) => {
+                    if !editor.current_document_mut().undo_later(duration) {
+                        notify_warn!("no later changes");
+                    }
+                }
+                Err(err) => notify_error!("{}", err),
+            });
+        Ok(())
+    }
+}
+
+pub struct OpenUndoTree;
+
+impl Action for OpenUndoTree {
+    fn name(&self) -> &'static str {
+        "open_undo_tree"
+    }
+
+    fn run(&self, editor: &mut Editor, compositor: &mut Compositor<Editor>) -> Result<()> {
+        let doc = editor.current_document_mut();
+        doc.save_undo();
+
+        // Newest first.
+        let mut branches = doc.undo_branches();
+        branches.reverse();
+
+        let selected = branches.iter().position(|b| b.is_current).unwrap_or(0);
+        let items = branches
+            .iter()
+            .map(|branch| SelectorItem {
+                label: format!(
+                    "{} {}",
+                    if branch.is_current { "*" } else { " " },
+                    format_elapsed(branch.saved_at)
+                ),
+                detail: format!("{:+} lines", branch.line_delta),
+            })
+            .collect();
+
+        compositor
+            .get_mut_surface_by_name::<SelectorView>("selector")
+            .open("undo tree", items, selected, move |editor, _, index| {
+                editor
+                    .current_document_mut()
+                    .goto_undo_state(branches[index].id);
+            });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("30").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_duration("30s").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_duration(" 5m").unwrap(), Duration::from_secs(300));
+        assert_eq!(parse_duration("2h").unwrap(), Duration::from_secs(7200));
+        assert!(parse_duration("5x").is_err());
+        assert!(parse_duration("m").is_err());
+    }
+}
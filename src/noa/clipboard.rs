@@ -1,19 +1,29 @@
 use std::io::prelude::*;
-use std::process::{Command, Stdio};
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::PathBuf,
+    process::{Command, Stdio},
+};
 
 use anyhow::Result;
 use noa_buffer::buffer::Buffer;
+use noa_common::dirs::noa_dir;
 use once_cell::sync::Lazy;
 use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
 
 use which::which;
 
 /// Represents data in the clipboard with more detailed contexts.
-#[derive(Clone, Debug)]
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub struct ClipboardData {
     /// The texts copied into the clipboard. It's more than one if multiple
     /// cursors were selected.
     pub texts: Vec<String>,
+    /// Whether the texts were copied from a block selection.
+    pub is_block: bool,
 }
 
 impl ClipboardData {
@@ -23,7 +33,20 @@ impl ClipboardData {
             texts.push(buffer.substr(c.selection()));
         }
 
-        ClipboardData { texts }
+        ClipboardData {
+            texts,
+            is_block: buffer.is_block_selection(),
+        }
+    }
+
+    /// Returns the first line of the copied text for previewing.
+    pub fn preview(&self) -> &str {
+        self.texts
+            .iter()
+            .flat_map(|text| text.lines())
+            .map(|line| line.trim())
+            .find(|line| !line.is_empty())
+            .unwrap_or("")
     }
 
     pub fn equals_to_str(&self, text: &str) -> bool {
@@ -49,6 +72,7 @@ impl Default for ClipboardData {
     fn default() -> Self {
         ClipboardData {
             texts: vec!["".to_string()],
+            is_block: false,
         }
     }
 }
@@ -100,6 +124,7 @@ impl ClipboardProvider for Osc52Provider {
         .ok();
         stdout.flush().ok();
 
+        save_last_clipboard_data(data);
         Ok(())
     }
 }
@@ -150,7 +175,7 @@ impl ClipboardProvider for FallbackProvider {
 
     fn copy_into_clipboard(&self, data: ClipboardData) -> Result<()> {
         // Use LAST_OUR_DATA as clipboard.
-        *LAST_OUR_DATA.lock() = data;
+        save_last_clipboard_data(data);
         Ok(())
     }
 }
@@ -182,5 +207,132 @@ pub fn get_last_clipboard_data(text: &str) -> Option<ClipboardData> {
 }
 
 fn save_last_clipboard_data(data: ClipboardData) {
-    *LAST_OUR_DATA.lock() = data;
+    *LAST_OUR_DATA.lock() = data.clone();
+
+    let mut history = CLIPBOARD_HISTORY.lock();
+    history.push(data);
+    if let Err(err) = history.save() {
+        warn!("failed to save the clipboard history: {}", err);
+    }
+}
+
+const CLIPBOARD_HISTORY_MAX_LEN: usize = 64;
+
+static CLIPBOARD_HISTORY: Lazy<Mutex<ClipboardHistory>> =
+    Lazy::new(|| Mutex::new(ClipboardHistory::load()));
+
+/// Recently copied data. Unlike the system's clipboard, it remembers all
+/// texts copied from multiple cursors.
+#[derive(Default, Serialize, Deserialize)]
+pub struct ClipboardHistory {
+    /// The newest first.
+    entries: VecDeque<ClipboardData>,
+}
+
+impl ClipboardHistory {
+    fn path() -> PathBuf {
+        noa_dir().join("clipboard_history")
+    }
+
+    /// Loads the history saved in the previous sessions.
+    fn load() -> ClipboardHistory {
+        let path = ClipboardHistory::path();
+        if !path.exists() {
+            return ClipboardHistory::default();
+        }
+
+        match File::open(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|file| Ok(bincode::deserialize_from(BufReader::new(file))?))
+        {
+            Ok(history) => history,
+            Err(err) => {
+                warn!("failed to load {}: {}", path.display(), err);
+                ClipboardHistory::default()
+            }
+        }
+    }
+
+    fn save(&self) -> Result<()> {
+        let writer = BufWriter::new(File::create(ClipboardHistory::path())?);
+        bincode::serialize_into(writer, self)?;
+        Ok(())
+    }
+
+    /// Adds an entry as the newest one. If the same data is already in the
+    /// history, it's moved to the front.
+    fn push(&mut self, data: ClipboardData) {
+        if data.texts.iter().all(|text| text.is_empty()) {
+            return;
+        }
+
+        self.entries.retain(|entry| *entry != data);
+        self.entries.push_front(data);
+        self.entries.truncate(CLIPBOARD_HISTORY_MAX_LEN);
+    }
+}
+
+/// Returns the clipboard history, the newest first.
+pub fn clipboard_history() -> Vec<ClipboardData> {
+    CLIPBOARD_HISTORY.lock().entries.iter().cloned().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use noa_buffer::cursor::Cursor;
+
+    use super::*;
+
+    fn data(texts: &[&str]) -> ClipboardData {
+        ClipboardData {
+            texts: texts.iter().map(|s| s.to_string()).collect(),
+            is_block: false,
+        }
+    }
+
+    #[test]
+    fn test_clipboard_history() {
+        let mut history = ClipboardHistory::default();
+        history.push(data(&["a"]));
+        history.push(data(&["b", "c"]));
+        history.push(data(&[""]));
+        assert_eq!(history.entries, vec![data(&["b", "c"]), data(&["a"])]);
+
+        // Copying the same text again moves it to the front.
+        history.push(data(&["a"]));
+        assert_eq!(history.entries, vec![data(&["a"]), data(&["b", "c"])]);
+
+        for i in 0..CLIPBOARD_HISTORY_MAX_LEN + 10 {
+            history.push(data(&[&i.to_string()]));
+        }
+        assert_eq!(history.entries.len(), CLIPBOARD_HISTORY_MAX_LEN);
+        assert_eq!(
+            history.entries[0],
+            data(&[&(CLIPBOARD_HISTORY_MAX_LEN + 9).to_string()])
+        );
+    }
+
+    #[test]
+    fn test_copy_and_paste_with_multiple_cursors() {
+        let mut b = Buffer::from_text("ab\ncd\nef");
+        b.set_cursors_for_test(&[
+            Cursor::new_selection(0, 0, 0, 1),
+            Cursor::new_selection(1, 0, 1, 1),
+            Cursor::new_selection(2, 0, 2, 1),
+        ]);
+        let copied = ClipboardData::from_buffer(&b);
+        assert_eq!(copied, data(&["a", "c", "e"]));
+
+        // Each text is pasted at the cursor it was copied from.
+        b.set_cursors_for_test(&[Cursor::new(0, 2), Cursor::new(1, 2), Cursor::new(2, 2)]);
+        let strs: Vec<&str> = copied.texts.iter().map(|s| s.as_str()).collect();
+        b.insert_multiple(&strs);
+        assert_eq!(b.text(), "aba\ncdc\nefe");
+    }
+
+    #[test]
+    fn test_preview() {
+        assert_eq!(data(&["", "  \n  foo\nbar"]).preview(), "foo");
+        assert_eq!(data(&[""]).preview(), "");
+    }
 }
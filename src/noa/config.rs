@@ -61,6 +61,69 @@ struct ConfigFile {
     key_bindings: Vec<KeyBinding>,
     theme: HashMap<String, ThemeItem>,
     colors: HashMap<String, String>,
+    #[serde(default)]
+    settings: Settings,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+struct Settings {
+    /// Files larger than this (in bytes) are opened in the large-file mode.
+    large_file_threshold: Option<u64>,
+}
+
+/// Names of non-character keys.
+const KEY_NAMES: &[(&str, KeyCode)] = &[
+    ("enter", KeyCode::Enter),
+    ("tab", KeyCode::Tab),
+    ("backtab", KeyCode::BackTab),
+    ("backspace", KeyCode::Backspace),
+    ("delete", KeyCode::Delete),
+    ("up", KeyCode::Up),
+    ("down", KeyCode::Down),
+    ("left", KeyCode::Left),
+    ("right", KeyCode::Right),
+    ("esc", KeyCode::Esc),
+    ("home", KeyCode::Home),
+    ("end", KeyCode::End),
+    ("pageup", KeyCode::PageUp),
+    ("pagedown", KeyCode::PageDown),
+    ("F1", KeyCode::F(1)),
+    ("F2", KeyCode::F(2)),
+    ("F3", KeyCode::F(3)),
+    ("F4", KeyCode::F(4)),
+    ("F5", KeyCode::F(5)),
+    ("F6", KeyCode::F(6)),
+    ("F7", KeyCode::F(7)),
+    ("F8", KeyCode::F(8)),
+    ("F9", KeyCode::F(9)),
+    ("F10", KeyCode::F(10)),
+    ("F11", KeyCode::F(11)),
+    ("F12", KeyCode::F(12)),
+];
+
+/// Parses a key name like "enter", "F1", or "a".
+pub fn parse_key_code(name: &str) -> Option<KeyCode> {
+    if let Some((_, keycode)) = KEY_NAMES.iter().find(|(n, _)| *n == name) {
+        return Some(*keycode);
+    }
+
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(ch), None) => Some(KeyCode::Char(ch)),
+        _ => None,
+    }
+}
+
+/// The inverse of `parse_key_code`.
+pub fn key_code_name(keycode: KeyCode) -> Option<String> {
+    if let KeyCode::Char(ch) = keycode {
+        return Some(ch.to_string());
+    }
+
+    KEY_NAMES
+        .iter()
+        .find(|(_, code)| *code == keycode)
+        .map(|(name, _)| name.to_string())
 }
 
 fn parse_keybindings(
@@ -68,36 +131,10 @@ fn parse_keybindings(
     bindings: &[KeyBinding],
 ) {
     for binding in bindings {
-        let keycode = match binding.key.as_str() {
-            "enter" => KeyCode::Enter,
-            "tab" => KeyCode::Tab,
-            "backtab" => KeyCode::BackTab,
-            "backspace" => KeyCode::Backspace,
-            "delete" => KeyCode::Delete,
-            "up" => KeyCode::Up,
-            "down" => KeyCode::Down,
-            "left" => KeyCode::Left,
-            "right" => KeyCode::Right,
-            "esc" => KeyCode::Esc,
-            "home" => KeyCode::Home,
-            "end" => KeyCode::End,
-            "pageup" => KeyCode::PageUp,
-            "pagedown" => KeyCode::PageDown,
-            "F1" => KeyCode::F(1),
-            "F2" => KeyCode::F(2),
-            "F3" => KeyCode::F(3),
-            "F4" => KeyCode::F(4),
-            "F5" => KeyCode::F(5),
-            "F6" => KeyCode::F(6),
-            "F7" => KeyCode::F(7),
-            "F8" => KeyCode::F(8),
-            "F9" => KeyCode::F(9),
-            "F10" => KeyCode::F(10),
-            "F11" => KeyCode::F(11),
-            "F12" => KeyCode::F(12),
-            s if s.len() == 1 => KeyCode::Char(s.chars().next().unwrap()),
-            s => {
-                panic!("invalid key binding: key='{}'", s);
+        let keycode = match parse_key_code(&binding.key) {
+            Some(keycode) => keycode,
+            None => {
+                panic!("invalid key binding: key='{}'", binding.key);
             }
         };
 
@@ -271,6 +308,14 @@ pub fn theme_for(key: &str) -> Style {
     }
 }
 
+pub fn large_file_threshold() -> u64 {
+    USER_CONFIG_FILE
+        .settings
+        .large_file_threshold
+        .or(DEFAULT_CONFIG_FILE.settings.large_file_threshold)
+        .unwrap()
+}
+
 pub fn parse_config_files() {
     Lazy::force(&KEY_BINDINGS);
     Lazy::force(&THEME);
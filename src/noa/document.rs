@@ -1,16 +1,29 @@
 use std::{
-    fs::File,
-    io::ErrorKind,
+    fs::{create_dir_all, File},
+    io::{BufReader, BufWriter, ErrorKind},
     ops::{Deref, DerefMut},
     path::{Path, PathBuf},
     sync::atomic::{AtomicUsize, Ordering},
     time::SystemTime,
 };
 
-use anyhow::Result;
-use noa_buffer::{buffer::Buffer, cursor::Position, raw_buffer::RawBuffer, scroll::Scroll};
+use anyhow::{anyhow, Result};
+use noa_buffer::{
+    buffer::Buffer,
+    cursor::Position,
+    encoding::{is_binary, Encoding},
+    raw_buffer::RawBuffer,
+    scroll::Scroll,
+    undo_tree::UndoTree,
+};
+use noa_common::dirs::{noa_workdir, path_into_dotted_str};
+use noa_editorconfig::{EditorConfig, EndOfLine};
+use sha2::{Digest, Sha256};
 
-use crate::{notify_info, notify_warn};
+use crate::{
+    config::large_file_threshold, hex_buffer::HexBuffer, large_file::LargeFile, notify_info,
+    notify_warn,
+};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct DocumentId(usize);
@@ -34,10 +47,19 @@ pub struct Document {
     pub name: String,
     pub buffer: Buffer,
     pub saved_buffer: RawBuffer,
+    /// The line ending of the file. The buffer always uses LF internally.
+    saved_end_of_line: EndOfLine,
     last_saved_at: Option<SystemTime>,
     pub path: Option<PathBuf>,
     pub backup_path: Option<PathBuf>,
     pub scroll: Scroll,
+    /// The contents of a binary file. If it's set, the document is shown in
+    /// the hex view instead and `buffer` is empty.
+    pub hex: Option<HexBuffer>,
+    /// A file too large to be loaded into `buffer`. If it's set, the document
+    /// is shown in the large file view instead and `buffer` is empty: syntax
+    /// highlighting is not available and undo works per line.
+    pub large_file: Option<LargeFile>,
 }
 
 impl Document {
@@ -51,18 +73,46 @@ impl Document {
             kind: DocumentKind::Scratch,
             buffer,
             saved_buffer,
+            saved_end_of_line: EndOfLine::Lf,
             last_saved_at: None,
             path: None,
             backup_path: None,
             scroll: Scroll::zeroed(),
+            hex: None,
+            large_file: None,
         }
     }
 
+    /// A document whose contents are not in `buffer`.
+    fn placeholder(path: &Path, name: &str) -> Document {
+        let mut doc = Document::virtual_file(name, "");
+        doc.kind = DocumentKind::File {
+            path: path.to_owned(),
+        };
+        doc.path = Some(path.to_owned());
+        doc
+    }
+
     pub async fn open(path: &Path) -> Result<Document> {
-        let file = File::open(path)?;
-        let buffer = Buffer::from_reader(file)?;
-        let saved_buffer = buffer.raw_buffer().clone();
         let name = path.file_name().unwrap().to_string_lossy().to_string();
+        if std::fs::metadata(path)?.len() > large_file_threshold() {
+            let mut doc = Document::placeholder(path, &name);
+            doc.large_file = Some(LargeFile::open(path)?);
+            return Ok(doc);
+        }
+
+        let bytes = std::fs::read(path)?;
+        if is_binary(&bytes) {
+            let mut doc = Document::placeholder(path, &name);
+            doc.hex = Some(HexBuffer::new(bytes));
+            return Ok(doc);
+        }
+
+        let mut buffer = Buffer::from_reader(bytes.as_slice())?;
+        buffer.set_editorconfig(EditorConfig::resolve_or_guess(path));
+        restore_undo_history(&mut buffer, path);
+        let saved_buffer = buffer.raw_buffer().clone();
+        let saved_end_of_line = buffer.editorconfig().end_of_line;
         Ok(Document {
             id: DocumentId::alloc(),
             name,
@@ -71,10 +121,13 @@ impl Document {
             },
             buffer,
             saved_buffer,
+            saved_end_of_line,
             last_saved_at: None,
             path: Some(path.to_owned()),
             backup_path: None, // TODO:
             scroll: Scroll::zeroed(),
+            hex: None,
+            large_file: None,
         })
     }
 
@@ -85,6 +138,26 @@ impl Document {
         };
 
         trace!("saving into a file: {}", path.display());
+        if let Some(hex) = &mut self.hex {
+            // Write the exact bytes.
+            match std::fs::write(path, hex.bytes()) {
+                Ok(()) => {
+                    hex.mark_as_saved();
+                    notify_info!("written {} bytes", hex.len());
+                }
+                Err(err) => notify_warn!("failed to save: {}", err),
+            }
+            return;
+        }
+
+        if let Some(large_file) = &mut self.large_file {
+            match large_file.save() {
+                Ok(()) => notify_info!("written {} lines", large_file.num_lines()),
+                Err(err) => notify_warn!("failed to save: {}", err),
+            }
+            return;
+        }
+
         let with_sudo = match self.buffer.save_to_file(path) {
             Ok(()) => {
                 if let Some(backup_path) = &self.backup_path {
@@ -115,6 +188,12 @@ impl Document {
         };
 
         self.saved_buffer = self.buffer.raw_buffer().clone();
+        self.saved_end_of_line = self.buffer.editorconfig().end_of_line;
+
+        self.buffer.save_undo();
+        if let Err(err) = save_undo_history(&self.buffer, path) {
+            warn!("failed to save undo history: {}", err);
+        }
 
         // FIXME: By any chance, the file was modified by another process
         // between saving the file and updating the last saved time here.
@@ -137,10 +216,50 @@ impl Document {
     }
 
     pub fn is_dirty(&self) -> bool {
+        if let Some(hex) = &self.hex {
+            return hex.is_dirty();
+        }
+
+        if let Some(large_file) = &self.large_file {
+            return large_file.is_dirty();
+        }
+
         let a = self.buffer.raw_buffer();
         let b = &self.saved_buffer;
 
-        a.len_chars() != b.len_chars() && a != b
+        (a.len_chars() != b.len_chars() && a != b)
+            || self.buffer.editorconfig().end_of_line != self.saved_end_of_line
+    }
+
+    /// Reads the file again in `encoding`. It can be undone.
+    pub fn reopen_with_encoding(&mut self, encoding: &'static Encoding) -> Result<()> {
+        let path = self.path.as_ref().ok_or_else(|| anyhow!("not a file"))?;
+        if self.large_file.is_some() {
+            return Err(anyhow!("not supported in the large-file mode"));
+        }
+
+        let (raw_buffer, encoding) =
+            RawBuffer::from_reader_with_encoding(File::open(path)?, Some(encoding))?;
+
+        // A file misdetected as binary can be opened as a text.
+        if self.hex.take().is_some() {
+            self.buffer
+                .set_editorconfig(EditorConfig::resolve_or_guess(path));
+        }
+
+        self.buffer.set_raw_buffer(raw_buffer);
+        self.buffer.set_encoding(encoding);
+        self.buffer.clear_secondary_cursors();
+        self.buffer.move_main_cursor_to_pos(Position::new(0, 0));
+        self.saved_buffer = self.buffer.raw_buffer().clone();
+        Ok(())
+    }
+
+    /// Changes the line ending used when saving the file.
+    pub fn set_end_of_line(&mut self, eol: EndOfLine) {
+        let mut config = *self.buffer.editorconfig();
+        config.end_of_line = eol;
+        self.buffer.set_editorconfig(config);
     }
 
     pub fn scroll_down(&mut self, n: usize, screen_width: usize) {
@@ -182,6 +301,75 @@ impl Document {
     }
 }
 
+/// Returns the path to the file where the undo history of `path` is saved.
+fn undo_history_path(path: &Path) -> Result<PathBuf> {
+    let path = path.canonicalize()?;
+    let dir = noa_workdir(&std::env::current_dir()?).join("undo");
+    Ok(dir.join(path_into_dotted_str(&path)))
+}
+
+fn content_hash(buffer: &Buffer) -> String {
+    let mut hasher = Sha256::new();
+    for chunk in buffer.raw_buffer().rope().chunks() {
+        hasher.update(chunk.as_bytes());
+    }
+
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Saves the undo history along with the hash of the saved contents.
+fn save_undo_history(buffer: &Buffer, path: &Path) -> Result<()> {
+    let history_path = undo_history_path(path)?;
+    if let Some(dir) = history_path.parent() {
+        create_dir_all(dir)?;
+    }
+
+    let mut writer = BufWriter::new(File::create(history_path)?);
+    bincode::serialize_into(&mut writer, &content_hash(buffer))?;
+    bincode::serialize_into(&mut writer, buffer.undo_tree())?;
+    Ok(())
+}
+
+/// Restores the undo history if the file is not modified since the last save
+/// in noa. Otherwise, the history is discarded.
+fn restore_undo_history(buffer: &mut Buffer, path: &Path) {
+    let history_path = match undo_history_path(path) {
+        Ok(history_path) if history_path.exists() => history_path,
+        _ => return,
+    };
+
+    let result = File::open(&history_path)
+        .map_err(anyhow::Error::from)
+        .and_then(|file| {
+            let mut reader = BufReader::new(file);
+            let hash: String = bincode::deserialize_from(&mut reader)?;
+            if hash != content_hash(buffer) {
+                return Ok(None);
+            }
+
+            let undo_tree: UndoTree = bincode::deserialize_from(&mut reader)?;
+            Ok(Some(undo_tree))
+        });
+
+    match result {
+        Ok(Some(undo_tree)) => {
+            buffer.set_undo_tree(undo_tree);
+        }
+        Ok(None) => {
+            trace!("discarding stale undo history: {}", history_path.display());
+            let _ = std::fs::remove_file(&history_path);
+        }
+        Err(err) => {
+            warn!("failed to restore undo history: {}", err);
+            let _ = std::fs::remove_file(&history_path);
+        }
+    }
+}
+
 impl Deref for Document {
     type Target = Buffer;
 
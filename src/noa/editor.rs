@@ -1,14 +1,33 @@
 use std::collections::HashMap;
 
+use noa_buffer::{cursor::Range, find::FindOptions, raw_buffer::RawBuffer};
+
 use crate::{
     clipboard::{self, ClipboardProvider},
     document::{Document, DocumentId},
+    macros::Macros,
 };
 
+/// The state of cycling through the clipboard history by `paste_previous`.
+pub struct PasteCycle {
+    /// The index in the clipboard history to be pasted next.
+    pub next_index: usize,
+    /// The buffer contents right after the last paste. If they've changed,
+    /// the pasted text is no longer replaced.
+    pub buffer_after: RawBuffer,
+}
+
 pub struct Editor {
     current_doc: DocumentId,
     documents: HashMap<DocumentId, Document>,
     pub clipboard: Box<dyn ClipboardProvider>,
+    pub paste_cycle: Option<PasteCycle>,
+    /// The text being searched. Its matches are highlighted in the buffer.
+    pub find_query: String,
+    pub find_options: FindOptions,
+    /// Whether `find_query` is a regular expression.
+    pub find_regex: bool,
+    pub macros: Macros,
 }
 
 impl Editor {
@@ -22,6 +41,32 @@ impl Editor {
             documents,
             current_doc: scratch_id,
             clipboard: clipboard::build_provider(),
+            paste_cycle: None,
+            find_query: String::new(),
+            find_options: FindOptions::default(),
+            find_regex: false,
+            macros: Macros::new(),
+        }
+    }
+
+    /// Returns matches of `find_query` in the current document which start
+    /// in `range`. An invalid regex matches nothing.
+    pub fn find_matches(&self, range: Range) -> Vec<Range> {
+        if self.find_query.is_empty() {
+            return Vec::new();
+        }
+
+        let doc = self.current_document();
+        let in_range = |m: &Range| m.front() < range.back();
+        if self.find_regex {
+            match doc.find_regex_iter(&self.find_query, range.front(), self.find_options) {
+                Ok(iter) => iter.take_while(in_range).collect(),
+                Err(_) => Vec::new(),
+            }
+        } else {
+            doc.find_iter(&self.find_query, range.front(), self.find_options)
+                .take_while(in_range)
+                .collect()
         }
     }
 
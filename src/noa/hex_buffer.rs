@@ -0,0 +1,173 @@
+use anyhow::{anyhow, Result};
+
+pub const BYTES_PER_ROW: usize = 16;
+
+/// The contents of a binary file, edited byte by byte in the hex view.
+pub struct HexBuffer {
+    bytes: Vec<u8>,
+    saved_bytes: Vec<u8>,
+    /// The offset of the byte under the cursor.
+    pub cursor: usize,
+    /// Whether the cursor is on the lower 4 bits of the byte.
+    pub low_nibble: bool,
+    /// The first visible row.
+    pub top_row: usize,
+}
+
+impl HexBuffer {
+    pub fn new(bytes: Vec<u8>) -> HexBuffer {
+        HexBuffer {
+            saved_bytes: bytes.clone(),
+            bytes,
+            cursor: 0,
+            low_nibble: false,
+            top_row: 0,
+        }
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn num_rows(&self) -> usize {
+        self.bytes.len().div_ceil(BYTES_PER_ROW)
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.bytes != self.saved_bytes
+    }
+
+    pub fn mark_as_saved(&mut self) {
+        self.saved_bytes = self.bytes.clone();
+    }
+
+    /// Whether the byte at `offset` differs from the saved one.
+    pub fn is_modified(&self, offset: usize) -> bool {
+        self.bytes.get(offset) != self.saved_bytes.get(offset)
+    }
+
+    /// Moves the cursor by `diff` bytes.
+    pub fn move_cursor(&mut self, diff: isize) {
+        let last = self.bytes.len().saturating_sub(1) as isize;
+        self.cursor = (self.cursor as isize + diff).clamp(0, last) as usize;
+        self.low_nibble = false;
+    }
+
+    pub fn move_cursor_to(&mut self, offset: usize) {
+        self.cursor = offset.min(self.bytes.len().saturating_sub(1));
+        self.low_nibble = false;
+    }
+
+    /// Overwrites the nibble under the cursor with a hex digit and moves the
+    /// cursor to the next nibble.
+    pub fn overwrite_nibble(&mut self, digit: u8) {
+        debug_assert!(digit < 16);
+        let byte = match self.bytes.get_mut(self.cursor) {
+            Some(byte) => byte,
+            None => return,
+        };
+
+        if self.low_nibble {
+            *byte = (*byte & 0xf0) | digit;
+            if self.cursor + 1 < self.bytes.len() {
+                self.cursor += 1;
+                self.low_nibble = false;
+            }
+        } else {
+            *byte = (*byte & 0x0f) | (digit << 4);
+            self.low_nibble = true;
+        }
+    }
+
+    /// Reverts the byte under the cursor to the saved one.
+    pub fn revert_byte(&mut self) {
+        if let Some(saved) = self.saved_bytes.get(self.cursor) {
+            self.bytes[self.cursor] = *saved;
+        }
+    }
+
+    /// Scrolls so that the cursor is visible in `height` rows.
+    pub fn adjust_scroll(&mut self, height: usize) {
+        let cursor_row = self.cursor / BYTES_PER_ROW;
+        if cursor_row < self.top_row {
+            self.top_row = cursor_row;
+        } else if height > 0 && cursor_row >= self.top_row + height {
+            self.top_row = cursor_row + 1 - height;
+        }
+    }
+}
+
+/// Parses an offset in hexadecimal ("0x1f0" or "1f0h") or decimal.
+pub fn parse_offset(s: &str) -> Result<usize> {
+    let s = s.trim();
+    let result = if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_suffix('h')) {
+        usize::from_str_radix(hex, 16)
+    } else {
+        s.parse()
+    };
+
+    result.map_err(|_| anyhow!("invalid offset: \"{}\"", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overwrite_nibble() {
+        let mut buf = HexBuffer::new(vec![0x00, 0x11]);
+        buf.overwrite_nibble(0xa);
+        buf.overwrite_nibble(0xb);
+        assert_eq!(buf.bytes(), &[0xab, 0x11]);
+        assert_eq!((buf.cursor, buf.low_nibble), (1, false));
+        assert!(buf.is_dirty());
+        assert!(buf.is_modified(0));
+        assert!(!buf.is_modified(1));
+
+        // The cursor stays at the last byte.
+        buf.overwrite_nibble(0xc);
+        buf.overwrite_nibble(0xd);
+        buf.overwrite_nibble(0xe);
+        assert_eq!(buf.bytes(), &[0xab, 0xce]);
+        assert_eq!((buf.cursor, buf.low_nibble), (1, true));
+
+        buf.revert_byte();
+        assert_eq!(buf.bytes(), &[0xab, 0x11]);
+        buf.mark_as_saved();
+        assert!(!buf.is_dirty());
+    }
+
+    #[test]
+    fn test_move_cursor() {
+        let mut buf = HexBuffer::new(vec![0; 40]);
+        buf.move_cursor(-1);
+        assert_eq!(buf.cursor, 0);
+        buf.move_cursor(BYTES_PER_ROW as isize * 2);
+        assert_eq!(buf.cursor, 32);
+        buf.move_cursor(100);
+        assert_eq!(buf.cursor, 39);
+
+        buf.adjust_scroll(2);
+        assert_eq!(buf.top_row, 1);
+        buf.move_cursor_to(0);
+        buf.adjust_scroll(2);
+        assert_eq!(buf.top_row, 0);
+    }
+
+    #[test]
+    fn test_parse_offset() {
+        assert_eq!(parse_offset("16").unwrap(), 16);
+        assert_eq!(parse_offset("0x1f").unwrap(), 31);
+        assert_eq!(parse_offset(" 1fh ").unwrap(), 31);
+        assert!(parse_offset("0xzz").is_err());
+        assert!(parse_offset("").is_err());
+    }
+}
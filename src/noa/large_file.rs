@@ -0,0 +1,496 @@
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    fs::File,
+    io::{BufWriter, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use memchr::{memchr, memchr_iter, memmem};
+use memmap2::Mmap;
+use noa_buffer::{cursor::Position, encoding::is_binary};
+
+/// The interval of lines whose offsets are recorded in the line index.
+const LINE_INDEX_INTERVAL: usize = 1024;
+/// Lines longer than this are truncated in the view and can't be edited.
+const MAX_LINE_LEN: usize = 16 * 1024;
+
+/// Builds the line index: the byte offsets of every `LINE_INDEX_INTERVAL`-th
+/// line. Also returns the number of lines.
+fn build_line_index(bytes: &[u8]) -> (Vec<usize>, usize) {
+    let mut checkpoints = vec![0];
+    let mut num_lines = 1;
+    for newline in memchr_iter(b'\n', bytes) {
+        if num_lines % LINE_INDEX_INTERVAL == 0 {
+            checkpoints.push(newline + 1);
+        }
+        num_lines += 1;
+    }
+
+    (checkpoints, num_lines)
+}
+
+/// Returns the byte range of the line `y` except the newline characters.
+fn line_range(bytes: &[u8], checkpoints: &[usize], y: usize) -> Range<usize> {
+    let mut start = checkpoints[y / LINE_INDEX_INTERVAL];
+    for _ in 0..(y % LINE_INDEX_INTERVAL) {
+        start += memchr(b'\n', &bytes[start..]).unwrap() + 1;
+    }
+
+    let mut end = memchr(b'\n', &bytes[start..])
+        .map(|i| start + i)
+        .unwrap_or(bytes.len());
+    if end > start && bytes[end - 1] == b'\r' {
+        end -= 1;
+    }
+
+    start..end
+}
+
+/// Returns the line which contains the byte at `offset`.
+fn line_at_offset(bytes: &[u8], checkpoints: &[usize], offset: usize) -> usize {
+    let index = checkpoints.partition_point(|start| *start <= offset) - 1;
+    index * LINE_INDEX_INTERVAL + memchr_iter(b'\n', &bytes[checkpoints[index]..offset]).count()
+}
+
+/// The state of a line before an edit, to undo or redo it.
+struct LineState {
+    y: usize,
+    /// `None` if the line was not edited.
+    text: Option<String>,
+    cursor: Position,
+}
+
+/// A file too large to be loaded into a buffer. It's memory-mapped and
+/// only the visible lines are read.
+///
+/// Edits are kept per line and merged with the file on save. Since lines
+/// are indexed by their numbers, lines can't be split or joined. Lines not
+/// in UTF-8 can't be edited since they're shown lossily.
+///
+/// Undo works per line: it restores the edited line instead of comparing
+/// the whole buffer.
+pub struct LargeFile {
+    path: PathBuf,
+    mmap: Mmap,
+    checkpoints: Vec<usize>,
+    num_lines: usize,
+    /// `true` if the file looks like a binary. It can't be edited.
+    read_only: bool,
+    /// Edited lines.
+    edits: BTreeMap<usize, String>,
+    undo_stack: Vec<LineState>,
+    redo_stack: Vec<LineState>,
+    /// `x` is in characters.
+    pub cursor: Position,
+    pub top_line: usize,
+    pub left_column: usize,
+}
+
+impl LargeFile {
+    pub fn open(path: &Path) -> Result<LargeFile> {
+        let file = File::open(path)?;
+        // SAFETY: We assume the file is not modified by others while it's
+        // opened. Otherwise, we might see inconsistent contents.
+        let mmap = unsafe { Mmap::map(&file)? };
+        let (checkpoints, num_lines) = build_line_index(&mmap);
+        let read_only = is_binary(&mmap);
+        Ok(LargeFile {
+            path: path.to_owned(),
+            mmap,
+            checkpoints,
+            num_lines,
+            read_only,
+            edits: BTreeMap::new(),
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            cursor: Position::new(0, 0),
+            top_line: 0,
+            left_column: 0,
+        })
+    }
+
+    pub fn num_lines(&self) -> usize {
+        self.num_lines
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        !self.edits.is_empty()
+    }
+
+    fn line_range(&self, y: usize) -> Range<usize> {
+        line_range(&self.mmap, &self.checkpoints, y)
+    }
+
+    /// Returns the line `y`. Long lines are truncated.
+    pub fn line(&self, y: usize) -> Cow<'_, str> {
+        if let Some(text) = self.edits.get(&y) {
+            return Cow::Borrowed(text);
+        }
+
+        let range = self.line_range(y);
+        let end = range.end.min(range.start + MAX_LINE_LEN);
+        String::from_utf8_lossy(&self.mmap[range.start..end])
+    }
+
+    pub fn line_len(&self, y: usize) -> usize {
+        self.line(y).chars().count()
+    }
+
+    pub fn move_cursor_to(&mut self, y: usize, x: usize) {
+        let y = y.min(self.num_lines - 1);
+        self.cursor = Position::new(y, x.min(self.line_len(y)));
+    }
+
+    /// Edits the line under the cursor. `f` returns the new cursor column.
+    fn edit_line<F>(&mut self, f: F) -> Result<()>
+    where
+        F: FnOnce(&mut Vec<char>, usize) -> Result<usize>,
+    {
+        if self.read_only {
+            return Err(anyhow!("can't edit a binary file"));
+        }
+
+        let y = self.cursor.y;
+        if !self.edits.contains_key(&y) {
+            let range = self.line_range(y);
+            if range.len() > MAX_LINE_LEN {
+                return Err(anyhow!("too long line to edit in large-file mode"));
+            }
+
+            // Saving a lossily decoded line would corrupt it.
+            if std::str::from_utf8(&self.mmap[range]).is_err() {
+                return Err(anyhow!("can't edit a non-UTF-8 line in large-file mode"));
+            }
+        }
+
+        let mut chars: Vec<char> = self.line(y).chars().collect();
+        let x = self.cursor.x.min(chars.len());
+        let x = f(&mut chars, x)?;
+        let prev_state = self.line_state(y);
+        self.undo_stack.push(prev_state);
+        self.redo_stack.clear();
+        self.edits.insert(y, chars.into_iter().collect());
+        self.cursor.x = x;
+        Ok(())
+    }
+
+    fn line_state(&self, y: usize) -> LineState {
+        LineState {
+            y,
+            text: self.edits.get(&y).cloned(),
+            cursor: self.cursor,
+        }
+    }
+
+    /// Restores `state` and returns the state replaced by it.
+    fn restore_line_state(&mut self, state: LineState) -> LineState {
+        let current = self.line_state(state.y);
+        match state.text {
+            Some(text) => self.edits.insert(state.y, text),
+            None => self.edits.remove(&state.y),
+        };
+        self.cursor = state.cursor;
+        current
+    }
+
+    /// Returns `false` if there's nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        match self.undo_stack.pop() {
+            Some(state) => {
+                let current = self.restore_line_state(state);
+                self.redo_stack.push(current);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns `false` if there's nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        match self.redo_stack.pop() {
+            Some(state) => {
+                let current = self.restore_line_state(state);
+                self.undo_stack.push(current);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn insert_char(&mut self, ch: char) -> Result<()> {
+        if ch == '\n' {
+            return Err(anyhow!("can't split lines in large-file mode"));
+        }
+
+        self.edit_line(|chars, x| {
+            chars.insert(x, ch);
+            Ok(x + 1)
+        })
+    }
+
+    pub fn backspace(&mut self) -> Result<()> {
+        self.edit_line(|chars, x| {
+            if x == 0 {
+                return Err(anyhow!("can't join lines in large-file mode"));
+            }
+
+            chars.remove(x - 1);
+            Ok(x - 1)
+        })
+    }
+
+    pub fn delete(&mut self) -> Result<()> {
+        self.edit_line(|chars, x| {
+            if x == chars.len() {
+                return Err(anyhow!("can't join lines in large-file mode"));
+            }
+
+            chars.remove(x);
+            Ok(x)
+        })
+    }
+
+    /// Finds the first occurrence of `query` after `pos`, or from the
+    /// beginning if it's `None`.
+    pub fn find(&self, query: &str, pos: Option<Position>) -> Option<Position> {
+        if query.is_empty() {
+            return None;
+        }
+
+        // Byte offset of the character after `pos`.
+        let start = match pos {
+            // Offsets in an edited line don't correspond to the file. Its
+            // matches are found in `edits` below.
+            Some(pos) if self.edits.contains_key(&pos.y) => {
+                if pos.y + 1 < self.num_lines {
+                    self.line_range(pos.y + 1).start
+                } else {
+                    self.mmap.len()
+                }
+            }
+            Some(pos) => {
+                let line = self.line(pos.y);
+                let x_offset = line
+                    .char_indices()
+                    .nth(pos.x + 1)
+                    .map(|(i, _)| i)
+                    .unwrap_or(line.len());
+                self.line_range(pos.y).start + x_offset
+            }
+            None => 0,
+        };
+        let start = start.min(self.mmap.len());
+
+        // Matches in the file. Ones in edited lines are outdated.
+        let in_file = memmem::find_iter(&self.mmap[start..], query.as_bytes())
+            .map(|i| start + i)
+            .map(|offset| {
+                let y = line_at_offset(&self.mmap, &self.checkpoints, offset);
+                (y, offset)
+            })
+            .find(|(y, _)| !self.edits.contains_key(y))
+            .map(|(y, offset)| {
+                let line_start = self.line_range(y).start;
+                let x = String::from_utf8_lossy(&self.mmap[line_start..offset])
+                    .chars()
+                    .count();
+                Position::new(y, x)
+            });
+
+        // Matches in edited lines.
+        let first_y = pos.map(|pos| pos.y).unwrap_or(0);
+        let in_edits = self.edits.range(first_y..).find_map(|(y, text)| {
+            let min_x = match pos {
+                Some(pos) if pos.y == *y => pos.x + 1,
+                _ => 0,
+            };
+
+            text.match_indices(query)
+                .map(|(i, _)| text[..i].chars().count())
+                .find(|x| *x >= min_x)
+                .map(|x| Position::new(*y, x))
+        });
+
+        match (in_file, in_edits) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    /// Writes the file with the edits applied.
+    pub fn save(&mut self) -> Result<()> {
+        let tmp_path = self.path.with_file_name(format!(
+            ".{}.noa-tmp",
+            self.path.file_name().unwrap().to_string_lossy()
+        ));
+
+        let mut writer = BufWriter::new(File::create(&tmp_path)?);
+        let mut written = 0;
+        for (y, text) in &self.edits {
+            let range = self.line_range(*y);
+            writer.write_all(&self.mmap[written..range.start])?;
+            writer.write_all(text.as_bytes())?;
+            written = range.end;
+        }
+        writer.write_all(&self.mmap[written..])?;
+        writer.flush()?;
+        drop(writer);
+
+        std::fs::rename(&tmp_path, &self.path)?;
+
+        let cursor = self.cursor;
+        let top_line = self.top_line;
+        *self = LargeFile::open(&self.path)?;
+        self.cursor = cursor;
+        self.top_line = top_line;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_index() {
+        let mut text = String::new();
+        for i in 0..(LINE_INDEX_INTERVAL * 2 + 10) {
+            text.push_str(&format!("line{}\r\n", i));
+        }
+        let bytes = text.as_bytes();
+
+        let (checkpoints, num_lines) = build_line_index(bytes);
+        assert_eq!(checkpoints.len(), 3);
+        assert_eq!(num_lines, LINE_INDEX_INTERVAL * 2 + 11);
+
+        for y in [0, 1, LINE_INDEX_INTERVAL - 1, LINE_INDEX_INTERVAL, 2050] {
+            let range = line_range(bytes, &checkpoints, y);
+            assert_eq!(&text[range.clone()], format!("line{}", y));
+            assert_eq!(line_at_offset(bytes, &checkpoints, range.start), y);
+            assert_eq!(line_at_offset(bytes, &checkpoints, range.end), y);
+        }
+
+        // The last empty line.
+        assert_eq!(
+            line_range(bytes, &checkpoints, num_lines - 1),
+            bytes.len()..bytes.len()
+        );
+    }
+
+    #[test]
+    fn test_edit_and_save() {
+        let path = std::env::temp_dir().join(format!("noa-large-file-test-{}", std::process::id()));
+        std::fs::write(&path, "abc\nxyz\nabc\n").unwrap();
+
+        let mut file = LargeFile::open(&path).unwrap();
+        assert_eq!(file.num_lines(), 4);
+        assert_eq!(file.find("abc", None), Some(Position::new(0, 0)));
+        assert_eq!(
+            file.find("abc", Some(Position::new(0, 0))),
+            Some(Position::new(2, 0))
+        );
+
+        file.move_cursor_to(1, 1);
+        file.insert_char('!').unwrap();
+        file.delete().unwrap();
+        assert!(file.insert_char('\n').is_err());
+        assert_eq!(file.line(1), "x!z");
+        assert_eq!(
+            file.find("!", Some(Position::new(0, 0))),
+            Some(Position::new(1, 1))
+        );
+
+        file.move_cursor_to(2, 0);
+        assert!(file.backspace().is_err());
+
+        // Undo both edits and redo them.
+        assert!(file.undo());
+        assert_eq!(file.line(1), "x!yz");
+        assert_eq!(file.cursor, Position::new(1, 2));
+        assert!(file.undo());
+        assert_eq!(file.line(1), "xyz");
+        assert!(!file.is_dirty());
+        assert!(!file.undo());
+        assert!(file.redo());
+        assert_eq!(file.line(1), "x!yz");
+        assert!(file.redo());
+        assert!(!file.redo());
+
+        file.save().unwrap();
+        assert!(!file.is_dirty());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "abc\nx!z\nabc\n");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_find_after_lengthened_lines() {
+        let path =
+            std::env::temp_dir().join(format!("noa-large-file-find-test-{}", std::process::id()));
+        std::fs::write(&path, "abc\nx\nabc").unwrap();
+
+        let mut file = LargeFile::open(&path).unwrap();
+        file.move_cursor_to(1, 1);
+        for _ in 0..8 {
+            file.insert_char('x').unwrap();
+        }
+        assert_eq!(
+            file.find("abc", Some(Position::new(1, 8))),
+            Some(Position::new(2, 0))
+        );
+
+        // The last line.
+        file.move_cursor_to(2, 3);
+        for _ in 0..8 {
+            file.insert_char('x').unwrap();
+        }
+        assert_eq!(file.find("abc", Some(Position::new(2, 10))), None);
+        assert_eq!(
+            file.find("x", Some(Position::new(2, 9))),
+            Some(Position::new(2, 10))
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_non_utf8_lines() {
+        let path = std::env::temp_dir().join(format!(
+            "noa-large-file-non-utf8-test-{}",
+            std::process::id()
+        ));
+        std::fs::write(&path, b"abc\nx\xffz\n").unwrap();
+
+        let mut file = LargeFile::open(&path).unwrap();
+        assert!(!file.is_read_only());
+        file.move_cursor_to(1, 1);
+        assert!(file.insert_char('!').is_err());
+        assert!(file.delete().is_err());
+        assert!(!file.is_dirty());
+
+        file.move_cursor_to(0, 0);
+        file.insert_char('!').unwrap();
+        file.save().unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"!abc\nx\xffz\n");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_binary_is_read_only() {
+        let path =
+            std::env::temp_dir().join(format!("noa-large-file-binary-test-{}", std::process::id()));
+        std::fs::write(&path, b"\x7fELF\x02\x01\x01\x00\x00\x00\nabc").unwrap();
+
+        let mut file = LargeFile::open(&path).unwrap();
+        assert!(file.is_read_only());
+        file.move_cursor_to(1, 0);
+        assert!(file.insert_char('!').is_err());
+        assert!(!file.is_dirty());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
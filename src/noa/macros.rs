@@ -0,0 +1,366 @@
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use anyhow::Result;
+use noa_buffer::cursor::Range;
+use noa_common::dirs::noa_dir;
+use noa_compositor::{
+    compositor::Compositor,
+    surface::KeyEvent,
+    terminal::{Event, InputEvent, KeyCode, KeyModifiers},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    actions::execute_action_or_notify,
+    config::{key_code_name, parse_key_code},
+    editor::Editor,
+    notify_warn,
+};
+
+pub const DEFAULT_REGISTER: &str = "default";
+
+/// An input recorded in a macro.
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "value", rename_all = "snake_case")]
+pub enum MacroStep {
+    /// A key event handled by the buffer view or a prompt, e.g. "ctrl+a".
+    Key(String),
+    /// A text pasted at once.
+    Text(String),
+    /// An action name.
+    Action(String),
+}
+
+impl MacroStep {
+    /// Returns `None` if the key can't be written into the macros file.
+    pub fn key(code: KeyCode, modifiers: KeyModifiers) -> Option<MacroStep> {
+        let mut s = String::new();
+        if modifiers.contains(KeyModifiers::CONTROL) {
+            s.push_str("ctrl+");
+        }
+        if modifiers.contains(KeyModifiers::ALT) {
+            s.push_str("alt+");
+        }
+        if modifiers.contains(KeyModifiers::SHIFT) {
+            s.push_str("shift+");
+        }
+
+        s.push_str(&key_code_name(code)?);
+        Some(MacroStep::Key(s))
+    }
+}
+
+/// Parses a key recorded by `MacroStep::key`.
+fn parse_key(s: &str) -> Option<(KeyCode, KeyModifiers)> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut rest = s;
+    for (prefix, modifier) in [
+        ("ctrl+", KeyModifiers::CONTROL),
+        ("alt+", KeyModifiers::ALT),
+        ("shift+", KeyModifiers::SHIFT),
+    ] {
+        if let Some(stripped) = rest.strip_prefix(prefix) {
+            // "ctrl++" is Ctrl and '+'.
+            if !stripped.is_empty() {
+                modifiers |= modifier;
+                rest = stripped;
+            }
+        }
+    }
+
+    parse_key_code(rest).map(|code| (code, modifiers))
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct MacroReplay {
+    pub register: String,
+    pub times: usize,
+    /// Replays the macro at each cursor independently.
+    pub at_every_cursor: bool,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct MacrosFile {
+    #[serde(default)]
+    registers: HashMap<String, Vec<MacroStep>>,
+}
+
+/// Recorded keyboard macros.
+pub struct Macros {
+    registers: HashMap<String, Vec<MacroStep>>,
+    /// The register name and the steps being recorded.
+    recording: Option<(String, Vec<MacroStep>)>,
+    replaying: bool,
+    /// The register recorded or replayed last.
+    last_register: String,
+    /// A replay requested by an action. It's done by the main loop since
+    /// the buffer view can't receive key events while handling one.
+    pending_replay: Option<MacroReplay>,
+}
+
+impl Macros {
+    pub fn new() -> Macros {
+        Macros {
+            registers: HashMap::new(),
+            recording: None,
+            replaying: false,
+            last_register: DEFAULT_REGISTER.to_owned(),
+            pending_replay: None,
+        }
+    }
+
+    /// Loads macros saved in the previous sessions.
+    pub fn load() -> Macros {
+        let mut macros = Macros::new();
+        let path = macros_file_path();
+        if !path.exists() {
+            return macros;
+        }
+
+        match fs::read_to_string(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|text| Ok(toml::from_str::<MacrosFile>(&text)?))
+        {
+            Ok(file) => macros.registers = file.registers,
+            Err(err) => warn!("failed to load {}: {}", path.display(), err),
+        }
+
+        macros
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let file = MacrosFile {
+            registers: self.registers.clone(),
+        };
+        fs::write(macros_file_path(), toml::to_string(&file)?)?;
+        Ok(())
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    pub fn recording_register(&self) -> Option<&str> {
+        self.recording.as_ref().map(|(name, _)| name.as_str())
+    }
+
+    pub fn last_register(&self) -> &str {
+        &self.last_register
+    }
+
+    pub fn start_recording(&mut self, register: &str) {
+        self.recording = Some((register.to_owned(), Vec::new()));
+        self.last_register = register.to_owned();
+    }
+
+    /// Stops recording and saves the macro into its register. Returns the
+    /// register name and the number of recorded steps.
+    pub fn stop_recording(&mut self) -> Option<(String, usize)> {
+        let (register, steps) = self.recording.take()?;
+        let num_steps = steps.len();
+        self.registers.insert(register.clone(), steps);
+        Some((register, num_steps))
+    }
+
+    pub fn record(&mut self, step: MacroStep) {
+        if self.replaying {
+            return;
+        }
+
+        if let Some((_, steps)) = self.recording.as_mut() {
+            steps.push(step);
+        }
+    }
+
+    /// Records a key event. Keys which can't be written into the macros
+    /// file are ignored.
+    pub fn record_key(&mut self, code: KeyCode, modifiers: KeyModifiers) {
+        if let Some(step) = MacroStep::key(code, modifiers) {
+            self.record(step);
+        }
+    }
+
+    pub fn get(&self, register: &str) -> Option<&[MacroStep]> {
+        self.registers.get(register).map(|steps| steps.as_slice())
+    }
+
+    /// Returns the register names in alphabetical order.
+    pub fn register_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.registers.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    pub fn request_replay(&mut self, replay: MacroReplay) {
+        self.last_register = replay.register.clone();
+        self.pending_replay = Some(replay);
+    }
+
+    pub fn take_replay_request(&mut self) -> Option<MacroReplay> {
+        self.pending_replay.take()
+    }
+}
+
+impl Default for Macros {
+    fn default() -> Macros {
+        Macros::new()
+    }
+}
+
+fn macros_file_path() -> PathBuf {
+    noa_dir().join("macros.toml")
+}
+
+fn replay_steps(editor: &mut Editor, compositor: &mut Compositor<Editor>, steps: &[MacroStep]) {
+    for step in steps {
+        match step {
+            MacroStep::Key(key) => match parse_key(key) {
+                Some((code, modifiers)) => {
+                    let ev = Event::Input(InputEvent::Key(KeyEvent::new(code, modifiers)));
+                    compositor.handle_event(editor, ev);
+                }
+                None => warn!("invalid key in a macro: {}", key),
+            },
+            MacroStep::Text(text) => {
+                let ev = Event::Input(InputEvent::KeyBatch(text.clone()));
+                compositor.handle_event(editor, ev);
+            }
+            MacroStep::Action(action) => {
+                execute_action_or_notify(editor, compositor, action);
+            }
+        }
+    }
+}
+
+/// Replays a macro. Changes made by a replay are undone at once.
+pub fn replay_macro(editor: &mut Editor, compositor: &mut Compositor<Editor>, replay: MacroReplay) {
+    let steps = match editor.macros.get(&replay.register) {
+        Some(steps) => steps.to_vec(),
+        None => {
+            notify_warn!("no macro in \"{}\"", replay.register);
+            return;
+        }
+    };
+
+    editor.macros.replaying = true;
+    editor.current_document_mut().save_undo();
+
+    if replay.at_every_cursor {
+        // Start from the bottom so that edits don't move the cursors not
+        // yet visited.
+        let mut cursors: Vec<Range> = editor
+            .current_document()
+            .cursors()
+            .iter()
+            .map(|c| c.selection())
+            .collect();
+        cursors.sort_by_key(|r| r.front());
+
+        let mut results: Vec<Range> = Vec::new();
+        for selection in cursors.into_iter().rev() {
+            let doc = editor.current_document_mut();
+            let prev_num_lines = doc.num_lines();
+            doc.clear_secondary_cursors();
+            doc.select_main_cursor_range(selection);
+
+            for _ in 0..replay.times {
+                replay_steps(editor, compositor, &steps);
+            }
+
+            // Lines inserted or removed above shift the previous results.
+            let doc = editor.current_document_mut();
+            let line_diff = doc.num_lines() as isize - prev_num_lines as isize;
+            let shift = |y: usize| (y as isize + line_diff).max(0) as usize;
+            for r in &mut results {
+                r.front_mut().y = shift(r.front().y);
+                r.back_mut().y = shift(r.back().y);
+            }
+            results.push(doc.main_cursor().selection());
+        }
+
+        let doc = editor.current_document_mut();
+        doc.clear_secondary_cursors();
+        if let Some((main, rest)) = results.split_last() {
+            let main = doc.clamp_range(*main);
+            doc.select_main_cursor_range(main);
+            for r in rest {
+                let r = doc.clamp_range(*r);
+                doc.add_cursor(r);
+            }
+        }
+    } else {
+        for _ in 0..replay.times {
+            replay_steps(editor, compositor, &steps);
+        }
+    }
+
+    editor.current_document_mut().save_undo();
+    editor.macros.replaying = false;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_format() {
+        for (code, modifiers) in [
+            (KeyCode::Char('a'), KeyModifiers::NONE),
+            (KeyCode::Char('+'), KeyModifiers::CONTROL),
+            (KeyCode::Enter, KeyModifiers::ALT | KeyModifiers::SHIFT),
+            (KeyCode::F(5), KeyModifiers::CONTROL | KeyModifiers::ALT),
+        ] {
+            let step = MacroStep::key(code, modifiers).unwrap();
+            match step {
+                MacroStep::Key(key) => assert_eq!(parse_key(&key), Some((code, modifiers))),
+                _ => unreachable!(),
+            }
+        }
+
+        assert_eq!(
+            MacroStep::key(KeyCode::Up, KeyModifiers::CONTROL | KeyModifiers::SHIFT),
+            Some(MacroStep::Key("ctrl+shift+up".to_owned()))
+        );
+        assert_eq!(parse_key("ctrl+nosuchkey"), None);
+    }
+
+    #[test]
+    fn test_record_key() {
+        let mut macros = Macros::new();
+        macros.record_key(KeyCode::Char('x'), KeyModifiers::NONE);
+        macros.start_recording("a");
+        // Keys typed into a prompt followed by Enter.
+        macros.record(MacroStep::Action("goto_line".to_owned()));
+        macros.record_key(KeyCode::Char('3'), KeyModifiers::NONE);
+        macros.record_key(KeyCode::Enter, KeyModifiers::NONE);
+        assert_eq!(macros.stop_recording(), Some(("a".to_owned(), 3)));
+        assert_eq!(
+            macros.get("a").unwrap(),
+            &[
+                MacroStep::Action("goto_line".to_owned()),
+                MacroStep::key(KeyCode::Char('3'), KeyModifiers::NONE).unwrap(),
+                MacroStep::key(KeyCode::Enter, KeyModifiers::NONE).unwrap(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_macros_file() {
+        let mut registers = HashMap::new();
+        registers.insert(
+            "default".to_owned(),
+            vec![
+                MacroStep::Key("ctrl+a".to_owned()),
+                MacroStep::Text("hello\n".to_owned()),
+                MacroStep::Action("move_to_end_of_line".to_owned()),
+            ],
+        );
+
+        let text = toml::to_string(&MacrosFile {
+            registers: registers.clone(),
+        })
+        .unwrap();
+        let file: MacrosFile = toml::from_str(&text).unwrap();
+        assert_eq!(file.registers, registers);
+    }
+}
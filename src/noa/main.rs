@@ -19,13 +19,19 @@ use tokio::{
     sync::mpsc,
     time::{self, Instant},
 };
-use views::{buffer_view::BufferView, metaline_view::MetaLine};
+use views::{
+    buffer_view::BufferView, hex_view::HexView, large_file_view::LargeFileView,
+    metaline_view::MetaLine, selector_view::SelectorView,
+};
 
 mod actions;
 mod clipboard;
 mod config;
 mod document;
 mod editor;
+mod hex_buffer;
+mod large_file;
+mod macros;
 mod notification;
 mod views;
 
@@ -41,7 +47,10 @@ async fn mainloop(mut editor: Editor) {
     let mut compositor = Compositor::new();
     let (mainloop_tx, mut mainloop_rx) = mpsc::unbounded_channel();
     compositor.add_frontmost_layer(Box::new(BufferView::new(mainloop_tx.clone())));
+    compositor.add_frontmost_layer(Box::new(HexView::new(mainloop_tx.clone())));
+    compositor.add_frontmost_layer(Box::new(LargeFileView::new(mainloop_tx.clone())));
     compositor.add_frontmost_layer(Box::new(MetaLine::new()));
+    compositor.add_frontmost_layer(Box::new(SelectorView::new()));
 
     let undo_timeout = time::sleep(FOREVER);
     tokio::pin!(undo_timeout);
@@ -85,6 +94,9 @@ async fn mainloop(mut editor: Editor) {
                         let prev_buffer = editor.current_document().raw_buffer().clone();
 
                         compositor.handle_event(&mut editor, ev);
+                        if let Some(replay) = editor.macros.take_replay_request() {
+                            macros::replay_macro(&mut editor, &mut compositor, replay);
+                        }
 
                         let doc = editor.current_document();
                         if *doc.raw_buffer() != prev_buffer {
@@ -130,6 +142,7 @@ async fn main() {
     // warm_up_search_cache();
 
     let mut editor = editor::Editor::new();
+    editor.macros = macros::Macros::load();
 
     for file in args.files {
         let doc = document::Document::open(&file)
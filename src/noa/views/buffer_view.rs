@@ -1,8 +1,9 @@
 use std::cmp::{max, min};
 
 use noa_buffer::{
-    cursor::Position,
+    cursor::{Position, Range},
     display_width::DisplayWidth,
+    extras::block_selection::{BlockSelection, DisplayPosition},
     paragraph_iter::Paragraph,
     reflow_iter::{PrintableGrapheme, ReflowItem},
 };
@@ -10,14 +11,16 @@ use noa_compositor::{
     canvas::{CanvasViewMut, Grapheme},
     compositor::Compositor,
     surface::{HandledEvent, KeyEvent, Layout, RectSize, Surface},
-    terminal::{KeyCode, KeyModifiers},
+    terminal::{KeyCode, KeyModifiers, MouseButton, MouseEventKind},
 };
 use tokio::sync::mpsc::UnboundedSender;
 
 use crate::{
     actions::execute_action_or_notify,
-    config::{get_keybinding_for, KeyBindingScope},
+    config::{get_keybinding_for, theme_for, KeyBindingScope},
+    document::Document,
     editor::Editor,
+    macros::MacroStep,
     MainloopCommand,
 };
 
@@ -30,6 +33,10 @@ pub struct BufferView {
     last_visible_pos: Position,
     cursor_screen_pos: Option<(usize, usize)>,
     softwrap: bool,
+    /// The buffer positions rendered in each row: `(canvas_x, pos)`.
+    rendered_positions: Vec<Vec<(usize, Position)>>,
+    /// The start of the block selection by mouse.
+    block_anchor: Option<DisplayPosition>,
 }
 
 impl BufferView {
@@ -43,8 +50,42 @@ impl BufferView {
             last_visible_pos: Position::new(0, 0),
             cursor_screen_pos: None,
             softwrap: true,
+            rendered_positions: Vec::new(),
+            block_anchor: None,
         }
     }
+
+    pub fn adjust_scroll(&self, doc: &mut Document) {
+        doc.adjust_scroll(
+            self.virtual_buffer_width,
+            self.buffer_width,
+            self.buffer_height,
+            self.first_visible_pos,
+            self.last_visible_pos,
+        );
+    }
+
+    /// Returns the buffer line and the display column at the given point of
+    /// the surface. The column can be beyond the end of line.
+    fn display_position_at(
+        &self,
+        editor: &Editor,
+        surface_y: usize,
+        surface_x: usize,
+    ) -> Option<DisplayPosition> {
+        let (canvas_x, pos) = self
+            .rendered_positions
+            .get(surface_y)?
+            .iter()
+            .rev()
+            .find(|(canvas_x, _)| *canvas_x <= surface_x)?;
+
+        let doc = editor.current_document();
+        Some(DisplayPosition {
+            y: pos.y,
+            column: doc.display_column(*pos) + (surface_x - canvas_x),
+        })
+    }
 }
 
 impl Surface for BufferView {
@@ -98,6 +139,8 @@ impl Surface for BufferView {
 
         let _show_completion = false;
         let mut adjust_scroll = true;
+        // Actions are recorded by their names instead.
+        let mut record_key = true;
         // TODO: Move into defaults.toml
         match (key.code, key.modifiers) {
             (KeyCode::Char('q'), CTRL) => {
@@ -105,6 +148,7 @@ impl Surface for BufferView {
             }
             (KeyCode::Esc, NONE) => {
                 doc.clear_secondary_cursors();
+                editor.find_query.clear();
             }
             (KeyCode::Up, NONE) => {
                 doc.move_cursors_up(self.virtual_buffer_width);
@@ -134,13 +178,13 @@ impl Surface for BufferView {
                 doc.smart_insert_char(ch);
             }
             (KeyCode::Enter, NONE) => {
-                doc.smart_insert_char('\n');
+                doc.insert_newline_and_indent();
             }
             (KeyCode::Char(ch), SHIFT) => {
                 doc.smart_insert_char(ch.to_ascii_uppercase());
             }
             (KeyCode::Backspace, NONE) => {
-                doc.backspace();
+                doc.smart_backspace();
             }
             (KeyCode::Delete, NONE) => {
                 doc.delete();
@@ -169,6 +213,7 @@ impl Surface for BufferView {
                 adjust_scroll = false;
             }
             _ => {
+                record_key = false;
                 if let Some(binding) =
                     get_keybinding_for(KeyBindingScope::Buffer, key.code, key.modifiers)
                 {
@@ -177,35 +222,64 @@ impl Surface for BufferView {
             }
         }
 
-        let doc = editor.current_document_mut();
+        if record_key {
+            editor.macros.record_key(key.code, key.modifiers);
+        }
+
         if adjust_scroll {
-            doc.adjust_scroll(
-                self.virtual_buffer_width,
-                self.buffer_width,
-                self.buffer_height,
-                self.first_visible_pos,
-                self.last_visible_pos,
-            );
+            self.adjust_scroll(editor.current_document_mut());
         }
 
         HandledEvent::Consumed
     }
 
+    fn handle_mouse_event(
+        &mut self,
+        editor: &mut Editor,
+        _compositor: &mut Compositor<Editor>,
+        kind: MouseEventKind,
+        modifiers: KeyModifiers,
+        surface_y: usize,
+        surface_x: usize,
+    ) -> HandledEvent {
+        // Alt + drag selects a block.
+        if modifiers != KeyModifiers::ALT {
+            return HandledEvent::Ignored;
+        }
+
+        let head = match self.display_position_at(editor, surface_y, surface_x) {
+            Some(pos) => pos,
+            None => return HandledEvent::Ignored,
+        };
+
+        let anchor = match kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                self.block_anchor = Some(head);
+                head
+            }
+            MouseEventKind::Drag(MouseButton::Left) => match self.block_anchor {
+                Some(anchor) => anchor,
+                None => return HandledEvent::Ignored,
+            },
+            _ => return HandledEvent::Ignored,
+        };
+
+        editor
+            .current_document_mut()
+            .select_block(BlockSelection { anchor, head });
+        HandledEvent::Consumed
+    }
+
     fn handle_key_batch_event(
         &mut self,
         editor: &mut Editor,
         _compositor: &mut Compositor<Self::Context>,
         input: &str,
     ) -> HandledEvent {
+        editor.macros.record(MacroStep::Text(input.to_owned()));
         let doc = editor.current_document_mut();
         doc.insert(input);
-        doc.adjust_scroll(
-            self.virtual_buffer_width,
-            self.buffer_width,
-            self.buffer_height,
-            self.first_visible_pos,
-            self.last_visible_pos,
-        );
+        self.adjust_scroll(doc);
         HandledEvent::Consumed
     }
 
@@ -232,6 +306,12 @@ impl Surface for BufferView {
 
         self.first_visible_pos = Position::new(usize::MAX, usize::MAX);
         self.last_visible_pos = Position::new(0, 0);
+        self.rendered_positions = vec![Vec::new(); canvas.height()];
+
+        // Search matches in the visible area.
+        let first_y = doc.scroll.paragraph_index.buffer_y;
+        let find_matches =
+            editor.find_matches(Range::new(first_y, 0, first_y + self.buffer_height + 1, 0));
 
         // Buffer contents.
         let main_cursor_pos = doc.main_cursor().moving_position();
@@ -283,6 +363,7 @@ impl Surface for BufferView {
 
                     self.first_visible_pos = min(self.first_visible_pos, pos_in_buffer);
                     self.last_visible_pos = max(self.last_visible_pos, pos_in_buffer);
+                    self.rendered_positions[canvas_y].push((canvas_x, pos_in_buffer));
 
                     if canvas_y >= linenos.len() {
                         linenos.push(pos_in_buffer.y + 1);
@@ -305,6 +386,15 @@ impl Surface for BufferView {
                         }
                     }
 
+                    if find_matches.iter().any(|r| r.contains(pos_in_buffer)) {
+                        canvas.apply_style(
+                            canvas_y,
+                            canvas_x,
+                            canvas_x + grapheme_width,
+                            theme_for("buffer.find_match"),
+                        );
+                    }
+
                     for c in doc.cursors() {
                         if c.selection().contains(pos_in_buffer)
                             || (!c.is_main_cursor() && c.position() == Some(pos_in_buffer))
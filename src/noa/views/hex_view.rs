@@ -0,0 +1,226 @@
+use noa_compositor::{
+    canvas::CanvasViewMut,
+    compositor::Compositor,
+    surface::{HandledEvent, KeyEvent, Layout, RectSize, Surface},
+    terminal::{KeyCode, KeyModifiers},
+};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::{
+    actions::execute_action_or_notify,
+    config::{get_keybinding_for, theme_for, KeyBindingScope},
+    editor::Editor,
+    hex_buffer::{parse_offset, BYTES_PER_ROW},
+    notify_error,
+    views::selector_view::SelectorView,
+    MainloopCommand,
+};
+
+const OFFSET_X: usize = 1;
+const HEX_X: usize = OFFSET_X + 8 + 2;
+const ASCII_X: usize = HEX_X + BYTES_PER_ROW * 3 + 2;
+
+/// The x of the hex column of the `i`-th byte in a row.
+fn hex_x(i: usize) -> usize {
+    // An extra space in the middle.
+    HEX_X + i * 3 + if i >= BYTES_PER_ROW / 2 { 1 } else { 0 }
+}
+
+fn printable_char(byte: u8) -> char {
+    if byte.is_ascii_graphic() || byte == b' ' {
+        byte as char
+    } else {
+        '.'
+    }
+}
+
+/// Shows a binary file as a hex dump: offsets, bytes in hex, and in ASCII.
+/// Bytes can be overwritten but not inserted or deleted.
+pub struct HexView {
+    mainloop_tx: UnboundedSender<MainloopCommand>,
+    height: usize,
+    cursor_screen_pos: Option<(usize, usize)>,
+}
+
+impl HexView {
+    pub fn new(mainloop_tx: UnboundedSender<MainloopCommand>) -> HexView {
+        HexView {
+            mainloop_tx,
+            height: 0,
+            cursor_screen_pos: None,
+        }
+    }
+}
+
+impl Surface for HexView {
+    type Context = Editor;
+
+    fn name(&self) -> &str {
+        "hex"
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+
+    fn is_active(&self, editor: &mut Editor) -> bool {
+        editor.current_document().hex.is_some()
+    }
+
+    fn layout(&mut self, _editor: &mut Editor, screen_size: RectSize) -> (Layout, RectSize) {
+        (
+            Layout::Fixed { y: 0, x: 0 },
+            RectSize {
+                width: screen_size.width,
+                height: screen_size.height.saturating_sub(2),
+            },
+        )
+    }
+
+    fn cursor_position(&self, _editor: &mut Editor) -> Option<(usize, usize)> {
+        self.cursor_screen_pos
+    }
+
+    fn render(&mut self, editor: &mut Editor, canvas: &mut CanvasViewMut<'_>) {
+        canvas.clear();
+        self.cursor_screen_pos = None;
+        self.height = canvas.height();
+
+        let hex = match editor.current_document_mut().hex.as_mut() {
+            Some(hex) => hex,
+            None => return,
+        };
+
+        hex.adjust_scroll(self.height);
+        for y in 0..self.height {
+            let row = hex.top_row + y;
+            if row >= hex.num_rows() {
+                break;
+            }
+
+            let offset = row * BYTES_PER_ROW;
+            canvas.write_str(y, OFFSET_X, &format!("{:08x}", offset));
+            canvas.apply_style(y, OFFSET_X, OFFSET_X + 8, theme_for("hex.offset"));
+
+            let row_end = (offset + BYTES_PER_ROW).min(hex.len());
+            for (i, byte) in hex.bytes()[offset..row_end].iter().enumerate() {
+                let x = hex_x(i);
+                canvas.write_str(y, x, &format!("{:02x}", byte));
+                canvas.write_char(y, ASCII_X + i, printable_char(*byte));
+
+                if hex.is_modified(offset + i) {
+                    let style = theme_for("hex.modified");
+                    canvas.apply_style(y, x, x + 2, style);
+                    canvas.apply_style(y, ASCII_X + i, ASCII_X + i + 1, style);
+                }
+
+                if offset + i == hex.cursor {
+                    canvas.set_inverted(y, ASCII_X + i, ASCII_X + i + 1, true);
+                    self.cursor_screen_pos = Some((y, x + hex.low_nibble as usize));
+                }
+            }
+        }
+    }
+
+    fn handle_key_event(
+        &mut self,
+        editor: &mut Editor,
+        compositor: &mut Compositor<Editor>,
+        key: KeyEvent,
+    ) -> HandledEvent {
+        const NONE: KeyModifiers = KeyModifiers::NONE;
+        const CTRL: KeyModifiers = KeyModifiers::CONTROL;
+        const SHIFT: KeyModifiers = KeyModifiers::SHIFT;
+
+        let doc = editor.current_document_mut();
+        let hex = match doc.hex.as_mut() {
+            Some(hex) => hex,
+            None => return HandledEvent::Ignored,
+        };
+
+        let row = BYTES_PER_ROW as isize;
+        let page = row * self.height.max(1) as isize;
+        match (key.code, key.modifiers) {
+            (KeyCode::Char('q'), CTRL) => {
+                let _ = self.mainloop_tx.send(MainloopCommand::Quit);
+            }
+            (KeyCode::Char('s'), CTRL) => {
+                doc.save();
+            }
+            (KeyCode::Char('g'), CTRL) => {
+                compositor
+                    .get_mut_surface_by_name::<SelectorView>("selector")
+                    .open_prompt("offset", |editor, _, input| match parse_offset(input) {
+                        Ok(offset) => {
+                            if let Some(hex) = editor.current_document_mut().hex.as_mut() {
+                                hex.move_cursor_to(offset);
+                            }
+                        }
+                        Err(err) => notify_error!("{}", err),
+                    });
+            }
+            (KeyCode::Up, NONE) => hex.move_cursor(-row),
+            (KeyCode::Down, NONE) => hex.move_cursor(row),
+            (KeyCode::Left, NONE) => hex.move_cursor(-1),
+            (KeyCode::Right, NONE) => hex.move_cursor(1),
+            (KeyCode::PageUp, NONE) => hex.move_cursor(-page),
+            (KeyCode::PageDown, NONE) => hex.move_cursor(page),
+            (KeyCode::Home, NONE) => {
+                hex.move_cursor_to(hex.cursor - hex.cursor % BYTES_PER_ROW);
+            }
+            (KeyCode::End, NONE) => {
+                hex.move_cursor_to(hex.cursor - hex.cursor % BYTES_PER_ROW + BYTES_PER_ROW - 1);
+            }
+            (KeyCode::Backspace, NONE) => {
+                hex.revert_byte();
+            }
+            (KeyCode::Char(ch), NONE | SHIFT) if ch.is_ascii_hexdigit() => {
+                hex.overwrite_nibble(ch.to_digit(16).unwrap() as u8);
+            }
+            _ => {
+                // Run the bound action like switching documents.
+                if let Some(binding) =
+                    get_keybinding_for(KeyBindingScope::Buffer, key.code, key.modifiers)
+                {
+                    execute_action_or_notify(editor, compositor, &binding.action);
+                }
+            }
+        }
+
+        // Don't let the buffer view handle keys: the buffer is not used.
+        HandledEvent::Consumed
+    }
+
+    fn handle_key_batch_event(
+        &mut self,
+        editor: &mut Editor,
+        _compositor: &mut Compositor<Editor>,
+        input: &str,
+    ) -> HandledEvent {
+        if let Some(hex) = editor.current_document_mut().hex.as_mut() {
+            for ch in input.chars() {
+                if let Some(digit) = ch.to_digit(16) {
+                    hex.overwrite_nibble(digit as u8);
+                }
+            }
+        }
+
+        HandledEvent::Consumed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_columns() {
+        assert_eq!(hex_x(0), HEX_X);
+        assert_eq!(hex_x(7), HEX_X + 21);
+        assert_eq!(hex_x(8), HEX_X + 25);
+        assert!(hex_x(BYTES_PER_ROW - 1) + 2 < ASCII_X);
+        assert_eq!(printable_char(b'a'), 'a');
+        assert_eq!(printable_char(0x00), '.');
+        assert_eq!(printable_char(0x80), '.');
+    }
+}
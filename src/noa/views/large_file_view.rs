@@ -0,0 +1,302 @@
+use noa_buffer::{cursor::Position, display_width::DisplayWidth};
+use noa_compositor::{
+    canvas::CanvasViewMut,
+    compositor::Compositor,
+    surface::{HandledEvent, KeyEvent, Layout, RectSize, Surface},
+    terminal::{KeyCode, KeyModifiers},
+};
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::{
+    actions::execute_action_or_notify,
+    config::{get_keybinding_for, KeyBindingScope},
+    editor::Editor,
+    large_file::LargeFile,
+    notify_error, notify_info,
+    views::selector_view::SelectorView,
+    MainloopCommand,
+};
+
+/// Replaces characters which can't be drawn as is.
+fn displayable_char(ch: char) -> char {
+    if ch.is_control() {
+        ' '
+    } else {
+        ch
+    }
+}
+
+fn chars_width(chars: &[char]) -> usize {
+    chars
+        .iter()
+        .map(|ch| displayable_char(*ch).display_width())
+        .sum()
+}
+
+/// Moves the cursor to the next match of the query, wrapping around at the
+/// end of the file.
+fn find_next(large_file: &mut LargeFile, query: &str) {
+    let found = large_file
+        .find(query, Some(large_file.cursor))
+        .or_else(|| large_file.find(query, None));
+
+    match found {
+        Some(pos) => large_file.move_cursor_to(pos.y, pos.x),
+        None => notify_info!("no matches"),
+    }
+}
+
+/// Shows a file opened in the large-file mode. Only the visible lines are
+/// read from the file.
+pub struct LargeFileView {
+    mainloop_tx: UnboundedSender<MainloopCommand>,
+    height: usize,
+    cursor_screen_pos: Option<(usize, usize)>,
+}
+
+impl LargeFileView {
+    pub fn new(mainloop_tx: UnboundedSender<MainloopCommand>) -> LargeFileView {
+        LargeFileView {
+            mainloop_tx,
+            height: 0,
+            cursor_screen_pos: None,
+        }
+    }
+}
+
+impl Surface for LargeFileView {
+    type Context = Editor;
+
+    fn name(&self) -> &str {
+        "large_file"
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+
+    fn is_active(&self, editor: &mut Editor) -> bool {
+        editor.current_document().large_file.is_some()
+    }
+
+    fn layout(&mut self, _editor: &mut Editor, screen_size: RectSize) -> (Layout, RectSize) {
+        (
+            Layout::Fixed { y: 0, x: 0 },
+            RectSize {
+                width: screen_size.width,
+                height: screen_size.height.saturating_sub(2),
+            },
+        )
+    }
+
+    fn cursor_position(&self, _editor: &mut Editor) -> Option<(usize, usize)> {
+        self.cursor_screen_pos
+    }
+
+    fn render(&mut self, editor: &mut Editor, canvas: &mut CanvasViewMut<'_>) {
+        canvas.clear();
+        self.cursor_screen_pos = None;
+        self.height = canvas.height();
+
+        let large_file = match editor.current_document_mut().large_file.as_mut() {
+            Some(large_file) => large_file,
+            None => return,
+        };
+
+        let lineno_width = 2 + large_file.num_lines().display_width();
+        let text_width = canvas.width().saturating_sub(lineno_width);
+        if self.height == 0 || text_width == 0 {
+            return;
+        }
+
+        // Scroll so that the cursor is visible.
+        let cursor = large_file.cursor;
+        if cursor.y < large_file.top_line {
+            large_file.top_line = cursor.y;
+        } else if cursor.y >= large_file.top_line + self.height {
+            large_file.top_line = cursor.y + 1 - self.height;
+        }
+
+        let cursor_line: Vec<char> = large_file.line(cursor.y).chars().collect();
+        let cursor_x = cursor.x.min(cursor_line.len());
+        if cursor_x < large_file.left_column {
+            large_file.left_column = cursor_x;
+        }
+        while chars_width(&cursor_line[large_file.left_column..cursor_x]) >= text_width {
+            large_file.left_column += 1;
+        }
+
+        for y in 0..self.height {
+            let line_y = large_file.top_line + y;
+            if line_y >= large_file.num_lines() {
+                break;
+            }
+
+            canvas.write_str(
+                y,
+                1,
+                &format!("{:>width$}", line_y + 1, width = lineno_width - 2),
+            );
+
+            let mut x = 0;
+            for ch in large_file.line(line_y).chars().skip(large_file.left_column) {
+                let ch = displayable_char(ch);
+                if x + ch.display_width() > text_width {
+                    break;
+                }
+
+                canvas.write_char(y, lineno_width + x, ch);
+                x += ch.display_width();
+            }
+        }
+
+        self.cursor_screen_pos = Some((
+            cursor.y - large_file.top_line,
+            lineno_width + chars_width(&cursor_line[large_file.left_column..cursor_x]),
+        ));
+    }
+
+    fn handle_key_event(
+        &mut self,
+        editor: &mut Editor,
+        compositor: &mut Compositor<Editor>,
+        key: KeyEvent,
+    ) -> HandledEvent {
+        const NONE: KeyModifiers = KeyModifiers::NONE;
+        const CTRL: KeyModifiers = KeyModifiers::CONTROL;
+        const ALT: KeyModifiers = KeyModifiers::ALT;
+        const SHIFT: KeyModifiers = KeyModifiers::SHIFT;
+
+        let find_query = editor.find_query.clone();
+        let doc = editor.current_document_mut();
+        let large_file = match doc.large_file.as_mut() {
+            Some(large_file) => large_file,
+            None => return HandledEvent::Ignored,
+        };
+
+        let Position { y, x } = large_file.cursor;
+        let page = self.height.max(1);
+        let result = match (key.code, key.modifiers) {
+            (KeyCode::Char('q'), CTRL) => {
+                let _ = self.mainloop_tx.send(MainloopCommand::Quit);
+                Ok(())
+            }
+            (KeyCode::Char('s'), CTRL) => {
+                doc.save();
+                Ok(())
+            }
+            (KeyCode::Char('g'), CTRL) => {
+                compositor
+                    .get_mut_surface_by_name::<SelectorView>("selector")
+                    .open_prompt("line", |editor, _, input| {
+                        match input.trim().parse::<usize>() {
+                            Ok(lineno) => {
+                                if let Some(large_file) =
+                                    editor.current_document_mut().large_file.as_mut()
+                                {
+                                    large_file.move_cursor_to(lineno.saturating_sub(1), 0);
+                                }
+                            }
+                            Err(_) => notify_error!("invalid line number: \"{}\"", input),
+                        }
+                    });
+                Ok(())
+            }
+            (KeyCode::Char('s'), ALT) => {
+                compositor
+                    .get_mut_surface_by_name::<SelectorView>("selector")
+                    .open_prompt("search", |editor, _, input| {
+                        editor.find_query = input.to_owned();
+                        if let Some(large_file) = editor.current_document_mut().large_file.as_mut()
+                        {
+                            find_next(large_file, input);
+                        }
+                    });
+                Ok(())
+            }
+            (KeyCode::Char('n'), ALT) => {
+                find_next(large_file, &find_query);
+                Ok(())
+            }
+            (KeyCode::Up, NONE) => {
+                large_file.move_cursor_to(y.saturating_sub(1), x);
+                Ok(())
+            }
+            (KeyCode::Down, NONE) => {
+                large_file.move_cursor_to(y + 1, x);
+                Ok(())
+            }
+            (KeyCode::Left, NONE) => {
+                large_file.move_cursor_to(y, x.saturating_sub(1));
+                Ok(())
+            }
+            (KeyCode::Right, NONE) => {
+                large_file.move_cursor_to(y, x + 1);
+                Ok(())
+            }
+            (KeyCode::PageUp, NONE) => {
+                large_file.move_cursor_to(y.saturating_sub(page), x);
+                Ok(())
+            }
+            (KeyCode::PageDown, NONE) => {
+                large_file.move_cursor_to(y + page, x);
+                Ok(())
+            }
+            (KeyCode::Home, NONE) => {
+                large_file.move_cursor_to(y, 0);
+                Ok(())
+            }
+            (KeyCode::End, NONE) => {
+                large_file.move_cursor_to(y, usize::MAX);
+                Ok(())
+            }
+            (KeyCode::Home, CTRL) => {
+                large_file.move_cursor_to(0, 0);
+                Ok(())
+            }
+            (KeyCode::End, CTRL) => {
+                large_file.move_cursor_to(usize::MAX, 0);
+                Ok(())
+            }
+            (KeyCode::Enter, NONE) => large_file.insert_char('\n'),
+            (KeyCode::Backspace, NONE) => large_file.backspace(),
+            (KeyCode::Delete, NONE) => large_file.delete(),
+            (KeyCode::Tab, NONE) => large_file.insert_char('\t'),
+            (KeyCode::Char(ch), NONE | SHIFT) => large_file.insert_char(ch),
+            _ => {
+                // Run the bound action like switching documents.
+                if let Some(binding) =
+                    get_keybinding_for(KeyBindingScope::Buffer, key.code, key.modifiers)
+                {
+                    execute_action_or_notify(editor, compositor, &binding.action);
+                }
+                Ok(())
+            }
+        };
+
+        if let Err(err) = result {
+            notify_error!("{}", err);
+        }
+
+        // Don't let the buffer view handle keys: the buffer is not used.
+        HandledEvent::Consumed
+    }
+
+    fn handle_key_batch_event(
+        &mut self,
+        editor: &mut Editor,
+        _compositor: &mut Compositor<Editor>,
+        input: &str,
+    ) -> HandledEvent {
+        if let Some(large_file) = editor.current_document_mut().large_file.as_mut() {
+            for ch in input.chars() {
+                if let Err(err) = large_file.insert_char(ch) {
+                    notify_error!("{}", err);
+                    break;
+                }
+            }
+        }
+
+        HandledEvent::Consumed
+    }
+}
@@ -1,35 +1,295 @@
-use noa_buffer::display_width::DisplayWidth;
+use noa_buffer::{
+    buffer::TextEdit,
+    cursor::{Cursor, Position, Range},
+    display_width::DisplayWidth,
+    find::{CaseMatching, FindOptions},
+    scroll::Scroll,
+};
 use noa_compositor::{
     canvas::CanvasViewMut,
-    surface::{Layout, RectSize, Surface},
+    compositor::Compositor,
+    surface::{HandledEvent, KeyEvent, Layout, RectSize, Surface},
+    terminal::{KeyCode, KeyModifiers},
 };
 
 use crate::{
     config::theme_for,
     editor::Editor,
+    macros::MacroStep,
     notification::{notification_manager, Notification},
+    notify_error, notify_info, notify_warn,
 };
 
-use super::truncate_to_width_suffix;
+use super::{buffer_view::BufferView, truncate_to_width_suffix};
 
 pub const META_LINE_HEIGHT: usize = 2;
+const REPLACE_SEPARATOR: &str = " -> ";
 
 pub enum MetaLineMode {
     Normal,
     Search,
+    Replace,
+    /// Asks whether to replace each match.
+    ReplaceConfirm,
 }
 
 pub struct MetaLine {
     mode: MetaLineMode,
     clear_notification_after: usize,
+    /// Occurrences of the search query.
+    matches: Vec<Range>,
+    /// The index in `matches` of the selected one.
+    current_match: Option<usize>,
+    /// The cursors and the scroll before starting the search.
+    saved_cursors: Vec<Cursor>,
+    saved_scroll: Option<Scroll>,
+    replacement: String,
+    /// Whether the user is editing the replacement instead of the query.
+    editing_replacement: bool,
+    /// In `ReplaceConfirm` mode, the replacements of `matches`.
+    replace_edits: Vec<TextEdit>,
+    /// In `ReplaceConfirm` mode, the replacements the user accepted.
+    accepted_edits: Vec<TextEdit>,
 }
+
 impl MetaLine {
     pub fn new() -> Self {
         MetaLine {
             mode: MetaLineMode::Normal,
             clear_notification_after: 0,
+            matches: Vec::new(),
+            current_match: None,
+            saved_cursors: Vec::new(),
+            saved_scroll: None,
+            replacement: String::new(),
+            editing_replacement: false,
+            replace_edits: Vec::new(),
+            accepted_edits: Vec::new(),
+        }
+    }
+
+    /// Enters the incremental search mode.
+    pub fn start_search(&mut self, editor: &mut Editor) {
+        self.mode = MetaLineMode::Search;
+        self.save_state(editor);
+        editor.find_query.clear();
+        editor.find_regex = false;
+        editor.find_options = FindOptions {
+            case_matching: CaseMatching::Smart,
+            ..Default::default()
+        };
+    }
+
+    /// Enters the replace mode. The query of the last search is reused.
+    pub fn start_replace(&mut self, editor: &mut Editor) {
+        self.mode = MetaLineMode::Replace;
+        self.save_state(editor);
+        self.replacement.clear();
+        self.editing_replacement = !editor.find_query.is_empty();
+        if editor.find_query.is_empty() {
+            editor.find_options = FindOptions {
+                case_matching: CaseMatching::Smart,
+                ..Default::default()
+            };
+        }
+
+        notify_info!("Tab: query/replacement, Enter: replace, ^A: all, ^S: in selection, ^Y: confirm each, ^R: regex");
+        self.update_matches(editor, self.search_origin());
+    }
+
+    fn save_state(&mut self, editor: &mut Editor) {
+        let doc = editor.current_document();
+        self.saved_cursors = doc.cursors().to_vec();
+        self.saved_scroll = Some(doc.scroll.clone());
+        self.matches.clear();
+        self.current_match = None;
+    }
+
+    /// Leaves the search mode. The cursors and the scroll are restored if
+    /// `cancel` is true.
+    fn end_search(&mut self, editor: &mut Editor, cancel: bool) {
+        if cancel {
+            let doc = editor.current_document_mut();
+            doc.update_cursors(&self.saved_cursors);
+            if let Some(scroll) = self.saved_scroll.take() {
+                doc.scroll = scroll;
+            }
+            editor.find_query.clear();
         }
+
+        self.mode = MetaLineMode::Normal;
+        self.matches.clear();
+        self.current_match = None;
+        self.saved_cursors.clear();
+        self.saved_scroll = None;
+        self.replace_edits.clear();
+        self.accepted_edits.clear();
+    }
+
+    /// The position where the search started.
+    fn search_origin(&self) -> Position {
+        self.saved_cursors
+            .iter()
+            .find(|c| c.is_main_cursor())
+            .map(|c| c.selection().front())
+            .unwrap_or_else(|| Position::new(0, 0))
+    }
+
+    /// Updates matches and selects the first one at or after `origin`.
+    fn update_matches(&mut self, editor: &mut Editor, origin: Position) {
+        let num_lines = editor.current_document().num_lines();
+        self.matches = editor.find_matches(Range::new(0, 0, num_lines, 0));
+        let index = self
+            .matches
+            .iter()
+            .position(|range| range.front() >= origin)
+            .unwrap_or(0);
+        self.select_match(editor, index);
     }
+
+    /// Selects the next match (or the previous one if `forward` is false).
+    /// Wraps around at the end of the buffer.
+    fn move_to_next_match(&mut self, editor: &mut Editor, forward: bool) {
+        let len = self.matches.len();
+        let index = match self.current_match {
+            Some(i) if forward => (i + 1) % len,
+            Some(i) => (i + len - 1) % len,
+            None => return,
+        };
+        self.select_match(editor, index);
+    }
+
+    fn select_match(&mut self, editor: &mut Editor, index: usize) {
+        let doc = editor.current_document_mut();
+        match self.matches.get(index) {
+            Some(range) => {
+                self.current_match = Some(index);
+                doc.clear_secondary_cursors();
+                doc.select_main_cursor_range(*range);
+            }
+            None => {
+                // No matches: go back to where we started.
+                self.current_match = None;
+                doc.update_cursors(&self.saved_cursors);
+            }
+        }
+    }
+
+    /// Returns the replacements of matches in `ranges`.
+    fn replace_edits_in(&self, editor: &Editor, ranges: &[Range]) -> Option<Vec<TextEdit>> {
+        let doc = editor.current_document();
+        let mut edits = Vec::new();
+        for range in ranges {
+            match doc.replace_edits(
+                &editor.find_query,
+                &self.replacement,
+                editor.find_regex,
+                editor.find_options,
+                *range,
+            ) {
+                Ok(new_edits) => edits.extend(new_edits),
+                Err(err) => {
+                    notify_error!("{}", err);
+                    return None;
+                }
+            }
+        }
+
+        Some(edits)
+    }
+
+    /// Replaces the selected match and selects the next one.
+    fn replace_current_match(&mut self, editor: &mut Editor) {
+        let range = match self.current_match {
+            Some(i) => self.matches[i],
+            None => return,
+        };
+
+        let edits = match self.replace_edits_in(editor, &[range]) {
+            Some(edits) => edits,
+            None => return,
+        };
+
+        // Continue from the end of the replaced text so that it won't match
+        // again.
+        let next_pos = edits
+            .first()
+            .map(|edit| Position::position_after_edit(edit.range, &edit.new_text))
+            .unwrap_or_else(|| range.back());
+        let doc = editor.current_document_mut();
+        doc.apply_replacements(edits);
+        // The saved cursors may no longer be valid.
+        self.saved_cursors = doc.cursors().to_vec();
+        self.update_matches(editor, next_pos);
+    }
+
+    /// Replaces all matches in `ranges` at once.
+    fn replace_all(&mut self, editor: &mut Editor, ranges: &[Range]) {
+        let edits = match self.replace_edits_in(editor, ranges) {
+            Some(edits) => edits,
+            None => return,
+        };
+
+        self.finish_replace(editor, edits);
+    }
+
+    fn finish_replace(&mut self, editor: &mut Editor, edits: Vec<TextEdit>) {
+        let num_edits = edits.len();
+        if num_edits > 0 {
+            editor.current_document_mut().apply_replacements(edits);
+        }
+
+        notify_info!("replaced {} occurrences", num_edits);
+        editor.find_query.clear();
+        self.end_search(editor, false);
+    }
+
+    /// Starts asking whether to replace each match from the current one.
+    fn start_confirm(&mut self, editor: &mut Editor) {
+        let start = self.current_match.unwrap_or(0);
+        let num_lines = editor.current_document().num_lines();
+        let mut edits = match self.replace_edits_in(editor, &[Range::new(0, 0, num_lines, 0)]) {
+            Some(edits) => edits,
+            None => return,
+        };
+
+        if edits.is_empty() {
+            notify_warn!("no matches");
+            return;
+        }
+
+        // Visit the matches from the current one and wrap around.
+        let start = start.min(edits.len() - 1);
+        edits.rotate_left(start);
+        self.mode = MetaLineMode::ReplaceConfirm;
+        self.matches = edits.iter().map(|edit| edit.range).collect();
+        self.replace_edits = edits;
+        self.accepted_edits.clear();
+        self.select_match(editor, 0);
+    }
+
+    /// Moves to the next match in `ReplaceConfirm` mode. Returns false if
+    /// there're no more matches.
+    fn next_confirm(&mut self, editor: &mut Editor) -> bool {
+        let next = self.current_match.map(|i| i + 1).unwrap_or(0);
+        if next >= self.matches.len() {
+            return false;
+        }
+
+        self.select_match(editor, next);
+        true
+    }
+}
+
+fn prompt_width(name: &str) -> usize {
+    name.display_width() + 2
+}
+
+/// Scrolls the buffer to make the main cursor visible.
+fn adjust_buffer_scroll(editor: &mut Editor, compositor: &mut Compositor<Editor>) {
+    compositor
+        .get_mut_surface_by_name::<BufferView>("buffer")
+        .adjust_scroll(editor.current_document_mut());
 }
 
 impl Surface for MetaLine {
@@ -60,8 +320,26 @@ impl Surface for MetaLine {
         )
     }
 
-    fn cursor_position(&self, _editor: &mut Editor) -> Option<(usize, usize)> {
-        None
+    fn cursor_position(&self, editor: &mut Editor) -> Option<(usize, usize)> {
+        match self.mode {
+            MetaLineMode::Search => Some((
+                0,
+                prompt_width("search") + 1 + editor.find_query.display_width(),
+            )),
+            MetaLineMode::Replace if self.editing_replacement => Some((
+                0,
+                prompt_width("replace")
+                    + 1
+                    + editor.find_query.display_width()
+                    + REPLACE_SEPARATOR.display_width()
+                    + self.replacement.display_width(),
+            )),
+            MetaLineMode::Replace => Some((
+                0,
+                prompt_width("replace") + 1 + editor.find_query.display_width(),
+            )),
+            MetaLineMode::ReplaceConfirm | MetaLineMode::Normal => None,
+        }
     }
 
     fn render(&mut self, editor: &mut Editor, canvas: &mut CanvasViewMut<'_>) {
@@ -72,14 +350,54 @@ impl Surface for MetaLine {
         canvas.apply_style(0, 0, canvas.width(), theme_for("meta_line.background"));
 
         match self.mode {
-            MetaLineMode::Search => {
-                // TODO:
+            MetaLineMode::Search | MetaLineMode::Replace | MetaLineMode::ReplaceConfirm => {
+                let (name, text) = match self.mode {
+                    MetaLineMode::Search => ("search", editor.find_query.clone()),
+                    MetaLineMode::Replace => (
+                        "replace",
+                        format!(
+                            "{}{}{}",
+                            editor.find_query, REPLACE_SEPARATOR, self.replacement
+                        ),
+                    ),
+                    _ => (
+                        "replace",
+                        format!("replace with \"{}\"? (y/n/a/q)", self.replacement),
+                    ),
+                };
+
+                // The match counter, e.g. "3/17".
+                let counter = match self.current_match {
+                    Some(i) => format!("{}/{}", i + 1, self.matches.len()),
+                    None if editor.find_query.is_empty() => String::new(),
+                    None => "no matches".to_owned(),
+                };
+                let right_text = if editor.find_regex {
+                    format!("(regex) {}", counter)
+                } else {
+                    counter
+                };
+
+                let prompt_width = prompt_width(name);
+                canvas.write_str(0, 1, name);
+                canvas.apply_style(0, 0, prompt_width, theme_for("prompt.name"));
+                canvas.write_str(0, prompt_width + 1, &text);
+                canvas.write_str(
+                    0,
+                    canvas
+                        .width()
+                        .saturating_sub(1 + right_text.display_width()),
+                    &right_text,
+                );
             }
             MetaLineMode::Normal => {
                 // Cursor position.
                 let cursor_pos = doc.main_cursor().moving_position();
                 let cursor_col = cursor_pos.x + 1;
-                let cursor_text = if doc.cursors().len() > 1 {
+                let cursor_text = if let Some(large_file) = &doc.large_file {
+                    let pos = large_file.cursor;
+                    format!("{}:{}", pos.y + 1, pos.x + 1)
+                } else if doc.cursors().len() > 1 {
                     let num_invisible_cursors = doc
                         .cursors()
                         .iter()
@@ -109,8 +427,28 @@ impl Surface for MetaLine {
                 // Is the buffer dirty?
                 let is_dirty = if doc.is_dirty() { "[+]" } else { "" };
 
-                let left_text = [is_dirty].join(" ");
-                let right_text = [cursor_text.as_str()].join(" ");
+                // Are we recording a macro?
+                let recording = editor
+                    .macros
+                    .recording_register()
+                    .map(|register| format!("[recording @{}]", register))
+                    .unwrap_or_default();
+
+                let left_text = [is_dirty, recording.as_str()].join(" ");
+                // Encoding and line ending.
+                let (encoding, eol) = if doc.hex.is_some() {
+                    ("binary".to_owned(), "")
+                } else if let Some(large_file) = &doc.large_file {
+                    if large_file.is_read_only() {
+                        ("large binary (read-only)".to_owned(), "")
+                    } else {
+                        ("large file".to_owned(), "")
+                    }
+                } else {
+                    (doc.encoding().name(), doc.editorconfig().end_of_line.name())
+                };
+
+                let right_text = [encoding.as_str(), eol, cursor_text.as_str()].join(" ");
 
                 // File name.
                 let filename = truncate_to_width_suffix(
@@ -146,4 +484,179 @@ impl Surface for MetaLine {
             canvas.apply_style(1, 1, canvas.width(), theme_for(theme_key));
         };
     }
+
+    fn handle_key_event(
+        &mut self,
+        editor: &mut Editor,
+        compositor: &mut Compositor<Editor>,
+        key: KeyEvent,
+    ) -> HandledEvent {
+        const NONE: KeyModifiers = KeyModifiers::NONE;
+        const CTRL: KeyModifiers = KeyModifiers::CONTROL;
+        const SHIFT: KeyModifiers = KeyModifiers::SHIFT;
+
+        // Record the input so that replaying the action which opened the
+        // prompt sees it.
+        if !matches!(self.mode, MetaLineMode::Normal) {
+            editor.macros.record_key(key.code, key.modifiers);
+        }
+
+        match self.mode {
+            MetaLineMode::Normal => return HandledEvent::Ignored,
+            MetaLineMode::ReplaceConfirm => {
+                match (key.code, key.modifiers) {
+                    (KeyCode::Char('y'), NONE) => {
+                        if let Some(i) = self.current_match {
+                            self.accepted_edits.push(self.replace_edits[i].clone());
+                        }
+                        if !self.next_confirm(editor) {
+                            let edits = std::mem::take(&mut self.accepted_edits);
+                            self.finish_replace(editor, edits);
+                        }
+                    }
+                    (KeyCode::Char('n'), NONE) => {
+                        if !self.next_confirm(editor) {
+                            let edits = std::mem::take(&mut self.accepted_edits);
+                            self.finish_replace(editor, edits);
+                        }
+                    }
+                    (KeyCode::Char('a'), NONE) => {
+                        let mut edits = std::mem::take(&mut self.accepted_edits);
+                        if let Some(i) = self.current_match {
+                            edits.extend_from_slice(&self.replace_edits[i..]);
+                        }
+                        self.finish_replace(editor, edits);
+                    }
+                    (KeyCode::Char('q'), NONE) | (KeyCode::Esc, NONE) => {
+                        let edits = std::mem::take(&mut self.accepted_edits);
+                        self.finish_replace(editor, edits);
+                    }
+                    _ => {}
+                }
+
+                adjust_buffer_scroll(editor, compositor);
+                return HandledEvent::Consumed;
+            }
+            MetaLineMode::Search | MetaLineMode::Replace => {}
+        }
+
+        let is_replace = matches!(self.mode, MetaLineMode::Replace);
+        match (key.code, key.modifiers) {
+            (KeyCode::Esc, NONE) => {
+                self.end_search(editor, true);
+            }
+            (KeyCode::Enter, NONE) if is_replace => {
+                self.replace_current_match(editor);
+            }
+            (KeyCode::Enter, NONE) | (KeyCode::Down, NONE) => {
+                self.move_to_next_match(editor, true);
+            }
+            (KeyCode::Enter, SHIFT) | (KeyCode::Up, NONE) => {
+                self.move_to_next_match(editor, false);
+            }
+            (KeyCode::Char('r'), CTRL) => {
+                editor.find_regex = !editor.find_regex;
+                self.update_matches(editor, self.search_origin());
+            }
+            (KeyCode::Tab, NONE) if is_replace => {
+                self.editing_replacement = !self.editing_replacement;
+            }
+            (KeyCode::Char('a'), CTRL) if is_replace => {
+                let num_lines = editor.current_document().num_lines();
+                self.replace_all(editor, &[Range::new(0, 0, num_lines, 0)]);
+            }
+            (KeyCode::Char('s'), CTRL) if is_replace => {
+                let selections: Vec<Range> = self
+                    .saved_cursors
+                    .iter()
+                    .map(|c| c.selection())
+                    .filter(|selection| !selection.is_empty())
+                    .collect();
+                if selections.is_empty() {
+                    notify_warn!("no selections");
+                } else {
+                    self.replace_all(editor, &selections);
+                }
+            }
+            (KeyCode::Char('y'), CTRL) if is_replace => {
+                self.start_confirm(editor);
+            }
+            (KeyCode::Backspace, NONE) if self.editing_replacement && is_replace => {
+                self.replacement.pop();
+            }
+            (KeyCode::Char(ch), NONE) | (KeyCode::Char(ch), SHIFT)
+                if self.editing_replacement && is_replace =>
+            {
+                self.replacement.push(ch);
+            }
+            (KeyCode::Backspace, NONE) => {
+                editor.find_query.pop();
+                self.update_matches(editor, self.search_origin());
+            }
+            (KeyCode::Char(ch), NONE) | (KeyCode::Char(ch), SHIFT) => {
+                editor.find_query.push(ch);
+                self.update_matches(editor, self.search_origin());
+            }
+            _ if is_replace => {}
+            _ => {
+                // Keep the selected match and let the buffer handle the key.
+                self.end_search(editor, false);
+                return HandledEvent::Ignored;
+            }
+        }
+
+        adjust_buffer_scroll(editor, compositor);
+        HandledEvent::Consumed
+    }
+
+    fn handle_key_batch_event(
+        &mut self,
+        editor: &mut Editor,
+        compositor: &mut Compositor<Editor>,
+        input: &str,
+    ) -> HandledEvent {
+        if !matches!(self.mode, MetaLineMode::Normal) {
+            editor.macros.record(MacroStep::Text(input.to_owned()));
+        }
+
+        let input = input.lines().next().unwrap_or("");
+        match self.mode {
+            MetaLineMode::Normal => return HandledEvent::Ignored,
+            MetaLineMode::ReplaceConfirm => {}
+            MetaLineMode::Replace if self.editing_replacement => {
+                self.replacement.push_str(input);
+            }
+            MetaLineMode::Search | MetaLineMode::Replace => {
+                editor.find_query.push_str(input);
+                self.update_matches(editor, self.search_origin());
+            }
+        }
+
+        adjust_buffer_scroll(editor, compositor);
+        HandledEvent::Consumed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use noa_compositor::canvas::Canvas;
+
+    use super::*;
+
+    #[test]
+    fn search_cursor_position() {
+        let mut editor = Editor::new();
+        let mut view = MetaLine::new();
+        view.start_search(&mut editor);
+        editor.find_query = "abc".to_owned();
+
+        let mut canvas = Canvas::new(1, 40);
+        view.render(&mut editor, &mut canvas.view_mut());
+
+        // The cursor is right after the query.
+        let (y, x) = view.cursor_position(&mut editor).unwrap();
+        assert_eq!(y, 0);
+        assert_eq!(canvas.graphemes()[x - 1].chars.as_str(), "c");
+        assert_eq!(canvas.graphemes()[x].chars.as_str(), " ");
+    }
 }
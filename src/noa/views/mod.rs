@@ -1,7 +1,10 @@
 use noa_buffer::display_width::DisplayWidth;
 
 pub mod buffer_view;
+pub mod hex_view;
+pub mod large_file_view;
 pub mod metaline_view;
+pub mod selector_view;
 
 pub(super) fn truncate_to_width_suffix(s: &str, width: usize) -> &str {
     if s.display_width() <= width {
@@ -0,0 +1,247 @@
+use std::cmp::min;
+
+use noa_buffer::display_width::DisplayWidth;
+use noa_compositor::{
+    canvas::CanvasViewMut,
+    compositor::Compositor,
+    surface::{HandledEvent, KeyEvent, Layout, RectSize, Surface},
+    terminal::{KeyCode, KeyModifiers},
+};
+
+use crate::{config::theme_for, editor::Editor, macros::MacroStep};
+
+use super::metaline_view::META_LINE_HEIGHT;
+
+const MAX_NUM_ITEMS: usize = 16;
+
+pub struct SelectorItem {
+    pub label: String,
+    pub detail: String,
+}
+
+type ItemCallback = Box<dyn FnOnce(&mut Editor, &mut Compositor<Editor>, usize) + Send>;
+type InputCallback = Box<dyn FnOnce(&mut Editor, &mut Compositor<Editor>, &str) + Send>;
+
+enum SelectorCallback {
+    /// Called with the index of the selected item.
+    Item(ItemCallback),
+    /// Called with the input text.
+    Input(InputCallback),
+}
+
+/// A popup to select an item from a list or to read a line of text.
+pub struct SelectorView {
+    active: bool,
+    title: String,
+    input: String,
+    items: Vec<SelectorItem>,
+    /// Indices of `items` matching the input.
+    filtered_items: Vec<usize>,
+    selected: usize,
+    callback: Option<SelectorCallback>,
+}
+
+impl SelectorView {
+    pub fn new() -> SelectorView {
+        SelectorView {
+            active: false,
+            title: String::new(),
+            input: String::new(),
+            items: Vec::new(),
+            filtered_items: Vec::new(),
+            selected: 0,
+            callback: None,
+        }
+    }
+
+    /// Opens the selector. `callback` is called with the index in `items`
+    /// when the user selects one.
+    pub fn open<F>(&mut self, title: &str, items: Vec<SelectorItem>, selected: usize, callback: F)
+    where
+        F: FnOnce(&mut Editor, &mut Compositor<Editor>, usize) + Send + 'static,
+    {
+        self.active = true;
+        self.title = title.to_owned();
+        self.input.clear();
+        self.items = items;
+        self.update_filtered_items();
+        self.selected = min(selected, self.filtered_items.len().saturating_sub(1));
+        self.callback = Some(SelectorCallback::Item(Box::new(callback)));
+    }
+
+    /// Opens the selector as a prompt. `callback` is called with the input
+    /// text when the user hits Enter.
+    pub fn open_prompt<F>(&mut self, title: &str, callback: F)
+    where
+        F: FnOnce(&mut Editor, &mut Compositor<Editor>, &str) + Send + 'static,
+    {
+        self.active = true;
+        self.title = title.to_owned();
+        self.input.clear();
+        self.items.clear();
+        self.filtered_items.clear();
+        self.selected = 0;
+        self.callback = Some(SelectorCallback::Input(Box::new(callback)));
+    }
+
+    pub fn close(&mut self) {
+        self.active = false;
+        self.callback = None;
+        self.items.clear();
+        self.filtered_items.clear();
+    }
+
+    fn update_filtered_items(&mut self) {
+        let query = self.input.to_lowercase();
+        self.filtered_items = self
+            .items
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| item.label.to_lowercase().contains(&query))
+            .map(|(i, _)| i)
+            .collect();
+        self.selected = 0;
+    }
+
+    fn title_width(&self) -> usize {
+        self.title.display_width() + 2
+    }
+}
+
+impl Surface for SelectorView {
+    type Context = Editor;
+
+    fn name(&self) -> &str {
+        "selector"
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+
+    fn is_active(&self, _editor: &mut Editor) -> bool {
+        self.active
+    }
+
+    fn layout(&mut self, _editor: &mut Editor, screen_size: RectSize) -> (Layout, RectSize) {
+        let height = 1 + min(self.filtered_items.len(), MAX_NUM_ITEMS);
+        (
+            Layout::Fixed {
+                y: screen_size.height.saturating_sub(META_LINE_HEIGHT + height),
+                x: 0,
+            },
+            RectSize {
+                height,
+                width: screen_size.width,
+            },
+        )
+    }
+
+    fn cursor_position(&self, _editor: &mut Editor) -> Option<(usize, usize)> {
+        Some((0, self.title_width() + 1 + self.input.display_width()))
+    }
+
+    fn render(&mut self, _editor: &mut Editor, canvas: &mut CanvasViewMut<'_>) {
+        canvas.clear();
+
+        // The title and the input.
+        let title_width = self.title_width();
+        canvas.write_str(0, 1, &self.title);
+        canvas.apply_style(0, 0, title_width, theme_for("prompt.name"));
+        canvas.write_str(0, title_width + 1, &self.input);
+        canvas.apply_style(0, title_width, canvas.width(), theme_for("selector.input"));
+
+        // Items.
+        let first = (self.selected + 1).saturating_sub(MAX_NUM_ITEMS);
+        for (i, item_index) in self
+            .filtered_items
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(MAX_NUM_ITEMS)
+        {
+            let y = 1 + i - first;
+            let item = &self.items[*item_index];
+            canvas.write_str(y, 1, &item.label);
+            canvas.write_str(
+                y,
+                canvas
+                    .width()
+                    .saturating_sub(1 + item.detail.display_width()),
+                &item.detail,
+            );
+
+            if i == self.selected {
+                canvas.apply_style(y, 0, canvas.width(), theme_for("selector.selected"));
+            }
+        }
+    }
+
+    fn handle_key_event(
+        &mut self,
+        editor: &mut Editor,
+        compositor: &mut Compositor<Editor>,
+        key: KeyEvent,
+    ) -> HandledEvent {
+        const NONE: KeyModifiers = KeyModifiers::NONE;
+        const CTRL: KeyModifiers = KeyModifiers::CONTROL;
+        const SHIFT: KeyModifiers = KeyModifiers::SHIFT;
+
+        // Record the input so that replaying the action which opened the
+        // selector sees it. Record it before the callback records its steps.
+        editor.macros.record_key(key.code, key.modifiers);
+
+        match (key.code, key.modifiers) {
+            (KeyCode::Esc, NONE) | (KeyCode::Char('q'), CTRL) => {
+                self.close();
+            }
+            (KeyCode::Enter, NONE) => {
+                let selected = self.filtered_items.get(self.selected).copied();
+                let input = self.input.clone();
+                let callback = self.callback.take();
+                self.close();
+                match (callback, selected) {
+                    (Some(SelectorCallback::Item(callback)), Some(index)) => {
+                        callback(editor, compositor, index);
+                    }
+                    (Some(SelectorCallback::Input(callback)), _) => {
+                        callback(editor, compositor, &input);
+                    }
+                    _ => {}
+                }
+            }
+            (KeyCode::Up, NONE) | (KeyCode::Char('p'), CTRL) => {
+                self.selected = self.selected.saturating_sub(1);
+            }
+            (KeyCode::Down, NONE) | (KeyCode::Char('n'), CTRL) => {
+                self.selected = min(
+                    self.selected + 1,
+                    self.filtered_items.len().saturating_sub(1),
+                );
+            }
+            (KeyCode::Backspace, NONE) => {
+                self.input.pop();
+                self.update_filtered_items();
+            }
+            (KeyCode::Char(ch), NONE) | (KeyCode::Char(ch), SHIFT) => {
+                self.input.push(ch);
+                self.update_filtered_items();
+            }
+            _ => {}
+        }
+
+        HandledEvent::Consumed
+    }
+
+    fn handle_key_batch_event(
+        &mut self,
+        editor: &mut Editor,
+        _compositor: &mut Compositor<Editor>,
+        input: &str,
+    ) -> HandledEvent {
+        editor.macros.record(MacroStep::Text(input.to_owned()));
+        self.input.push_str(input.lines().next().unwrap_or(""));
+        self.update_filtered_items();
+        HandledEvent::Consumed
+    }
+}